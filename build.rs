@@ -0,0 +1,73 @@
+use std::{env, fs, path, process, time};
+
+// Deployed builds are told apart by the commit they were built from, builds
+// outside of a git checkout (like release tarballs) report it as "unknown".
+fn main() {
+    let git_hash = process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| String::from("unknown"));
+
+    println!("cargo:rustc-env=MCTRLRS_GIT_HASH={git_hash}");
+
+    // Reproducible builds pin the date with SOURCE_DATE_EPOCH, otherwise it is
+    // the day the build script last ran, which happens for every new commit.
+    let build_time = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default()
+        });
+
+    println!(
+        "cargo:rustc-env=MCTRLRS_BUILD_DATE={}",
+        format_date(build_time)
+    );
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // HEAD only changes when switching branches, a new commit updates the ref
+    // HEAD points to instead.
+    let git_dir = path::Path::new(".git");
+    if git_dir.join("HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+
+        if let Some(head_ref) = fs::read_to_string(git_dir.join("HEAD"))
+            .ok()
+            .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+        {
+            println!("cargo:rerun-if-changed=.git/{head_ref}");
+        }
+
+        println!("cargo:rerun-if-changed=.git/packed-refs");
+    }
+}
+
+// Converts seconds since the Unix epoch to a `YYYY-MM-DD` date in UTC, using
+// the days-to-civil algorithm by Howard Hinnant.
+fn format_date(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,233 @@
+use crate::core;
+use rand::distr::{self, SampleString};
+use std::{fs, io, io::Write, path};
+
+// The example configuration documents every option, so it doubles as the
+// template for new installations.
+const EXAMPLE_CONFIG: &str = include_str!("../../config.yml");
+// Optional settings whose example values point at files and programs that
+// most likely don't exist, the generated file leaves them commented out.
+const EXAMPLE_PATH_OPTIONS: [&str; 9] = [
+    "backups_path",
+    "ops_path",
+    "tls_key",
+    "tls_chain",
+    "tls_client_ca",
+    "session_store_path",
+    "start_command",
+    "log_path",
+    "audit_log_path",
+];
+const SECRET_LENGTH: usize = 48;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The configuration is invalid")]
+    Invalid(#[source] core::LoadConfigError),
+    #[error("The file {} already exists, pass --force to overwrite it", .0.display())]
+    AlreadyExists(path::PathBuf),
+    #[error("Failed to write the configuration file {}", .path.display())]
+    Write {
+        path: path::PathBuf,
+        source: io::Error,
+    },
+}
+
+pub fn generate(output: &path::Path, force: bool) -> Result<(), Error> {
+    let file = if force {
+        fs::File::create(output)
+    } else {
+        fs::File::create_new(output)
+    };
+
+    file.and_then(|mut file| file.write_all(example_config().as_bytes()))
+        .map_err(|source| match source.kind() {
+            io::ErrorKind::AlreadyExists => Error::AlreadyExists(output.to_owned()),
+            _ => Error::Write {
+                path: output.to_owned(),
+                source,
+            },
+        })?;
+
+    println!(
+        "An example configuration was written to {}, adjust the required options before starting the server",
+        output.display()
+    );
+
+    Ok(())
+}
+
+// The example with its own secrets, so that no two installations share the
+// published ones.
+fn example_config() -> String {
+    EXAMPLE_CONFIG
+        .lines()
+        .map(|line| {
+            let option = line.split_once(':').map(|(option, _)| option);
+
+            if option == Some("cookie_key") {
+                format!("cookie_key: {}\n", generate_secret())
+            } else if option == Some("# api_token") {
+                format!("# api_token: {}\n", generate_secret())
+            } else if option.is_some_and(|option| EXAMPLE_PATH_OPTIONS.contains(&option)) {
+                format!("# {line}\n")
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect()
+}
+
+fn generate_secret() -> String {
+    distr::Alphanumeric.sample_string(&mut rand::rng(), SECRET_LENGTH)
+}
+
+pub fn check(config_path: &path::Path) -> Result<(), Error> {
+    let config = match core::Config::load(config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            if let Some(hint) = hint(&err) {
+                eprintln!("Hint: {hint}");
+            }
+
+            return Err(Error::Invalid(err));
+        }
+    };
+    let app_config = &config.app_config;
+    let tls = match &config.tls {
+        Some(tls) if tls.client_ca.is_some() => "enabled, client certificates required",
+        Some(_) => "enabled",
+        None => "disabled",
+    };
+    let backups = match &app_config.backups {
+        Some(backups) => backups.path.display().to_string(),
+        None => "disabled".to_string(),
+    };
+    let sessions = match &config.session_store_path {
+        Some(path) => path.display().to_string(),
+        None => "kept in memory".to_string(),
+    };
+
+    println!("The configuration file {} is valid", config_path.display());
+    println!();
+    println!("Listening on:      {}", config.listen_on);
+    println!("TLS:               {tls}");
+    println!("Workers:           {}", config.worker_count);
+    println!("Base URL:          {}", app_config.base_url);
+    println!(
+        "Base path:         {}",
+        match app_config.base_path.as_str() {
+            "" => "/",
+            base_path => base_path,
+        }
+    );
+    println!("Worlds:            {}", app_config.worlds_path.display());
+    println!("Backups:           {backups}");
+    println!(
+        "Users file:        {}",
+        describe_users_file(&app_config.users_file_path, app_config.create_users_file)
+    );
+    println!(
+        "server.properties: {}",
+        app_config.server_properties_path.display()
+    );
+    println!("RCON address:      {}", app_config.rcon_address);
+    println!("Sessions:          {sessions}");
+    // Only the web server needs these, so loading the configuration doesn't
+    // check that they exist.
+    println!(
+        "Templates:         {}",
+        describe_dir(&config.templates_path)
+    );
+    println!("Static files:      {}", describe_dir(&config.static_path));
+    println!("Locales:           {}", describe_dir(&config.locales_path));
+
+    Ok(())
+}
+
+fn describe_dir(path: &path::Path) -> String {
+    if path.is_dir() {
+        path.display().to_string()
+    } else {
+        format!("{} (missing, required by the web server)", path.display())
+    }
+}
+
+fn describe_users_file(path: &path::Path, create: bool) -> String {
+    if path.is_file() {
+        path.display().to_string()
+    } else if create {
+        format!("{} (missing, created empty on start)", path.display())
+    } else {
+        format!(
+            "{} (missing, create it with `mctrlrs manage user init`)",
+            path.display()
+        )
+    }
+}
+
+// The paths are the usual stumbling block when setting things up for the
+// first time.
+fn hint(err: &core::LoadConfigError) -> Option<&'static str> {
+    match err {
+        core::LoadConfigError::CanonicalizePath { .. }
+        | core::LoadConfigError::ReadError { .. } => Some(
+            "Pass the configuration file with --config. A relative path that doesn't start with ./ or ../ is resolved against the location of the binary.",
+        ),
+        core::LoadConfigError::Validate(err) => match err {
+            core::ConfigValidationError::WorldsPath(_) => Some(
+                "worlds_path must be an existing directory that holds a directory for every world.",
+            ),
+            core::ConfigValidationError::BackupsPath(_) => {
+                Some("backups_path must be an existing directory, create it before starting.")
+            }
+            core::ConfigValidationError::UsersFilePath(_) => Some(
+                "users_file_path must be a file in an existing directory, `mctrlrs manage user init` creates it with the first user.",
+            ),
+            core::ConfigValidationError::PropertiesPath(_)
+            | core::ConfigValidationError::LoadProperties(_) => Some(
+                "server_properties_path must point to the server.properties file of the Minecraft server, with RCON enabled.",
+            ),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option<'a>(config: &'a str, name: &str) -> Option<&'a str> {
+        config
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+    }
+
+    #[test]
+    fn generated_configs_have_their_own_secrets() {
+        let first = example_config();
+        let second = example_config();
+        let cookie_key = option(&first, "cookie_key").unwrap();
+        let api_token = option(&first, "# api_token").unwrap();
+
+        assert_eq!(cookie_key.len(), SECRET_LENGTH);
+        assert_eq!(api_token.len(), SECRET_LENGTH);
+        assert_ne!(Some(cookie_key), option(EXAMPLE_CONFIG, "cookie_key"));
+        assert_ne!(Some(cookie_key), option(&second, "cookie_key"));
+        assert_ne!(Some(api_token), option(&second, "# api_token"));
+        assert!(option(&first, "api_token").is_none());
+    }
+
+    #[test]
+    fn example_paths_are_commented_out() {
+        let config = example_config();
+
+        for name in EXAMPLE_PATH_OPTIONS {
+            assert!(option(EXAMPLE_CONFIG, name).is_some(), "{name}");
+            assert!(option(&config, name).is_none(), "{name}");
+            assert!(option(&config, &format!("# {name}")).is_some(), "{name}");
+        }
+        assert_eq!(config.lines().count(), EXAMPLE_CONFIG.lines().count());
+    }
+}
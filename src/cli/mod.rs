@@ -1,2 +1,4 @@
+pub mod config;
+pub mod status;
 pub mod user;
 pub mod world;
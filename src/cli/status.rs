@@ -0,0 +1,87 @@
+use crate::core::{self, server};
+use std::net;
+use tokio_util::sync;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The Minecraft server is not reachable at {0}")]
+    Unreachable(net::SocketAddr, #[source] server::Error),
+    #[error("Failed to fetch the list of online players: {0}")]
+    List(#[source] server::Error),
+    #[error("Failed to fetch the server tick stats: {0}")]
+    TickStats(#[source] server::Error),
+}
+
+pub fn show(config: core::AppConfig) -> Result<(), Error> {
+    actix_web::rt::System::new().block_on(async {
+        let client = server::Client::new(
+            config.rcon_address,
+            config.rcon_password,
+            config.rcon_timeouts,
+            sync::CancellationToken::new(),
+        );
+
+        let players = client.players().await.map_err(|err| {
+            if err.is_unreachable() {
+                Error::Unreachable(config.rcon_address, err)
+            } else {
+                Error::List(err)
+            }
+        })?;
+        let tick_stats = client
+            .query_tick(config.tick_command)
+            .await
+            .map_err(Error::TickStats)?;
+
+        match players.len() {
+            0 => println!("There are no players online"),
+            1 => println!("There is 1 player online:"),
+            len => println!("There are {len} players online:"),
+        }
+        for player in players {
+            print_player(&player);
+        }
+
+        print_tick_stats(&tick_stats);
+
+        client.close().await;
+
+        Ok(())
+    })
+}
+
+fn print_tick_stats(tick_stats: &server::TickStats) {
+    println!("Server tick stats:");
+
+    match tick_stats {
+        server::TickStats::TickTimes(times) => {
+            println!(
+                "  Average tick: {}",
+                server::TickTimes::format(times.average)
+            );
+            println!("  Target tick: {}", server::TickTimes::format(times.target));
+            println!(
+                "  p50: {}, p95: {}, p99: {}",
+                server::TickTimes::format(times.p50),
+                server::TickTimes::format(times.p95),
+                server::TickTimes::format(times.p99)
+            );
+        }
+        server::TickStats::Tps {
+            last_1m,
+            last_5m,
+            last_15m,
+        } => println!("  TPS from last 1m: {last_1m:.1}, 5m: {last_5m:.1}, 15m: {last_15m:.1}"),
+        server::TickStats::MeanTick { tick_time_ms, tps } => {
+            println!("  Mean tick: {tick_time_ms}ms");
+            println!("  TPS: {tps}");
+        }
+    }
+}
+
+fn print_player(player: &server::Player) {
+    match &player.uuid {
+        Some(uuid) => println!("  {} ({uuid})", player.name),
+        None => println!("  {}", player.name),
+    }
+}
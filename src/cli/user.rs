@@ -1,30 +1,90 @@
 use crate::core;
+use std::io;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Invalid username")]
     InvalidUserName(#[from] core::InvalidUsernameError),
+    #[error("Invalid role")]
+    InvalidRole(#[from] core::InvalidRoleError),
     #[error("Failed to enroll the user: {}", .0)]
     FailedToEnrol(#[source] core::ManageUsersError),
     #[error("Failed to remove the user: {}", .0)]
     FailedToDelete(#[source] core::ManageUsersError),
+    #[error("Failed to set the password: {}", .0)]
+    FailedToSetPassword(#[source] core::ManageUsersError),
+    #[error("Failed to set the role: {}", .0)]
+    FailedToSetRole(#[source] core::ManageUsersError),
+    #[error("Failed to initialize the users: {}", .0)]
+    FailedToInitialize(#[source] core::ManageUsersError),
+    #[error(
+        "The users file already contains {} user(s), use `enroll` to add another one or pass --force to replace them",
+        .0
+    )]
+    AlreadyInitialized(usize),
+    #[error("Failed to read the password: {}", .0)]
+    ReadPassword(#[source] io::Error),
+    #[error("Passwords do not match")]
+    PasswordMismatch,
+    #[error("Invalid password: {}", .0)]
+    InvalidPassword(#[source] core::PasswordError),
 }
 
-pub fn enroll(config: core::AppConfig, username: String) -> Result<(), Error> {
+pub fn enroll(config: core::AppConfig, username: String, role: String) -> Result<(), Error> {
     let username: core::Username = username.try_into()?;
+    let role: core::Role = role.parse()?;
 
-    let users = core::Users::load(config.users_file_path).map_err(Error::FailedToEnrol)?;
-    let token = users.enroll_user(username).map_err(Error::FailedToEnrol)?;
+    let users = if config.create_users_file {
+        core::Users::load_or_create(&config.users_file_path)
+    } else {
+        core::Users::load(&config.users_file_path)
+    }
+    .map_err(Error::FailedToEnrol)?;
+    let token = users
+        .enroll_user(username, role)
+        .map_err(Error::FailedToEnrol)?;
 
-    let mut url = config.base_url;
-    url.set_path("/enroll");
-    url.set_query(Some(&format!("token={}", token.reveal())));
+    print_enroll_url(config, &token);
 
-    println!("To finish the enrollment visit {}", url);
+    Ok(())
+}
+
+pub fn init(
+    config: core::AppConfig,
+    username: String,
+    role: String,
+    force: bool,
+) -> Result<(), Error> {
+    let username: core::Username = username.try_into()?;
+    let role: core::Role = role.parse()?;
+
+    let token = core::Users::initialize(&config.users_file_path, username.clone(), role, force)
+        .map_err(|err| match err {
+            core::ManageUsersError::AlreadyInitialized(count) => Error::AlreadyInitialized(count),
+            err => Error::FailedToInitialize(err),
+        })?;
+
+    println!(
+        "The users file {} was created with user {}",
+        config.users_file_path.display(),
+        username
+    );
+    print_enroll_url(config, &token);
 
     Ok(())
 }
 
+fn print_enroll_url(config: core::AppConfig, token: &core::EnrollToken) {
+    println!(
+        "To finish the enrollment visit {}",
+        config.enroll_url(token)
+    );
+    println!(
+        "The link expires in {} hours",
+        config.enroll_token_ttl.as_secs() / 60 / 60
+    );
+}
+
 pub fn remove(config: core::AppConfig, username: String) -> Result<(), Error> {
     let username: core::Username = username.try_into()?;
 
@@ -35,3 +95,50 @@ pub fn remove(config: core::AppConfig, username: String) -> Result<(), Error> {
 
     Ok(())
 }
+
+pub fn set_password(config: core::AppConfig, username: String) -> Result<(), Error> {
+    let username: core::Username = username.try_into()?;
+
+    let users = core::Users::load(&config.users_file_path).map_err(Error::FailedToSetPassword)?;
+    if users.find_user_by_username(&username).is_none() {
+        return Err(Error::FailedToSetPassword(
+            core::ManageUsersError::NoSuchUser(username.to_string()),
+        ));
+    }
+
+    let password = rpassword::prompt_password(format!("New password for {username}: "))
+        .map_err(Error::ReadPassword)?;
+    let repassword =
+        rpassword::prompt_password("Repeat the password: ").map_err(Error::ReadPassword)?;
+
+    if password != repassword {
+        return Err(Error::PasswordMismatch);
+    }
+
+    let password = core::Password::new(secrecy::SecretString::from(password), &config)
+        .map_err(Error::InvalidPassword)?;
+    users
+        .update_password(&username, password)
+        .map_err(Error::FailedToSetPassword)?;
+
+    println!(
+        "The password for user {} was successfully updated",
+        username
+    );
+
+    Ok(())
+}
+
+pub fn set_role(config: core::AppConfig, username: String, role: String) -> Result<(), Error> {
+    let username: core::Username = username.try_into()?;
+    let role: core::Role = role.parse()?;
+
+    let users = core::Users::load(config.users_file_path).map_err(Error::FailedToSetRole)?;
+    users
+        .set_role(&username, role)
+        .map_err(Error::FailedToSetRole)?;
+
+    println!("The role of user {} was set to {}", username, role);
+
+    Ok(())
+}
@@ -12,8 +12,12 @@ pub enum Error {
 }
 
 pub fn list(config: core::AppConfig) -> Result<(), Error> {
-    let worlds = core::Worlds::new(&config.worlds_path, &config.server_properties_path)
-        .map_err(Error::LoadWorlds)?;
+    let worlds = core::Worlds::new(
+        &config.worlds_path,
+        &config.server_properties_path,
+        config.allow_empty_worlds,
+    )
+    .map_err(Error::LoadWorlds)?;
 
     println!("The following worlds are currently available:");
     for world in worlds.list() {
@@ -31,12 +35,17 @@ pub fn list(config: core::AppConfig) -> Result<(), Error> {
 
 pub fn switch(config: core::AppConfig, world_name: String) -> Result<(), Error> {
     actix_web::rt::System::new().block_on(async {
-        let worlds = core::Worlds::new(&config.worlds_path, &config.server_properties_path)
-            .map_err(Error::LoadWorlds)?;
+        let worlds = core::Worlds::new(
+            &config.worlds_path,
+            &config.server_properties_path,
+            config.allow_empty_worlds,
+        )
+        .map_err(Error::LoadWorlds)?;
 
         let client = server::Client::new(
             config.rcon_address,
             config.rcon_password,
+            config.rcon_timeouts,
             sync::CancellationToken::new(),
         );
         client
@@ -50,12 +59,30 @@ pub fn switch(config: core::AppConfig, world_name: String) -> Result<(), Error>
             .with_context(|| "Failed to shut down the server before switching")
             .map_err(Error::Switch)?;
 
+        if let Some(backups) = &config.backups {
+            match worlds.backup(&backups.path) {
+                Ok(path) => println!("The current world was backed up to {}", path.display()),
+                Err(err) if backups.best_effort => {
+                    tracing::warn!("Proceeding with the switch without a backup: {err}")
+                }
+                Err(err) => return Err(Error::Switch(err.into())),
+            }
+        }
+
         let world = worlds
             .switch(world_name)
             .map_err(|e| Error::Switch(e.into()))?;
 
         println!("The currently active world was changed to: {}", world.id(),);
 
+        if config.auto_start
+            && let Some(command) = &config.start_command
+        {
+            server::start(command).map_err(|e| Error::Switch(e.into()))?;
+
+            println!("The Minecraft server is starting");
+        }
+
         Ok(())
     })
 }
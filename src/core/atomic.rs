@@ -0,0 +1,102 @@
+use rand::distr::{self, SampleString};
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path,
+};
+
+// Writes the contents into a temporary file next to the target and renames it
+// over the target, so a crash or a failed write never leaves a truncated file
+// behind: readers either see the old contents or the new ones.
+pub fn write(path: &path::Path, contents: &[u8]) -> io::Result<()> {
+    write_with(path, |file| file.write_all(contents))
+}
+
+fn write_with<F>(path: &path::Path, write_contents: F) -> io::Result<()>
+where
+    F: FnOnce(&mut fs::File) -> io::Result<()>,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The path has no file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => path::Path::new("."),
+    };
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.tmp",
+        distr::Alphanumeric.sample_string(&mut rand::rng(), 8)
+    ));
+    let temp_path = dir.join(temp_name);
+
+    let result =
+        write_temp(path, &temp_path, write_contents).and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // The rename itself is only durable once the directory entry is synced.
+    fs::File::open(dir)?.sync_all()
+}
+
+fn write_temp<F>(path: &path::Path, temp_path: &path::Path, write_contents: F) -> io::Result<()>
+where
+    F: FnOnce(&mut fs::File) -> io::Result<()>,
+{
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(temp_path)?;
+
+    // The replacement keeps the permissions of the original, and until they
+    // are copied over nobody but the owner can read it, the users file holds
+    // password hashes.
+    match fs::metadata(path) {
+        Ok(metadata) => file.set_permissions(metadata.permissions())?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    write_contents(&mut file)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[test]
+    fn write_replaces_the_contents() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("users.yml", "old");
+
+        write(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_write_leaves_the_original_intact() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("users.yml", "old");
+
+        let result = write_with(&path, |file| {
+            file.write_all(b"partial")?;
+
+            Err(io::Error::other("disk full"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        // The temporary file is cleaned up as well.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+use super::log;
+use std::{collections::BTreeMap, fs, io, io::Write, path, sync};
+
+const TIMESTAMP: &[time::format_description::BorrowedFormatItem<'_>] =
+    time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    pub username: String,
+    pub action: String,
+    #[serde(default)]
+    pub details: BTreeMap<String, String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuditError {
+    #[error("Failed to write to the audit log {}", .path.display())]
+    Write {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to read the audit log {}", .path.display())]
+    Read {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to serialize the audit log entry")]
+    Serialize(#[source] serde_json::Error),
+    #[error("Failed to format the timestamp of the audit log entry")]
+    Timestamp(#[source] time::error::Format),
+}
+
+pub struct AuditLog {
+    path: Option<path::PathBuf>,
+    lock: sync::Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<path::PathBuf>) -> Self {
+        Self {
+            path,
+            lock: sync::Mutex::new(()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    // Every entry is a single JSON line written with one call, so the file
+    // stays readable by other tools even while it is being appended to.
+    pub fn record(
+        &self,
+        username: &str,
+        action: &str,
+        details: &[(&str, &str)],
+    ) -> Result<(), AuditError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let entry = AuditEntry {
+            timestamp: time::OffsetDateTime::now_utc()
+                .format(TIMESTAMP)
+                .map_err(AuditError::Timestamp)?,
+            username: username.to_string(),
+            action: action.to_string(),
+            details: details
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(AuditError::Serialize)?;
+        line.push(b'\n');
+
+        let _guard = self
+            .lock
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner);
+
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|source| AuditError::Write {
+                path: path.clone(),
+                source,
+            })
+    }
+
+    // Returns the entries newest first, skipping the `offset` most recent ones.
+    // Lines that can't be parsed are skipped rather than failing the page.
+    pub fn recent(&self, offset: usize, limit: usize) -> Result<Vec<AuditEntry>, AuditError> {
+        let Some(path) = &self.path else {
+            return Ok(vec![]);
+        };
+
+        let lines = match log::tail(path, offset.saturating_add(limit)) {
+            Ok(lines) => lines,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(source) => Err(AuditError::Read {
+                path: path.clone(),
+                source,
+            })?,
+        };
+
+        Ok(lines
+            .iter()
+            .rev()
+            .skip(offset)
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
@@ -1,24 +1,196 @@
-use super::properties;
-use actix_web::cookie;
+use super::{properties, server, user};
+use actix_web::{cookie, http};
 use secrecy::ExposeSecret;
-use std::{env, fs, io, net, num, path};
+use std::{env, fs, io, net, num, path, thread, time};
+
+const ENV_PREFIX: &str = "MCTRLRS_";
 
 #[derive(serde::Deserialize)]
 struct ConfigFile {
     listen_on: net::SocketAddr,
     worlds_path: path::PathBuf,
+    backups_path: Option<path::PathBuf>,
+    #[serde(default)]
+    backup_best_effort: bool,
+    #[serde(default)]
+    allow_empty_worlds: bool,
     users_file_path: path::PathBuf,
+    #[serde(default)]
+    create_users_file: bool,
     base_url: url::Url,
+    base_path: Option<String>,
     #[serde(default = "default_min_password_len")]
     min_password_length: u8,
     #[serde(default = "default_max_password_len")]
     max_password_length: u8,
+    min_password_score: Option<u8>,
+    #[serde(default)]
+    password_required_classes: Vec<user::CharacterClass>,
+    #[serde(default = "default_password_min_classes")]
+    password_min_classes: u8,
     server_properties_path: path::PathBuf,
+    ops_path: Option<path::PathBuf>,
+    #[serde(default = "default_rcon_host")]
+    rcon_host: net::IpAddr,
+    rcon_password_env: Option<String>,
+    rcon_password_file: Option<path::PathBuf>,
     tls_key: Option<path::PathBuf>,
     tls_chain: Option<path::PathBuf>,
-    worker_count: Option<num::NonZeroUsize>,
+    tls_client_ca: Option<path::PathBuf>,
+    worker_count: Option<WorkerCount>,
     cookie_key: Option<secrecy::SecretString>,
-    session_store_path: path::PathBuf,
+    api_token: Option<secrecy::SecretString>,
+    session_store_path: Option<path::PathBuf>,
+    #[serde(default = "default_enroll_token_ttl")]
+    enroll_token_ttl: u64,
+    #[serde(default = "default_rcon_connect_timeout")]
+    rcon_connect_timeout: u64,
+    #[serde(default = "default_rcon_read_timeout")]
+    rcon_read_timeout: u64,
+    #[serde(default = "default_rcon_write_timeout")]
+    rcon_write_timeout: u64,
+    start_command: Option<Vec<String>>,
+    #[serde(default)]
+    auto_start: bool,
+    #[serde(default)]
+    flush_before_switch: bool,
+    console_allowed_commands: Option<Vec<String>>,
+    console_denied_commands: Option<Vec<String>>,
+    #[serde(default)]
+    tick_command: server::TickCommand,
+    log_path: Option<path::PathBuf>,
+    audit_log_path: Option<path::PathBuf>,
+    rate_limit: Option<num::NonZeroU32>,
+    trusted_proxy_header: Option<String>,
+    #[serde(default = "default_trusted_proxies")]
+    trusted_proxies: Vec<net::IpAddr>,
+    #[serde(default = "default_content_security_policy")]
+    content_security_policy: String,
+    #[serde(default = "default_templates_path")]
+    templates_path: path::PathBuf,
+    #[serde(default)]
+    reload_templates: bool,
+    site_title: Option<String>,
+    logo_path: Option<String>,
+    #[serde(default = "default_locales_path")]
+    locales_path: path::PathBuf,
+    #[serde(default = "default_locale")]
+    default_locale: String,
+    #[serde(default = "default_static_path")]
+    static_path: path::PathBuf,
+    #[serde(default = "default_session_ttl_minutes")]
+    session_ttl_minutes: u64,
+    #[serde(default)]
+    session_ttl_extension: SessionTtlExtension,
+    #[serde(default = "default_remember_me_days")]
+    remember_me_days: u64,
+    session_idle_minutes: Option<u64>,
+    #[serde(default)]
+    log_format: LogFormat,
+    #[serde(default)]
+    log_level: LogLevel,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum WorkerCount {
+    Count(num::NonZeroUsize),
+    Keyword(String),
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionTtlExtension {
+    #[default]
+    OnEveryRequest,
+    OnStateChanges,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+fn default_rcon_host() -> net::IpAddr {
+    net::IpAddr::V4(net::Ipv4Addr::LOCALHOST)
+}
+
+fn default_trusted_proxies() -> Vec<net::IpAddr> {
+    vec![
+        net::IpAddr::V4(net::Ipv4Addr::LOCALHOST),
+        net::IpAddr::V6(net::Ipv6Addr::LOCALHOST),
+    ]
+}
+
+fn default_enroll_token_ttl() -> u64 {
+    24
+}
+
+fn default_rcon_connect_timeout() -> u64 {
+    5
+}
+
+fn default_rcon_read_timeout() -> u64 {
+    10
+}
+
+fn default_rcon_write_timeout() -> u64 {
+    10
+}
+
+fn default_session_ttl_minutes() -> u64 {
+    15
+}
+
+fn default_remember_me_days() -> u64 {
+    30
+}
+
+fn default_templates_path() -> path::PathBuf {
+    path::PathBuf::from("./templates/")
+}
+
+fn default_locales_path() -> path::PathBuf {
+    path::PathBuf::from("./locales/")
+}
+
+fn default_locale() -> String {
+    String::from("en")
+}
+
+fn default_static_path() -> path::PathBuf {
+    path::PathBuf::from("./static/")
+}
+
+fn default_content_security_policy() -> String {
+    "default-src 'self'; img-src 'self' data:; frame-ancestors 'none'; form-action 'self'"
+        .to_string()
 }
 
 fn default_min_password_len() -> u8 {
@@ -29,6 +201,10 @@ fn default_max_password_len() -> u8 {
     128
 }
 
+fn default_password_min_classes() -> u8 {
+    3
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum LoadConfigError {
     #[error("Failed to obtain current working directory")]
@@ -37,6 +213,15 @@ pub enum LoadConfigError {
     ExecutablePath(#[source] io::Error),
     #[error("Failed to parse configuration file")]
     ParseFailure(#[from] serde_yaml_ng::Error),
+    #[error("Failed to parse configuration file")]
+    ParseTomlFailure(#[from] toml::de::Error),
+    #[error(
+        "Unrecognized configuration file extension for {}, expected .yaml, .yml or .toml",
+        .0.display()
+    )]
+    UnsupportedExtension(path::PathBuf),
+    #[error("The configuration file must contain a mapping of options")]
+    NotAMapping,
     #[error("Failed to read configuration file contents {}", .path.display())]
     ReadError {
         path: path::PathBuf,
@@ -56,45 +241,134 @@ pub enum LoadConfigError {
 pub enum ConfigValidationError {
     #[error("Invalid worlds path: {0}")]
     WorldsPath(String),
+    #[error("Invalid backups path: {0}")]
+    BackupsPath(String),
     #[error("Invalid users file path: {0}")]
     UsersFilePath(String),
     #[error("Invalid base URL: {0}")]
     InvalidBaseUrl(url::Url),
+    #[error("The base path must be an absolute path like /minecraft, got: {0}")]
+    BasePath(String),
     #[error("Invalid server.properties path: {}", .0.display())]
     PropertiesPath(path::PathBuf),
+    #[error("Invalid ops.json path: {0}")]
+    OpsPath(String),
+    #[error("Invalid RCON password: {0}")]
+    RconPassword(String),
     #[error("Unable to load server.properties file")]
     LoadProperties(#[source] properties::Error),
     #[error("Invalid TLS configuration: {0}")]
     Tls(String),
     #[error("Cookie key must be at least 32 bytes long, got: {0}")]
     CookieKey(usize),
+    #[error("API token must be at least 32 bytes long, got: {0}")]
+    ApiToken(usize),
+    #[error("Invalid start command: {0}")]
+    StartCommand(#[source] server::StartError),
+    #[error("`auto_start` requires `start_command` to be configured")]
+    AutoStart,
+    #[error("Invalid console command filter: {0}")]
+    ConsoleFilter(String),
+    #[error("Invalid log path: {0}")]
+    LogPath(String),
+    #[error("Invalid audit log path: {0}")]
+    AuditLogPath(String),
+    #[error("Invalid trusted proxy header name: {0}")]
+    TrustedProxyHeader(String),
+    #[error("Invalid content security policy: {0}")]
+    ContentSecurityPolicy(String),
+    #[error("Invalid templates path: {0}")]
+    TemplatesPath(String),
+    #[error("Invalid static files path: {0}")]
+    StaticPath(String),
+    #[error("Invalid locales path: {0}")]
+    LocalesPath(String),
+    #[error("The site title can't be blank")]
+    SiteTitle,
+    #[error("The logo path must be an absolute path or an http(s) URL, got: {0}")]
+    LogoPath(String),
+    #[error(r#"Worker count must be a positive number or "auto", got: {0}"#)]
+    WorkerCount(String),
+    #[error("Session TTL must be a positive number of minutes")]
+    SessionTtl,
+    #[error("Enroll token TTL must be a positive number of hours")]
+    EnrollTokenTtl,
+    #[error("Remember me duration must be a positive number of days")]
+    RememberMeDays,
+    #[error("Session idle timeout must be a positive number of minutes")]
+    SessionIdle,
+    #[error("Minimum password score must be between 0 and 4, got: {0}")]
+    MinPasswordScore(u8),
+    #[error("Password minimum character classes must be between 0 and 4, got: {0}")]
+    PasswordMinClasses(u8),
     #[error("Unable to resolve the session storage file path: {0}")]
     SessionStorePath(String),
 }
 
+pub struct BackupConfig {
+    pub path: path::PathBuf,
+    pub best_effort: bool,
+}
+
 pub struct AppConfig {
     pub worlds_path: path::PathBuf,
+    pub allow_empty_worlds: bool,
+    pub backups: Option<BackupConfig>,
     pub rcon_address: net::SocketAddr,
     pub users_file_path: path::PathBuf,
+    pub create_users_file: bool,
     pub base_url: url::Url,
+    pub base_path: String,
     pub min_password_length: usize,
     pub max_password_length: usize,
+    pub min_password_score: Option<zxcvbn::Score>,
+    pub password_required_classes: Vec<user::CharacterClass>,
+    pub password_min_classes: usize,
     pub server_properties_path: path::PathBuf,
+    pub ops_path: path::PathBuf,
     pub rcon_password: secrecy::SecretString,
+    pub rcon_timeouts: server::Timeouts,
+    pub enroll_token_ttl: time::Duration,
+    pub api_token: Option<secrecy::SecretString>,
+    pub start_command: Option<server::StartCommand>,
+    pub flush_before_switch: bool,
+    pub console_filter: server::CommandFilter,
+    pub tick_command: server::TickCommand,
+    pub auto_start: bool,
+    pub log_path: Option<path::PathBuf>,
+    pub audit_log_path: Option<path::PathBuf>,
+    pub site_title: Option<String>,
+    pub logo_path: Option<String>,
 }
 
 pub struct TlsConfig {
     pub key: path::PathBuf,
     pub chain: path::PathBuf,
+    pub client_ca: Option<Vec<rustls::pki_types::CertificateDer<'static>>>,
 }
 
 pub struct Config {
     pub listen_on: net::SocketAddr,
     pub app_config: AppConfig,
     pub tls: Option<TlsConfig>,
-    pub worker_count: Option<num::NonZeroUsize>,
+    pub worker_count: num::NonZeroUsize,
     pub cookie_key: Option<secrecy::SecretBox<str>>,
-    pub session_store_path: path::PathBuf,
+    pub session_store_path: Option<path::PathBuf>,
+    pub rate_limit: Option<num::NonZeroU32>,
+    pub trusted_proxy_header: Option<http::header::HeaderName>,
+    pub trusted_proxies: Vec<net::IpAddr>,
+    pub content_security_policy: http::header::HeaderValue,
+    pub templates_path: path::PathBuf,
+    pub reload_templates: bool,
+    pub locales_path: path::PathBuf,
+    pub default_locale: String,
+    pub static_path: path::PathBuf,
+    pub session_ttl: cookie::time::Duration,
+    pub session_ttl_extension: SessionTtlExtension,
+    pub session_idle_timeout: Option<time::Duration>,
+    pub remember_me_ttl: cookie::time::Duration,
+    pub log_format: LogFormat,
+    pub log_level: tracing::Level,
 }
 
 impl Config {
@@ -107,71 +381,285 @@ impl Config {
     }
 }
 
+impl AppConfig {
+    // The token is passed in the query string, so the link works no matter
+    // whether the user is logged in somewhere else in the same browser.
+    pub fn enroll_url(&self, token: &user::EnrollToken) -> url::Url {
+        let mut url = self.base_url.clone();
+        url.set_path(&format!("{}/enroll", self.base_path));
+        url.set_query(Some(&format!("token={}", token.reveal())));
+
+        url
+    }
+}
+
 impl Config {
     pub fn load<P: AsRef<path::Path>>(path: P) -> Result<Self, LoadConfigError> {
         let path = canonicalize_path(path)?;
-        let config_reader =
-            fs::File::open(&path).map_err(|source| LoadConfigError::ReadError { path, source })?;
+        let config: serde_yaml_ng::Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => {
+                let config_reader = fs::File::open(&path)
+                    .map_err(|source| LoadConfigError::ReadError { path, source })?;
+
+                serde_yaml_ng::from_reader(config_reader).map_err(LoadConfigError::ParseFailure)?
+            }
+            Some("toml") => {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|source| LoadConfigError::ReadError { path, source })?;
+                let config: toml::Value =
+                    toml::from_str(&contents).map_err(LoadConfigError::ParseTomlFailure)?;
+
+                serde_yaml_ng::to_value(config).map_err(LoadConfigError::ParseFailure)?
+            }
+            _ => Err(LoadConfigError::UnsupportedExtension(path))?,
+        };
+        let config = apply_env_overrides(config, env::vars())?;
         let config: ConfigFile =
-            serde_yaml_ng::from_reader(config_reader).map_err(LoadConfigError::ParseFailure)?;
+            serde_yaml_ng::from_value(config).map_err(LoadConfigError::ParseFailure)?;
 
         config.try_into().map_err(LoadConfigError::Validate)
     }
 }
 
+// Every `MCTRLRS_<OPTION>` environment variable overrides the `<option>` value
+// from the configuration file. The values are parsed as YAML, so lists like
+// `start_command` can be set as `["program", "arg"]`.
+fn apply_env_overrides(
+    config: serde_yaml_ng::Value,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<serde_yaml_ng::Value, LoadConfigError> {
+    let mut config = match config {
+        serde_yaml_ng::Value::Mapping(mapping) => mapping,
+        serde_yaml_ng::Value::Null => serde_yaml_ng::Mapping::new(),
+        _ => Err(LoadConfigError::NotAMapping)?,
+    };
+
+    for (name, value) in vars {
+        if let Some(option) = name.strip_prefix(ENV_PREFIX) {
+            let value = serde_yaml_ng::from_str(&value)
+                .unwrap_or_else(|_| serde_yaml_ng::Value::String(value));
+
+            config.insert(option.to_lowercase().into(), value);
+        }
+    }
+
+    Ok(serde_yaml_ng::Value::Mapping(config))
+}
+
 impl TryFrom<ConfigFile> for Config {
     type Error = ConfigValidationError;
 
     fn try_from(config: ConfigFile) -> Result<Self, Self::Error> {
         let worlds_path = resolve_worlds_path(config.worlds_path)?;
+        let backups = resolve_backup_config(config.backups_path, config.backup_best_effort)?;
         let users_file_path = resolve_users_file_path(config.users_file_path)?;
         let base_url = check_base_url(config.base_url)?;
+        let base_path = check_base_path(config.base_path)?;
         let min_password_length = config.min_password_length.into();
         let max_password_length = config.max_password_length.into();
+        let min_password_score = check_min_password_score(config.min_password_score)?;
+        let password_min_classes = check_password_min_classes(config.password_min_classes)?;
         let server_properties_path =
             resolve_server_properties_file_path(config.server_properties_path)?;
         let rcon_properties = load_server_properties(&server_properties_path)?;
-        let tls = resolve_tls_config(config.tls_key, config.tls_chain)?;
+        let rcon_password = resolve_rcon_password(
+            config.rcon_password_env,
+            config.rcon_password_file,
+            rcon_properties.password,
+        )?;
+        let ops_path = resolve_ops_path(config.ops_path, &server_properties_path)?;
+        let tls = resolve_tls_config(config.tls_key, config.tls_chain, config.tls_client_ca)?;
         let cookie_key = check_cookie_key(config.cookie_key)?;
+        let api_token = check_api_token(config.api_token)?;
         let session_store_path = resolve_session_store_path(config.session_store_path)?;
+        let start_command = check_start_command(config.start_command, config.auto_start)?;
+        let console_filter = check_console_filter(
+            config.console_allowed_commands,
+            config.console_denied_commands,
+        )?;
+        let log_path = resolve_log_path(config.log_path)?;
+        let audit_log_path = resolve_audit_log_path(config.audit_log_path)?;
+        let trusted_proxy_header = check_trusted_proxy_header(config.trusted_proxy_header)?;
+        let site_title = check_site_title(config.site_title)?;
+        let logo_path = check_logo_path(config.logo_path, &base_path)?;
+        let templates_path = resolve_directory(config.templates_path)
+            .map_err(ConfigValidationError::TemplatesPath)?;
+        let static_path =
+            resolve_directory(config.static_path).map_err(ConfigValidationError::StaticPath)?;
+        let locales_path =
+            resolve_directory(config.locales_path).map_err(ConfigValidationError::LocalesPath)?;
+        let enroll_token_ttl = check_enroll_token_ttl(config.enroll_token_ttl)?;
+        let session_ttl = check_session_ttl(config.session_ttl_minutes)?;
+        let remember_me_ttl = check_remember_me_ttl(config.remember_me_days)?;
+        let session_idle_timeout = check_session_idle_timeout(config.session_idle_minutes)?;
+        let worker_count = resolve_worker_count(config.worker_count)?;
+        let content_security_policy =
+            http::header::HeaderValue::try_from(&config.content_security_policy).map_err(|_| {
+                ConfigValidationError::ContentSecurityPolicy(config.content_security_policy)
+            })?;
 
         Ok(Self {
             listen_on: config.listen_on,
             tls,
             app_config: AppConfig {
                 worlds_path,
+                allow_empty_worlds: config.allow_empty_worlds,
+                backups,
                 users_file_path,
+                create_users_file: config.create_users_file,
                 base_url,
+                base_path,
                 min_password_length,
                 max_password_length,
+                min_password_score,
+                password_required_classes: config.password_required_classes,
+                password_min_classes,
                 server_properties_path,
-                rcon_address: net::SocketAddr::from((
-                    net::Ipv4Addr::new(127, 0, 0, 1),
-                    rcon_properties.port,
-                )),
-                rcon_password: rcon_properties.password,
+                ops_path,
+                rcon_address: net::SocketAddr::from((config.rcon_host, rcon_properties.port)),
+                rcon_password,
+                rcon_timeouts: server::Timeouts {
+                    connect: time::Duration::from_secs(config.rcon_connect_timeout),
+                    read: time::Duration::from_secs(config.rcon_read_timeout),
+                    write: time::Duration::from_secs(config.rcon_write_timeout),
+                },
+                enroll_token_ttl,
+                api_token,
+                start_command,
+                flush_before_switch: config.flush_before_switch,
+                console_filter,
+                tick_command: config.tick_command,
+                auto_start: config.auto_start,
+                log_path,
+                audit_log_path,
+                site_title,
+                logo_path,
             },
-            worker_count: config.worker_count,
+            worker_count,
             cookie_key,
             session_store_path,
+            rate_limit: config.rate_limit,
+            trusted_proxy_header,
+            trusted_proxies: config.trusted_proxies,
+            content_security_policy,
+            templates_path,
+            reload_templates: config.reload_templates,
+            locales_path,
+            default_locale: config.default_locale,
+            static_path,
+            session_ttl,
+            session_ttl_extension: config.session_ttl_extension,
+            session_idle_timeout,
+            remember_me_ttl,
+            log_format: config.log_format,
+            log_level: config.log_level.into(),
         })
     }
 }
 
 fn resolve_session_store_path(
-    session_store_path: path::PathBuf,
-) -> Result<path::PathBuf, ConfigValidationError> {
-    relative_path_to_absolute(session_store_path)
-        .map_err(|err| ConfigValidationError::SessionStorePath(err.to_string()))
+    session_store_path: Option<path::PathBuf>,
+) -> Result<Option<path::PathBuf>, ConfigValidationError> {
+    session_store_path
+        .map(|path| {
+            relative_path_to_absolute(path)
+                .map_err(|err| ConfigValidationError::SessionStorePath(err.to_string()))
+        })
+        .transpose()
+}
+
+fn check_trusted_proxy_header(
+    header: Option<String>,
+) -> Result<Option<http::header::HeaderName>, ConfigValidationError> {
+    header
+        .map(|header| {
+            http::header::HeaderName::try_from(&header)
+                .map_err(|_| ConfigValidationError::TrustedProxyHeader(header))
+        })
+        .transpose()
+}
+
+fn check_site_title(title: Option<String>) -> Result<Option<String>, ConfigValidationError> {
+    match title {
+        Some(title) if title.trim().is_empty() => Err(ConfigValidationError::SiteTitle),
+        title => Ok(title.map(|title| title.trim().to_string())),
+    }
+}
+
+// The logo is linked from the pages as is, so it has to be either a path on
+// this server, which gets the base path in front of it, or an absolute web URL.
+fn check_logo_path(
+    logo_path: Option<String>,
+    base_path: &str,
+) -> Result<Option<String>, ConfigValidationError> {
+    logo_path
+        .map(|logo_path| {
+            if logo_path.starts_with('/') {
+                Ok(format!("{base_path}{logo_path}"))
+            } else if url::Url::parse(&logo_path)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+            {
+                Ok(logo_path)
+            } else {
+                Err(ConfigValidationError::LogoPath(logo_path))
+            }
+        })
+        .transpose()
+}
+
+fn resolve_log_path(
+    log_path: Option<path::PathBuf>,
+) -> Result<Option<path::PathBuf>, ConfigValidationError> {
+    log_path
+        .map(|path| {
+            relative_path_to_absolute(path)
+                .map_err(|err| ConfigValidationError::LogPath(err.to_string()))
+        })
+        .transpose()
+}
+
+fn resolve_audit_log_path(
+    audit_log_path: Option<path::PathBuf>,
+) -> Result<Option<path::PathBuf>, ConfigValidationError> {
+    audit_log_path
+        .map(|path| {
+            relative_path_to_absolute(path)
+                .map_err(|err| ConfigValidationError::AuditLogPath(err.to_string()))
+        })
+        .transpose()
+}
+
+fn check_start_command(
+    start_command: Option<Vec<String>>,
+    auto_start: bool,
+) -> Result<Option<server::StartCommand>, ConfigValidationError> {
+    match start_command {
+        Some(command) => Ok(Some(
+            command
+                .try_into()
+                .map_err(ConfigValidationError::StartCommand)?,
+        )),
+        None if auto_start => Err(ConfigValidationError::AutoStart),
+        None => Ok(None),
+    }
 }
 
 fn resolve_tls_config(
     key: Option<path::PathBuf>,
     chain: Option<path::PathBuf>,
+    client_ca: Option<path::PathBuf>,
 ) -> Result<Option<TlsConfig>, ConfigValidationError> {
-    match (key, chain) {
-        (Some(key), Some(chain)) => Ok(Some(TlsConfig { key, chain })),
-        (None, None) => Ok(None),
+    match (key, chain, client_ca) {
+        (Some(key), Some(chain), client_ca) => Ok(Some(TlsConfig {
+            key,
+            chain,
+            client_ca: client_ca.map(load_client_ca).transpose()?,
+        })),
+        (None, None, None) => Ok(None),
+        (None, None, Some(_)) => Err(ConfigValidationError::Tls(
+            "`tls_client_ca` requires both `tls_key` and `tls_chain` options to be present"
+                .to_string(),
+        ))?,
         _ => Err(ConfigValidationError::Tls(
             "Both `tls_key` and `tls_chain` options need to be either present or absent"
                 .to_string(),
@@ -179,6 +667,125 @@ fn resolve_tls_config(
     }
 }
 
+fn load_client_ca(
+    path: path::PathBuf,
+) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>, ConfigValidationError> {
+    let path =
+        canonicalize_path(path).map_err(|err| ConfigValidationError::Tls(err.to_string()))?;
+    let file = fs::File::open(&path).map_err(|e| {
+        ConfigValidationError::Tls(format!(
+            "Failed to open a client CA file `{}`: {e}",
+            path.display()
+        ))
+    })?;
+
+    let certs = rustls_pemfile::certs(&mut io::BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            ConfigValidationError::Tls(format!(
+                "Failed to parse a client CA file `{}`: {e}",
+                path.display()
+            ))
+        })?;
+
+    if certs.is_empty() {
+        Err(ConfigValidationError::Tls(format!(
+            "No certificates found in a client CA file `{}`",
+            path.display()
+        )))
+    } else {
+        Ok(certs)
+    }
+}
+
+// The password configured outside of server.properties takes precedence, so it
+// can be kept out of a world-readable file. The value itself is never part of
+// the error messages.
+fn resolve_rcon_password(
+    env_var: Option<String>,
+    file: Option<path::PathBuf>,
+    properties_password: Option<secrecy::SecretString>,
+) -> Result<secrecy::SecretString, ConfigValidationError> {
+    let password = match (env_var, file) {
+        (Some(_), Some(_)) => Err(ConfigValidationError::RconPassword(
+            "Only one of `rcon_password_env` and `rcon_password_file` can be set".to_string(),
+        ))?,
+        (Some(name), None) => env::var(&name)
+            .map(secrecy::SecretString::from)
+            .map_err(|_| {
+                ConfigValidationError::RconPassword(format!(
+                    "The `{name}` environment variable is not set or is not valid unicode"
+                ))
+            })?,
+        (None, Some(path)) => {
+            let path = canonicalize_path(path)
+                .map_err(|err| ConfigValidationError::RconPassword(err.to_string()))?;
+            let password = fs::read_to_string(&path).map_err(|err| {
+                ConfigValidationError::RconPassword(format!(
+                    "Failed to read `{}`: {err}",
+                    path.display()
+                ))
+            })?;
+
+            secrecy::SecretString::from(password.trim_end_matches(['\r', '\n']).to_string())
+        }
+        (None, None) => properties_password.ok_or_else(|| {
+            ConfigValidationError::RconPassword(
+                "The server.properties does not contain an rcon.password property".to_string(),
+            )
+        })?,
+    };
+
+    if password.expose_secret().is_empty() {
+        Err(ConfigValidationError::RconPassword(
+            "The password must not be empty".to_string(),
+        ))
+    } else {
+        Ok(password)
+    }
+}
+
+fn check_console_filter(
+    allowed: Option<Vec<String>>,
+    denied: Option<Vec<String>>,
+) -> Result<server::CommandFilter, ConfigValidationError> {
+    let filter = match (allowed, denied) {
+        (Some(_), Some(_)) => {
+            return Err(ConfigValidationError::ConsoleFilter(
+                "Only one of `console_allowed_commands` and `console_denied_commands` can be set"
+                    .to_string(),
+            ));
+        }
+        (Some(rules), None) => server::CommandFilter::Allow(rules),
+        (None, Some(rules)) => server::CommandFilter::Deny(rules),
+        (None, None) => server::CommandFilter::Any,
+    };
+
+    match &filter {
+        server::CommandFilter::Allow(rules) | server::CommandFilter::Deny(rules)
+            if rules.iter().any(|rule| rule.trim().is_empty()) =>
+        {
+            Err(ConfigValidationError::ConsoleFilter(
+                "Command rules must not be empty".to_string(),
+            ))
+        }
+        _ => Ok(filter),
+    }
+}
+
+// The server keeps `ops.json` next to `server.properties` unless configured
+// otherwise, so there is no need to ask for it in the common setup.
+fn resolve_ops_path(
+    ops_path: Option<path::PathBuf>,
+    server_properties_path: &path::Path,
+) -> Result<path::PathBuf, ConfigValidationError> {
+    match ops_path {
+        Some(path) => relative_path_to_absolute(path)
+            .map_err(|err| ConfigValidationError::OpsPath(err.to_string())),
+        None => Ok(server_properties_path.with_file_name("ops.json")),
+    }
+}
+
 fn load_server_properties(
     path: &path::Path,
 ) -> Result<properties::RconProperties, ConfigValidationError> {
@@ -205,6 +812,105 @@ fn resolve_worlds_path(worlds_path: path::PathBuf) -> Result<path::PathBuf, Conf
     }
 }
 
+fn resolve_worker_count(
+    worker_count: Option<WorkerCount>,
+) -> Result<num::NonZeroUsize, ConfigValidationError> {
+    match worker_count {
+        Some(WorkerCount::Count(count)) => Ok(count),
+        Some(WorkerCount::Keyword(keyword)) if keyword != "auto" => {
+            Err(ConfigValidationError::WorkerCount(keyword))
+        }
+        _ => Ok(thread::available_parallelism().unwrap_or(num::NonZeroUsize::MIN)),
+    }
+}
+
+fn check_session_ttl(minutes: u64) -> Result<cookie::time::Duration, ConfigValidationError> {
+    match i64::try_from(minutes)
+        .ok()
+        .and_then(|minutes| minutes.checked_mul(60))
+    {
+        Some(seconds) if seconds > 0 => Ok(cookie::time::Duration::seconds(seconds)),
+        _ => Err(ConfigValidationError::SessionTtl),
+    }
+}
+
+fn check_session_idle_timeout(
+    minutes: Option<u64>,
+) -> Result<Option<time::Duration>, ConfigValidationError> {
+    minutes
+        .map(|minutes| match minutes.checked_mul(60) {
+            Some(seconds) if seconds > 0 => Ok(time::Duration::from_secs(seconds)),
+            _ => Err(ConfigValidationError::SessionIdle),
+        })
+        .transpose()
+}
+
+fn check_min_password_score(
+    score: Option<u8>,
+) -> Result<Option<zxcvbn::Score>, ConfigValidationError> {
+    score
+        .map(|score| {
+            zxcvbn::Score::try_from(score)
+                .map_err(|_| ConfigValidationError::MinPasswordScore(score))
+        })
+        .transpose()
+}
+
+fn check_password_min_classes(count: u8) -> Result<usize, ConfigValidationError> {
+    let count_usize = usize::from(count);
+
+    if count_usize > user::CharacterClass::ALL.len() {
+        Err(ConfigValidationError::PasswordMinClasses(count))
+    } else {
+        Ok(count_usize)
+    }
+}
+
+fn check_enroll_token_ttl(hours: u64) -> Result<time::Duration, ConfigValidationError> {
+    match hours.checked_mul(60 * 60) {
+        Some(seconds) if seconds > 0 => Ok(time::Duration::from_secs(seconds)),
+        _ => Err(ConfigValidationError::EnrollTokenTtl),
+    }
+}
+
+fn check_remember_me_ttl(days: u64) -> Result<cookie::time::Duration, ConfigValidationError> {
+    match i64::try_from(days)
+        .ok()
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+    {
+        Some(seconds) if seconds > 0 => Ok(cookie::time::Duration::seconds(seconds)),
+        _ => Err(ConfigValidationError::RememberMeDays),
+    }
+}
+
+// The directories are only needed by the web server, so they are not required
+// to exist when the configuration is loaded by the management commands.
+fn resolve_directory(path: path::PathBuf) -> Result<path::PathBuf, String> {
+    relative_path_to_absolute(path).map_err(|err| err.to_string())
+}
+
+fn resolve_backup_config(
+    backups_path: Option<path::PathBuf>,
+    best_effort: bool,
+) -> Result<Option<BackupConfig>, ConfigValidationError> {
+    match backups_path {
+        Some(backups_path) => {
+            let path = canonicalize_path(backups_path)
+                .map_err(|err| ConfigValidationError::BackupsPath(err.to_string()))?;
+
+            if !path.is_dir() {
+                Err(ConfigValidationError::BackupsPath(format!(
+                    "`{}` must be a directory",
+                    path.display()
+                )))
+            } else {
+                Ok(Some(BackupConfig { path, best_effort }))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
 fn resolve_server_properties_file_path(
     properties_path: path::PathBuf,
 ) -> Result<path::PathBuf, ConfigValidationError> {
@@ -212,13 +918,28 @@ fn resolve_server_properties_file_path(
         .map_err(|_| ConfigValidationError::PropertiesPath(properties_path))
 }
 
+// The users file doesn't exist until the first user is created with
+// `manage user init`, so only the directory it goes into has to.
 fn resolve_users_file_path(
     users_file: path::PathBuf,
 ) -> Result<path::PathBuf, ConfigValidationError> {
-    let users_file = canonicalize_path(users_file)
+    let users_file = relative_path_to_absolute(users_file)
         .map_err(|err| ConfigValidationError::UsersFilePath(err.to_string()))?;
+    let users_file = if users_file.exists() {
+        canonicalize_path(users_file)
+    } else {
+        let (Some(dir), Some(file_name)) = (users_file.parent(), users_file.file_name()) else {
+            return Err(ConfigValidationError::UsersFilePath(format!(
+                "`{}` must be a path to a file",
+                users_file.display()
+            )));
+        };
 
-    if !users_file.is_file() {
+        canonicalize_path(dir).map(|dir| dir.join(file_name))
+    }
+    .map_err(|err| ConfigValidationError::UsersFilePath(err.to_string()))?;
+
+    if users_file.exists() && !users_file.is_file() {
         Err(ConfigValidationError::UsersFilePath(format!(
             "`{}` must be a valid file",
             users_file.display()
@@ -236,6 +957,30 @@ fn check_base_url(url: url::Url) -> Result<url::Url, ConfigValidationError> {
     }
 }
 
+// The prefix is kept without the trailing slash, so the paths of the app can
+// be appended to it as they are. Serving from the root is an empty prefix.
+fn check_base_path(base_path: Option<String>) -> Result<String, ConfigValidationError> {
+    let Some(base_path) = base_path else {
+        return Ok(String::new());
+    };
+    let trimmed = base_path.trim_end_matches('/');
+
+    let is_valid = trimmed.is_empty()
+        || trimmed.starts_with('/')
+            && trimmed[1..].split('/').all(|segment| {
+                !matches!(segment, "" | "." | "..")
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c))
+            });
+
+    if is_valid {
+        Ok(trimmed.to_string())
+    } else {
+        Err(ConfigValidationError::BasePath(base_path))
+    }
+}
+
 fn canonicalize_path<P: AsRef<path::Path>>(path: P) -> Result<path::PathBuf, LoadConfigError> {
     let path = relative_path_to_absolute(path)?;
 
@@ -275,3 +1020,56 @@ fn check_cookie_key(
         Ok(None)
     }
 }
+
+fn check_api_token(
+    token: Option<secrecy::SecretString>,
+) -> Result<Option<secrecy::SecretString>, ConfigValidationError> {
+    if let Some(token) = token {
+        let token_len = token.expose_secret().len();
+
+        if token_len < 32 {
+            Err(ConfigValidationError::ApiToken(token_len))
+        } else {
+            Ok(Some(token))
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ttl_is_converted_to_seconds() {
+        assert_eq!(
+            check_session_ttl(15).ok(),
+            Some(cookie::time::Duration::seconds(15 * 60))
+        );
+    }
+
+    #[test]
+    fn session_ttl_rejects_zero_and_overflowing_values() {
+        assert!(check_session_ttl(0).is_err());
+        assert!(check_session_ttl(u64::MAX).is_err());
+        assert!(check_session_ttl(u64::try_from(i64::MAX).unwrap()).is_err());
+    }
+
+    #[test]
+    fn logo_paths_on_this_server_get_the_base_path() {
+        assert_eq!(
+            check_logo_path(Some("/static/my-logo.png".to_string()), "/mc").ok(),
+            Some(Some("/mc/static/my-logo.png".to_string()))
+        );
+        assert_eq!(
+            check_logo_path(Some("/static/my-logo.png".to_string()), "").ok(),
+            Some(Some("/static/my-logo.png".to_string()))
+        );
+        assert_eq!(
+            check_logo_path(Some("https://example.com/logo.png".to_string()), "/mc").ok(),
+            Some(Some("https://example.com/logo.png".to_string()))
+        );
+        assert!(check_logo_path(Some("static/my-logo.png".to_string()), "/mc").is_err());
+    }
+}
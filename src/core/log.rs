@@ -0,0 +1,112 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, Seek},
+    os::unix::fs::MetadataExt,
+    path,
+};
+
+// Lines are collected into a ring buffer, so only the last `max_lines` lines
+// are ever kept in memory no matter how large the log file is.
+pub fn tail(log_path: &path::Path, max_lines: usize) -> io::Result<Vec<String>> {
+    let mut reader = io::BufReader::new(fs::File::open(log_path)?);
+    let mut lines = VecDeque::with_capacity(max_lines);
+    let mut partial = vec![];
+
+    read_lines(&mut reader, &mut partial, &mut lines, max_lines)?;
+
+    if !partial.is_empty() {
+        push_line(&mut lines, &partial, max_lines);
+    }
+
+    Ok(lines.into())
+}
+
+pub struct LogFollower {
+    path: path::PathBuf,
+    reader: io::BufReader<fs::File>,
+    inode: u64,
+    position: u64,
+    partial: Vec<u8>,
+    max_lines: usize,
+}
+
+impl LogFollower {
+    pub fn new(log_path: &path::Path, max_lines: usize) -> io::Result<Self> {
+        let file = fs::File::open(log_path)?;
+        let metadata = file.metadata()?;
+        let mut reader = io::BufReader::new(file);
+        let position = reader.seek(io::SeekFrom::End(0))?;
+
+        Ok(Self {
+            path: log_path.to_owned(),
+            reader,
+            inode: metadata.ino(),
+            position,
+            partial: vec![],
+            max_lines,
+        })
+    }
+
+    pub fn read_new_lines(&mut self) -> io::Result<Vec<String>> {
+        let metadata = fs::metadata(&self.path)?;
+
+        // The log was either rotated (replaced by a new file) or truncated, in
+        // both cases it has to be read again from the beginning.
+        if metadata.ino() != self.inode {
+            self.reader = io::BufReader::new(fs::File::open(&self.path)?);
+            self.inode = metadata.ino();
+            self.position = 0;
+            self.partial.clear();
+        } else if metadata.len() < self.position {
+            self.position = self.reader.seek(io::SeekFrom::Start(0))?;
+            self.partial.clear();
+        }
+
+        let mut lines = VecDeque::new();
+        self.position += read_lines(
+            &mut self.reader,
+            &mut self.partial,
+            &mut lines,
+            self.max_lines,
+        )?;
+
+        Ok(lines.into())
+    }
+}
+
+// Only complete lines are pushed, a trailing line without a line break is kept
+// in `partial` until the rest of it is written.
+fn read_lines<R: BufRead>(
+    reader: &mut R,
+    partial: &mut Vec<u8>,
+    lines: &mut VecDeque<String>,
+    max_lines: usize,
+) -> io::Result<u64> {
+    let mut total = 0;
+
+    loop {
+        let read = reader.read_until(b'\n', partial)?;
+        total += read as u64;
+
+        if read == 0 || partial.last() != Some(&b'\n') {
+            return Ok(total);
+        }
+
+        push_line(lines, partial, max_lines);
+        partial.clear();
+    }
+}
+
+fn push_line(lines: &mut VecDeque<String>, line: &[u8], max_lines: usize) {
+    if max_lines == 0 {
+        return;
+    }
+
+    if lines.len() == max_lines {
+        lines.pop_front();
+    }
+
+    let line = String::from_utf8_lossy(line);
+    lines.push_back(line.trim_end_matches(['\r', '\n']).to_string());
+}
@@ -1,13 +1,28 @@
+mod atomic;
+mod audit;
 mod config;
+mod log;
+mod ops;
 mod properties;
 pub mod server;
+#[cfg(test)]
+pub mod testing;
+mod totp;
 mod user;
 mod world;
 
-pub use config::{AppConfig, Config, TlsConfig};
+pub use audit::{AuditEntry, AuditLog};
+pub use config::{
+    AppConfig, Config, ConfigValidationError, LoadConfigError, LogFormat, SessionTtlExtension,
+    TlsConfig,
+};
+pub use log::{LogFollower, tail as tail_log};
+pub use ops::{Operator, load_operators};
+pub use properties::Properties;
 // pub use server::Server;
+pub use totp::{TotpError, TotpSecret};
 pub use user::{
-    InvalidUsernameError, ManageUsersError, Password, PasswordError, PasswordVerifyResult, User,
-    Username, Users,
+    CharacterClass, EnrollToken, EnrollTokenStatus, InvalidRoleError, InvalidUsernameError,
+    ManageUsersError, Password, PasswordError, PasswordVerifyResult, Role, User, Username, Users,
 };
-pub use world::{WorldError, Worlds};
+pub use world::{DiskUsage, World, WorldError, Worlds};
@@ -0,0 +1,44 @@
+use std::{fs, io, path};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operator {
+    pub uuid: String,
+    pub name: String,
+    pub level: u8,
+    pub bypasses_player_limit: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpsError {
+    #[error("Failed to read the operators file {}", .path.display())]
+    Read {
+        path: path::PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Failed to parse the operators file {}", .path.display())]
+    Parse {
+        path: path::PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+// The server only creates the file once the first operator is added, so a
+// missing file just means there are no operators yet.
+pub fn load_operators(ops_path: &path::Path) -> Result<Vec<Operator>, OpsError> {
+    let contents = match fs::read_to_string(ops_path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(source) => Err(OpsError::Read {
+            path: ops_path.to_owned(),
+            source,
+        })?,
+    };
+
+    serde_json::from_str(&contents).map_err(|source| OpsError::Parse {
+        path: ops_path.to_owned(),
+        source,
+    })
+}
@@ -1,6 +1,6 @@
 use std::{
     collections, fs,
-    io::{self, BufRead, Write},
+    io::{self, BufRead},
     path,
 };
 
@@ -16,10 +16,15 @@ pub enum Error {
     MalformedLine(usize),
     #[error("The server.properties has an invalid rcon.port property or it is invalid")]
     InvalidRconPort,
-    #[error("The server.properties does not contain an rcon.password property")]
-    MissingRconPassword,
+    #[error("Invalid property key `{0}`, a key must not be empty or contain `=` or line breaks")]
+    InvalidKey(String),
+    #[error("Invalid value for the `{0}` property, a value must not contain line breaks")]
+    InvalidValue(String),
 }
 
+// The characters Java considers whitespace in properties files.
+const WHITESPACE: [char; 3] = [' ', '\t', '\x0c'];
+
 pub struct Properties {
     inner: collections::HashMap<String, String>,
     path: path::PathBuf,
@@ -37,18 +42,35 @@ impl Properties {
 
         let mut inner = collections::HashMap::new();
 
-        for (line_num, line) in reader.lines().enumerate() {
+        let mut lines = reader.lines().enumerate();
+        while let Some((line_num, line)) = lines.next() {
             let line = line.map_err(Error::Read)?;
-            let line = line.trim();
+            let mut line = line.trim_start_matches(WHITESPACE).to_string();
 
-            if line.starts_with('#') {
+            if line.is_empty() || line.starts_with(['#', '!']) {
                 continue;
             }
 
-            let (key, value) = line.split_once('=').ok_or(Error::MalformedLine(line_num))?;
-            let key = key.trim();
-            let value = value.trim();
-            inner.insert(key.to_string(), value.to_string());
+            // A line ending with an unescaped backslash goes on in the next
+            // one, without the whitespace the next line starts with.
+            while is_continued(&line) {
+                line.pop();
+
+                match lines.next() {
+                    Some((_, next)) => {
+                        line.push_str(next.map_err(Error::Read)?.trim_start_matches(WHITESPACE));
+                    }
+                    None => break,
+                }
+            }
+
+            let line_num = line_num + 1;
+            let (key, value) = split_line(&line);
+            let key = unescape(key)
+                .filter(|key| !key.is_empty())
+                .ok_or(Error::MalformedLine(line_num))?;
+            let value = unescape(value).ok_or(Error::MalformedLine(line_num))?;
+            inner.insert(key, value);
         }
 
         Ok(Self { inner, path })
@@ -62,46 +84,236 @@ impl Properties {
             .parse()
             .map_err(|_| Error::InvalidRconPort)?;
 
-        let password = secrecy::SecretString::from(
-            self.inner
-                .get(Self::RCON_PASSWORD_KEY)
-                .ok_or(Error::MissingRconPassword)?
-                .to_string(),
-        );
+        // The password can also be configured outside of server.properties,
+        // so a missing one is only an error once all the sources are checked.
+        let password = self
+            .inner
+            .get(Self::RCON_PASSWORD_KEY)
+            .filter(|password| !password.is_empty())
+            .map(|password| secrecy::SecretString::from(password.to_string()));
 
         Ok(RconProperties { port, password })
     }
 
     pub fn level_name(&self) -> String {
-        self.inner
-            .get(Self::LEVEL_NAME_KEY)
-            .cloned()
-            .unwrap_or("world".to_string())
+        self.get(Self::LEVEL_NAME_KEY)
+            .unwrap_or("world")
+            .to_string()
     }
 
-    pub fn with_level_name(mut self, world_name: String) -> Result<Self, Error> {
-        self.inner
-            .insert(Self::LEVEL_NAME_KEY.to_string(), world_name);
+    pub fn with_level_name(self, world_name: String) -> Result<Self, Error> {
+        self.set(Self::LEVEL_NAME_KEY, &world_name)
+    }
 
-        let mut file = fs::File::create(&self.path).map_err(Error::Write)?;
-        self.inner
-            .iter()
-            .map(|(key, value)| -> io::Result<()> {
-                file.write_all(key.as_bytes())?;
-                file.write_all("=".as_bytes())?;
-                file.write_all(value.as_bytes())?;
-                file.write_all("\n".as_bytes())?;
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.inner.get(key).map(String::as_str)
+    }
 
-                Ok(())
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(Error::Write)?;
+    pub fn set(mut self, key: &str, value: &str) -> Result<Self, Error> {
+        if key.is_empty() || key.contains(['=', '\n', '\r']) {
+            Err(Error::InvalidKey(key.to_string()))
+        } else if value.contains(['\n', '\r']) {
+            Err(Error::InvalidValue(key.to_string()))
+        } else {
+            self.inner.insert(key.to_string(), value.to_string());
+
+            self.persist()
+        }
+    }
+
+    fn persist(self) -> Result<Self, Error> {
+        let contents: String = self
+            .inner
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", escape(key, true), escape(value, false)))
+            .collect();
+        super::atomic::write(&self.path, contents.as_bytes()).map_err(Error::Write)?;
 
         Ok(self)
     }
 }
 
+fn is_continued(line: &str) -> bool {
+    line.chars().rev().take_while(|&chr| chr == '\\').count() % 2 == 1
+}
+
+// Like in Java properties files, the key ends at the first `=`, `:` or
+// whitespace that isn't escaped, and the whitespace around the separator is
+// insignificant. A line with just a key sets it to an empty value.
+fn split_line(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+
+    let index = line
+        .char_indices()
+        .find(|&(_, chr)| {
+            let is_separator = !escaped && (chr == '=' || chr == ':' || WHITESPACE.contains(&chr));
+            escaped = !escaped && chr == '\\';
+
+            is_separator
+        })
+        .map_or(line.len(), |(index, _)| index);
+
+    let (key, rest) = line.split_at(index);
+    let rest = rest.trim_start_matches(WHITESPACE);
+    let value = rest
+        .strip_prefix(['=', ':'])
+        .unwrap_or(rest)
+        .trim_start_matches(WHITESPACE);
+
+    (key, value)
+}
+
+// Resolves the escapes Java uses in properties files: the usual control
+// characters, `\uXXXX` code units, which may form a surrogate pair, and any
+// other escaped character standing for itself.
+fn unescape(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut units = vec![];
+    let mut chars = text.chars();
+
+    while let Some(chr) = chars.next() {
+        let chr = match chr {
+            '\\' => match chars.next()? {
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    if hex.len() != 4 || !hex.chars().all(|chr| chr.is_ascii_hexdigit()) {
+                        return None;
+                    }
+                    units.push(u16::from_str_radix(&hex, 16).ok()?);
+
+                    continue;
+                }
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                'f' => '\x0c',
+                chr => chr,
+            },
+            chr => chr,
+        };
+
+        decode_units(&mut units, &mut result)?;
+        result.push(chr);
+    }
+
+    decode_units(&mut units, &mut result)?;
+
+    Some(result)
+}
+
+fn decode_units(units: &mut Vec<u16>, result: &mut String) -> Option<()> {
+    for chr in char::decode_utf16(units.drain(..)) {
+        result.push(chr.ok()?);
+    }
+
+    Some(())
+}
+
+// Escapes the characters that would otherwise change the meaning of the line,
+// the same way Java writes properties files. Whitespace is only significant at
+// the start of a value, but anywhere in a key.
+fn escape(text: &str, is_key: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for (index, chr) in text.chars().enumerate() {
+        match chr {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\x0c' => result.push_str("\\f"),
+            '=' | ':' | '#' | '!' => {
+                result.push('\\');
+                result.push(chr);
+            }
+            ' ' if is_key || index == 0 => result.push_str("\\ "),
+            chr if chr.is_control() => result.push_str(&format!("\\u{:04X}", u32::from(chr))),
+            chr => result.push(chr),
+        }
+    }
+
+    result
+}
+
 pub struct RconProperties {
     pub port: u16,
-    pub password: secrecy::SecretString,
+    pub password: Option<secrecy::SecretString>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    #[test]
+    fn lines_ending_with_a_backslash_are_continued() {
+        let dir = testing::TempDir::new();
+        let path = dir.write(
+            "server.properties",
+            "motd=Hello \\\n    World\npath=C\\:\\\\\nlevel-name=survival\\",
+        );
+
+        let properties = Properties::parse(&path).unwrap();
+
+        assert_eq!(properties.get("motd"), Some("Hello World"));
+        // An escaped backslash doesn't continue the line.
+        assert_eq!(properties.get("path"), Some("C:\\"));
+        assert_eq!(properties.level_name(), "survival");
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let dir = testing::TempDir::new();
+        let path = dir.write(
+            "server.properties",
+            "\n# comment\n   \n\t\x0c\nlevel-name=survival\n\n",
+        );
+
+        let properties = Properties::parse(&path).unwrap();
+
+        assert_eq!(properties.level_name(), "survival");
+        assert_eq!(properties.inner.len(), 1);
+    }
+
+    #[test]
+    fn keys_without_a_value_are_empty() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("server.properties", "rcon.password\nrcon.port=25575\n");
+
+        let properties = Properties::parse(&path).unwrap();
+
+        assert_eq!(properties.get("rcon.password"), Some(""));
+        assert_eq!(properties.get("rcon.port"), Some("25575"));
+    }
+
+    #[test]
+    fn unicode_escapes_are_decoded() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("server.properties", "motd=caf\\u00e9 \\uD83D\\uDE00\n");
+
+        let properties = Properties::parse(&path).unwrap();
+
+        assert_eq!(properties.get("motd"), Some("caf\u{e9} \u{1F600}"));
+    }
+
+    #[test]
+    fn values_survive_a_round_trip() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("server.properties", "");
+
+        for value in [
+            "a=b:c",
+            " leading space",
+            "bell \u{7}",
+            "back\\slash\\",
+            "caf\u{e9}",
+        ] {
+            Properties::parse(&path)
+                .unwrap()
+                .set("motd", value)
+                .unwrap();
+
+            assert_eq!(Properties::parse(&path).unwrap().get("motd"), Some(value));
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use super::rcon;
 use std::net;
-use tokio::sync::{mpsc, oneshot};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
 use tokio_util::sync;
 
 pub enum Command {
@@ -31,57 +34,110 @@ impl RconMessage {
 pub struct RconActor {
     addr: net::SocketAddr,
     password: secrecy::SecretString,
+    timeouts: rcon::Timeouts,
+    retries: usize,
     client: Option<rcon::RconClient<rcon::Authenticated>>,
+    connect_failures: u32,
+    retry_at: Option<time::Instant>,
 }
 
 impl RconActor {
-    pub fn new(addr: net::SocketAddr, password: secrecy::SecretString) -> Self {
+    const DEFAULT_RETRIES: usize = 1;
+    const RETRY_DELAY: time::Duration = time::Duration::from_millis(500);
+    // After this many consecutive failed connection attempts commands fail
+    // right away until the cooldown expires, which doubles on every further
+    // failure.
+    const FAILURE_THRESHOLD: u32 = 3;
+    const BASE_COOLDOWN: time::Duration = time::Duration::from_secs(2);
+    const MAX_COOLDOWN: time::Duration = time::Duration::from_secs(60);
+
+    pub fn new(
+        addr: net::SocketAddr,
+        password: secrecy::SecretString,
+        timeouts: rcon::Timeouts,
+    ) -> Self {
         Self {
             addr,
             password,
+            timeouts,
+            retries: Self::DEFAULT_RETRIES,
             client: None,
+            connect_failures: 0,
+            retry_at: None,
         }
     }
 
-    pub fn start(self, cancel: sync::CancellationToken) -> mpsc::UnboundedSender<RconMessage> {
+    pub fn start(
+        self,
+        cancel: sync::CancellationToken,
+        complete: sync::CancellationToken,
+    ) -> mpsc::UnboundedSender<RconMessage> {
         let (sender, receiver) = mpsc::unbounded_channel();
 
-        tokio::spawn(self.handle(receiver, cancel));
+        tokio::spawn(self.handle(receiver, cancel, complete));
 
         sender
     }
 
+    // The actor keeps serving commands until every sender is gone, so a world
+    // switch that is still in progress during a shutdown runs to completion.
     async fn handle(
         mut self,
         mut chan: mpsc::UnboundedReceiver<RconMessage>,
         cancel_token: sync::CancellationToken,
+        complete: sync::CancellationToken,
     ) {
         let _drop_guard = cancel_token.drop_guard();
+        let _complete_guard = complete.drop_guard();
 
         while let Some(msg) = chan.recv().await {
             if let Err(e) = msg.result.send(self.handle_message(msg.command).await) {
                 tracing::error!(error=?e, "Failed to send the response to the caller");
             }
         }
-    }
 
-    async fn handle_message(&mut self, cmd: Command) -> Result<String, rcon::RconError> {
-        let mut client = match self.client.take() {
-            Some(client) => client,
-            None => {
-                rcon::RconClient::new()
-                    .connect(&self.addr)
-                    .await?
-                    .authenticate(&self.password)
-                    .await?
+        if let Some(client) = self.client.take() {
+            match client.disconnect().await {
+                Ok(()) => tracing::info!("Closed the RCON connection"),
+                Err(err) => tracing::warn!("Failed to close the RCON connection: {err}"),
             }
-        };
+        }
+    }
 
-        let (msg, should_shutdown) = match cmd {
+    async fn handle_message(&mut self, cmd: Command) -> Result<String, rcon::RconError> {
+        let (msg, should_shutdown): (String, _) = match cmd {
             Command::Stop => (cmd.into(), true),
             _ => (cmd.into(), false),
         };
 
+        let mut attempt = 0;
+
+        loop {
+            match self.run_command(msg.clone(), should_shutdown).await {
+                Err(err @ (rcon::RconError::Read(_) | rcon::RconError::Write(_)))
+                    if !should_shutdown && attempt < self.retries =>
+                {
+                    attempt += 1;
+
+                    tracing::warn!(%err, attempt, "Lost the RCON connection, retrying the command");
+
+                    time::sleep(Self::RETRY_DELAY).await;
+                }
+                result => break result,
+            }
+        }
+    }
+
+    async fn run_command(
+        &mut self,
+        msg: String,
+        should_shutdown: bool,
+    ) -> Result<String, rcon::RconError> {
+        let mut client = match self.client.take() {
+            Some(client) => client,
+            None => self.connect().await?,
+        };
+
         match client.command(msg).await {
             Ok(res) => {
                 if should_shutdown {
@@ -99,4 +155,46 @@ impl RconActor {
             }
         }
     }
+
+    async fn connect(&mut self) -> Result<rcon::RconClient<rcon::Authenticated>, rcon::RconError> {
+        let now = time::Instant::now();
+
+        if let Some(retry_at) = self.retry_at
+            && now < retry_at
+        {
+            return Err(rcon::RconError::Unavailable(retry_at - now));
+        }
+
+        match rcon::RconClient::new(self.timeouts)
+            .connect(&self.addr)
+            .await
+        {
+            Ok(client) => {
+                self.connect_failures = 0;
+                self.retry_at = None;
+
+                client.authenticate(&self.password).await
+            }
+            Err(err) => {
+                self.connect_failures += 1;
+
+                if self.connect_failures >= Self::FAILURE_THRESHOLD {
+                    let exponent = (self.connect_failures - Self::FAILURE_THRESHOLD).min(16);
+                    let cooldown = Self::BASE_COOLDOWN
+                        .saturating_mul(2u32.pow(exponent))
+                        .min(Self::MAX_COOLDOWN);
+
+                    tracing::warn!(
+                        failures = self.connect_failures,
+                        "The Minecraft server is unreachable, pausing connection attempts for {}s",
+                        cooldown.as_secs()
+                    );
+
+                    self.retry_at = Some(time::Instant::now() + cooldown);
+                }
+
+                Err(err)
+            }
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use super::{actor, rcon};
 use crate::core::server::actor::RconMessage;
+use serde::ser::SerializeStruct;
 use std::net;
 use tokio::sync::{
     mpsc::{self},
@@ -17,35 +18,191 @@ pub enum Error {
     Command(#[source] rcon::RconError),
     #[error("Lost Minecraft server connection: {0}")]
     BrokenConnection(#[source] rcon::RconError),
+    #[error("{0}")]
+    Timeout(#[source] rcon::RconError),
     #[error("Failed to send a message to the actor: {0}")]
     ActorSend(#[source] mpsc::error::SendError<RconMessage>),
     #[error("Failed to fetch the response from the actor: {0}")]
     ActorRecv(#[source] oneshot::error::RecvError),
     #[error("Failed to parse server tick stats: {0}")]
     TickStats(String),
+    #[error("Invalid command argument: {0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Unavailable(#[source] rcon::RconError),
+    #[error("The Minecraft server failed to save the game: {0}")]
+    Save(String),
+    #[error("The Minecraft server didn't change automatic saving: {0}")]
+    Autosave(String),
+}
+
+pub const DIFFICULTIES: [&str; 4] = ["peaceful", "easy", "normal", "hard"];
+pub const GAME_MODES: [&str; 4] = ["survival", "creative", "adventure", "spectator"];
+
+impl Error {
+    // Whether the server could not be reached at all, which most likely means
+    // it is not running.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            Self::Connect(_) | Self::Timeout(_) | Self::BrokenConnection(_) | Self::Unavailable(_)
+        )
+    }
+
+    // How long until the next connection attempt when connections are paused
+    // because the server kept being unreachable.
+    pub fn retry_in(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::Unavailable(rcon::RconError::Unavailable(wait)) => Some(*wait),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct Client(mpsc::UnboundedSender<actor::RconMessage>);
+pub struct Client(
+    mpsc::UnboundedSender<actor::RconMessage>,
+    sync::CancellationToken,
+);
+
+// Vanilla servers report tick times, while Spigot/Paper and Forge only report
+// the number of ticks per second.
+#[derive(serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TickStats {
+    TickTimes(TickTimes),
+    Tps {
+        last_1m: f64,
+        last_5m: f64,
+        last_15m: f64,
+    },
+    MeanTick {
+        tick_time_ms: f64,
+        tps: f64,
+    },
+}
+
+// All values are in milliseconds.
+pub struct TickTimes {
+    pub average: f64,
+    pub target: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl TickTimes {
+    pub fn format(value: f64) -> String {
+        format!("{value:.1}ms")
+    }
+}
+
+// Every value is serialized twice, formatted as it used to be (e.g. "13.2ms")
+// and as a number under the `_ms` suffixed name.
+impl serde::Serialize for TickTimes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TickTimes", 10)?;
+
+        for (name, name_ms, value) in [
+            ("average", "average_ms", self.average),
+            ("target", "target_ms", self.target),
+            ("p50", "p50_ms", self.p50),
+            ("p95", "p95_ms", self.p95),
+            ("p99", "p99_ms", self.p99),
+        ] {
+            state.serialize_field(name, &Self::format(value))?;
+            state.serialize_field(name_ms, &value)?;
+        }
+
+        state.end()
+    }
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TickCommand {
+    // `tick query`, available in vanilla 1.20.3 and later.
+    #[default]
+    TickQuery,
+    // `tps` on Spigot, Paper and their forks.
+    Tps,
+    // `forge tps` on Forge.
+    ForgeTps,
+}
+
+impl TickCommand {
+    const ALL: [Self; 3] = [Self::TickQuery, Self::Tps, Self::ForgeTps];
+
+    fn command(self) -> &'static str {
+        match self {
+            Self::TickQuery => "tick query",
+            Self::Tps => "tps",
+            Self::ForgeTps => "forge tps",
+        }
+    }
+
+    fn parse(self, output: &str) -> Option<TickStats> {
+        match self {
+            Self::TickQuery => parse_tick_query(output),
+            Self::Tps => parse_tps(output),
+            Self::ForgeTps => parse_forge_tps(output),
+        }
+    }
+}
 
 #[derive(serde::Serialize)]
-pub struct TickStats {
-    pub average: String,
+pub struct Player {
+    pub name: String,
+    pub uuid: Option<String>,
+}
+
+// The reply of the server to a command changing a list or a setting, which
+// tells whether the command changed anything.
+pub enum Update {
+    Updated(String),
+    Unchanged(String),
+}
+
+#[derive(serde::Serialize)]
+pub struct Ban {
     pub target: String,
-    pub p50: String,
-    pub p95: String,
-    pub p99: String,
+    pub source: String,
+    pub reason: String,
+}
+
+pub struct BanList {
+    pub players: Vec<Ban>,
+    pub ips: Vec<Ban>,
+}
+
+pub enum KickResult {
+    Kicked(String),
+    NotFound(String),
 }
 
 impl Client {
     pub fn new(
         addr: net::SocketAddr,
         password: secrecy::SecretString,
+        timeouts: rcon::Timeouts,
         cancel_token: sync::CancellationToken,
     ) -> Self {
-        let actor = actor::RconActor::new(addr, password);
+        let actor = actor::RconActor::new(addr, password, timeouts);
+        let complete = sync::CancellationToken::new();
+
+        Self(actor.start(cancel_token, complete.clone()), complete)
+    }
 
-        Self(actor.start(cancel_token))
+    // Resolves once every client is dropped and the connection is closed.
+    pub fn closed(&self) -> sync::WaitForCancellationFutureOwned {
+        self.1.clone().cancelled_owned()
+    }
+
+    pub async fn close(self) {
+        let closed = self.closed();
+
+        drop(self);
+        closed.await;
     }
 
     pub async fn save_all(&self) -> Result<(), Error> {
@@ -54,54 +211,565 @@ impl Client {
         Ok(())
     }
 
+    // Unlike `save_all` this waits until every chunk is written to disk.
+    pub async fn save_all_flush(&self) -> Result<(), Error> {
+        let response =
+            run_command(&self.0, actor::Command::Other("save-all flush".to_string())).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Saving the game (this may take a moment!)Saved the game
+        if response.contains("Saved the game") {
+            Ok(())
+        } else {
+            Err(Error::Save(response))
+        }
+    }
+
+    pub async fn save_off(&self) -> Result<Update, Error> {
+        let response = run_command(&self.0, actor::Command::Other("save-off".to_string())).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output of newer and older versions:
+        // > Automatic saving is now disabled
+        // > Turned off world auto-saving
+        // > Saving is already turned off
+        parse_autosave_update(
+            response,
+            &[
+                "Automatic saving is now disabled",
+                "Turned off world auto-saving",
+            ],
+        )
+    }
+
+    pub async fn save_on(&self) -> Result<Update, Error> {
+        let response = run_command(&self.0, actor::Command::Other("save-on".to_string())).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output of newer and older versions:
+        // > Automatic saving is now enabled
+        // > Turned on world auto-saving
+        // > Saving is already turned on
+        parse_autosave_update(
+            response,
+            &[
+                "Automatic saving is now enabled",
+                "Turned on world auto-saving",
+            ],
+        )
+    }
+
     pub async fn stop(&self) -> Result<(), Error> {
         run_command(&self.0, actor::Command::Stop).await?;
 
         Ok(())
     }
 
+    pub async fn command(&self, command: String) -> Result<String, Error> {
+        run_command(&self.0, actor::Command::Other(command)).await
+    }
+
     pub async fn list(&self) -> Result<Vec<String>, Error> {
-        let list = run_command(&self.0, actor::Command::Other("list".to_string())).await?;
-
-        Ok(match list.split_once(": ") {
-            Some((_, players)) => {
-                if players.is_empty() {
-                    vec![]
-                } else {
-                    players.split(", ").map(|f| f.to_owned()).collect()
+        Ok(self
+            .players()
+            .await?
+            .into_iter()
+            .map(|player| player.name)
+            .collect())
+    }
+
+    pub async fn players(&self) -> Result<Vec<Player>, Error> {
+        let list = run_command(&self.0, actor::Command::Other("list uuids".to_string())).await?;
+
+        Ok(parse_players(&strip_formatting(&list)))
+    }
+
+    pub async fn whitelist_list(&self) -> Result<Vec<String>, Error> {
+        let list =
+            run_command(&self.0, actor::Command::Other("whitelist list".to_string())).await?;
+
+        Ok(parse_player_list(&strip_formatting(&list)))
+    }
+
+    pub async fn whitelist_add(&self, player: &str) -> Result<Update, Error> {
+        let response = run_command(
+            &self.0,
+            actor::Command::Other(format!("whitelist add {}", player_name(player)?)),
+        )
+        .await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Added Steve to the whitelist
+        // > Player is already whitelisted
+        Ok(if response.starts_with("Added") {
+            Update::Updated(response)
+        } else {
+            Update::Unchanged(response)
+        })
+    }
+
+    pub async fn whitelist_remove(&self, player: &str) -> Result<Update, Error> {
+        let response = run_command(
+            &self.0,
+            actor::Command::Other(format!("whitelist remove {}", player_name(player)?)),
+        )
+        .await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Removed Steve from the whitelist
+        // > Player is not whitelisted
+        Ok(if response.starts_with("Removed") {
+            Update::Updated(response)
+        } else {
+            Update::Unchanged(response)
+        })
+    }
+
+    pub async fn kick_player(
+        &self,
+        player: &str,
+        reason: Option<&str>,
+    ) -> Result<KickResult, Error> {
+        let player = player_name(player)?;
+        let command = match reason {
+            Some(reason) => format!("kick {player} {}", command_argument(reason)?),
+            None => format!("kick {player}"),
+        };
+        let response = run_command(&self.0, actor::Command::Other(command)).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Kicked Steve: Kicked by an operator
+        // > No player was found
+        Ok(if response.starts_with("Kicked") {
+            KickResult::Kicked(response)
+        } else {
+            KickResult::NotFound(response)
+        })
+    }
+
+    pub async fn ban(&self, player: &str, reason: Option<&str>) -> Result<Update, Error> {
+        let player = player_name(player)?;
+        let command = match reason {
+            Some(reason) => format!("ban {player} {}", command_argument(reason)?),
+            None => format!("ban {player}"),
+        };
+
+        self.update_bans(command).await
+    }
+
+    pub async fn ban_ip(&self, ip: &str) -> Result<Update, Error> {
+        self.update_bans(format!("ban-ip {}", ip_address(ip)?))
+            .await
+    }
+
+    pub async fn pardon(&self, player: &str) -> Result<Update, Error> {
+        self.update_bans(format!("pardon {}", player_name(player)?))
+            .await
+    }
+
+    pub async fn pardon_ip(&self, ip: &str) -> Result<Update, Error> {
+        self.update_bans(format!("pardon-ip {}", ip_address(ip)?))
+            .await
+    }
+
+    async fn update_bans(&self, command: String) -> Result<Update, Error> {
+        let response = run_command(&self.0, actor::Command::Other(command)).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Banned Steve: Banned by an operator.
+        // > Nothing changed. The player is already banned
+        // > Unbanned IP 127.0.0.1
+        // > Nothing changed. That IP isn't banned
+        Ok(if response.starts_with("Nothing changed") {
+            Update::Unchanged(response)
+        } else {
+            Update::Updated(response)
+        })
+    }
+
+    pub async fn banlist(&self) -> Result<BanList, Error> {
+        let players = run_command(
+            &self.0,
+            actor::Command::Other("banlist players".to_string()),
+        )
+        .await?;
+        let ips = run_command(&self.0, actor::Command::Other("banlist ips".to_string())).await?;
+
+        Ok(BanList {
+            players: parse_ban_list(&strip_formatting(&players), split_player_name),
+            ips: parse_ban_list(&strip_formatting(&ips), split_ip_address),
+        })
+    }
+
+    pub async fn op(&self, player: &str) -> Result<Update, Error> {
+        self.update_ops(format!("op {}", player_name(player)?))
+            .await
+    }
+
+    pub async fn deop(&self, player: &str) -> Result<Update, Error> {
+        self.update_ops(format!("deop {}", player_name(player)?))
+            .await
+    }
+
+    async fn update_ops(&self, command: String) -> Result<Update, Error> {
+        let response = run_command(&self.0, actor::Command::Other(command)).await?;
+        let response = strip_formatting(&response);
+
+        // Example server output:
+        // > Made Steve a server operator
+        // > Nothing changed. The player already is an operator
+        // > Made Steve no longer a server operator
+        // > Nothing changed. The player is not an operator
+        Ok(if response.starts_with("Made") {
+            Update::Updated(response)
+        } else {
+            Update::Unchanged(response)
+        })
+    }
+
+    pub async fn say(&self, message: &str) -> Result<(), Error> {
+        let command = format!("say {}", command_argument(message)?);
+
+        if message.trim().is_empty() {
+            Err(Error::InvalidArgument(
+                "The message must not be empty.".to_string(),
+            ))
+        } else if command.len() > rcon::MAX_COMMAND_SIZE {
+            Err(Error::InvalidArgument(format!(
+                "The message is too long, the limit is {} bytes.",
+                rcon::MAX_COMMAND_SIZE - "say ".len()
+            )))
+        } else {
+            run_command(&self.0, actor::Command::Other(command)).await?;
+
+            Ok(())
+        }
+    }
+
+    pub async fn set_difficulty(&self, level: &str) -> Result<String, Error> {
+        let command = format!("difficulty {}", difficulty(level)?);
+        let response = run_command(&self.0, actor::Command::Other(command)).await?;
+
+        Ok(strip_formatting(&response))
+    }
+
+    pub async fn set_default_gamemode(&self, mode: &str) -> Result<String, Error> {
+        let command = format!("defaultgamemode {}", game_mode(mode)?);
+        let response = run_command(&self.0, actor::Command::Other(command)).await?;
+
+        Ok(strip_formatting(&response))
+    }
+
+    // The preferred command is tried first, the others only if it isn't
+    // supported by the server or its output can't be parsed.
+    pub async fn query_tick(&self, preferred: TickCommand) -> Result<TickStats, Error> {
+        let commands = std::iter::once(preferred)
+            .chain(TickCommand::ALL.into_iter().filter(|cmd| *cmd != preferred));
+        let mut last_error = None;
+
+        for tick_command in commands {
+            let output = match run_command(
+                &self.0,
+                actor::Command::Other(tick_command.command().to_string()),
+            )
+            .await
+            {
+                Ok(output) => strip_formatting(&output),
+                Err(err) if err.is_unreachable() => return Err(err),
+                Err(err) => {
+                    last_error = Some(err);
+
+                    continue;
                 }
+            };
+
+            match tick_command.parse(&output) {
+                Some(stats) => return Ok(stats),
+                None => last_error = Some(Error::TickStats(output)),
             }
-            None => vec![],
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::TickStats(String::new())))
+    }
+}
+
+// Anything else than the expected replies, like "Unknown command" when the
+// command isn't permitted, means automatic saving wasn't changed.
+fn parse_autosave_update(response: String, updated: &[&str]) -> Result<Update, Error> {
+    if response.contains("Saving is already turned") {
+        Ok(Update::Unchanged(response))
+    } else if updated.iter().any(|reply| response.contains(reply)) {
+        Ok(Update::Updated(response))
+    } else {
+        Err(Error::Autosave(response))
+    }
+}
+
+// Example server output:
+// > The game is running normally
+// > Target tick rate: 20.0 per second.
+// > Average time per tick: 0.0ms (Target: 50.0ms)
+// > Percentiles: P50: 0.0ms P95: 0.0ms P99: 0.1ms. Sample: 100
+fn parse_tick_query(output: &str) -> Option<TickStats> {
+    let tick_stats_stripped = output.replace([':', ',', '(', ')'], " ");
+    let timings = tick_stats_stripped
+        .split_whitespace()
+        .filter_map(|w| w.trim_end_matches('.').strip_suffix("ms"))
+        .map(|w| w.parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match timings[..] {
+        [average, target, p50, p95, p99] => Some(TickStats::TickTimes(TickTimes {
+            average,
+            target,
+            p50,
+            p95,
+            p99,
+        })),
+        _ => None,
+    }
+}
+
+// Example server output, Paper marks values capped at 20 with an asterisk:
+// > TPS from last 1m, 5m, 15m: 20.0, 19.8, 19.5
+// > TPS from last 1m, 5m, 15m: *20.0, *20.0, *20.0
+fn parse_tps(output: &str) -> Option<TickStats> {
+    let (_, values) = output.split_once("TPS from last 1m, 5m, 15m:")?;
+    let values = values
+        .lines()
+        .next()?
+        .split(',')
+        .map(|value| value.trim().trim_start_matches('*').parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match values[..] {
+        [last_1m, last_5m, last_15m] => Some(TickStats::Tps {
+            last_1m,
+            last_5m,
+            last_15m,
+        }),
+        _ => None,
+    }
+}
+
+// Only the overall line is used, the others break it down by dimension.
+// Example server output of older and newer Forge versions:
+// > Overall : Mean tick time: 0.684 ms. Mean TPS: 20.000
+// > Overall: 20.000 TPS (0.684 ms/tick)
+fn parse_forge_tps(output: &str) -> Option<TickStats> {
+    let overall = output
+        .lines()
+        .find(|line| line.trim_start().starts_with("Overall"))?
+        .replace([':', '(', ')'], " ");
+    let words: Vec<_> = overall.split_whitespace().collect();
+    let mut tick_time = None;
+    let mut tps = None;
+
+    for (index, word) in words.iter().enumerate() {
+        let Ok(value) = word.trim_end_matches('.').parse::<f64>() else {
+            continue;
+        };
+
+        let previous = index.checked_sub(1).and_then(|index| words.get(index));
+        let next = words.get(index + 1);
+
+        if next.is_some_and(|next| next.starts_with("ms")) {
+            tick_time = Some(value);
+        } else if next == Some(&"TPS") || previous == Some(&"TPS") {
+            tps = Some(value);
+        }
+    }
+
+    Some(TickStats::MeanTick {
+        tick_time_ms: tick_time?,
+        tps: tps?,
+    })
+}
+
+// Every user supplied value must go through one of the functions below before being interpolated
+// into a command, otherwise a crafted value could smuggle extra arguments or commands.
+fn player_name(name: &str) -> Result<&str, Error> {
+    if (3..=16).contains(&name.len())
+        && name
+            .chars()
+            .all(|chr| chr.is_ascii_alphanumeric() || chr == '_')
+    {
+        Ok(name)
+    } else {
+        Err(Error::InvalidArgument(format!(
+            r#""{name}" is not a valid player name. A player name must be 3 to 16 characters long and contain only letters, digits and the underscore "_" character."#
+        )))
+    }
+}
+
+fn ip_address(ip: &str) -> Result<net::IpAddr, Error> {
+    ip.parse()
+        .map_err(|_| Error::InvalidArgument(format!(r#""{ip}" is not a valid IP address."#)))
+}
+
+fn difficulty(level: &str) -> Result<&str, Error> {
+    if DIFFICULTIES.contains(&level) {
+        Ok(level)
+    } else {
+        Err(Error::InvalidArgument(format!(
+            r#""{level}" is not a valid difficulty. Expected one of: {}."#,
+            DIFFICULTIES.join(", ")
+        )))
+    }
+}
+
+fn game_mode(mode: &str) -> Result<&str, Error> {
+    if GAME_MODES.contains(&mode) {
+        Ok(mode)
+    } else {
+        Err(Error::InvalidArgument(format!(
+            r#""{mode}" is not a valid game mode. Expected one of: {}."#,
+            GAME_MODES.join(", ")
+        )))
+    }
+}
+
+fn command_argument(argument: &str) -> Result<&str, Error> {
+    if argument.chars().any(char::is_control) {
+        Err(Error::InvalidArgument(
+            "The value must not contain control characters or line breaks.".to_string(),
+        ))
+    } else {
+        Ok(argument)
+    }
+}
+
+// Example server output:
+// > There are 2 of a max of 20 players online: Steve, Alex
+// > There are 2 whitelisted player(s): Steve, Alex
+// > There are no whitelisted players
+fn parse_player_list(list: &str) -> Vec<String> {
+    match list.split_once(": ") {
+        Some((_, players)) => players
+            .split(',')
+            .map(str::trim)
+            .filter(|player| !player.is_empty())
+            .map(|player| player.to_owned())
+            .collect(),
+        None => vec![],
+    }
+}
+
+// Example server output:
+// > There are 2 of a max of 20 players online: Steve (069a79f4-44e9-4726-a5be-fca90e38aaf5), Alex
+// > There are 0 of a max of 20 players online:
+// Paper splits the players into groups on separate lines instead:
+// > There are 2 out of maximum 20 players online.
+// > default: Steve, Alex
+fn parse_players(list: &str) -> Vec<Player> {
+    let mut lines = list.lines();
+    let header = lines.next().unwrap_or_default();
+    let entries = header
+        .split_once(": ")
+        .map(|(_, players)| players)
+        .into_iter()
+        .chain(lines.filter_map(|line| line.split_once(": ").map(|(_, players)| players)));
+
+    entries
+        .flat_map(|players| players.split(','))
+        .map(str::trim)
+        .filter(|player| !player.is_empty())
+        .map(|player| match player.split_once(" (") {
+            Some((name, uuid)) => Player {
+                name: name.trim().to_owned(),
+                uuid: Some(uuid.trim_end_matches(')').to_owned()),
+            },
+            None => Player {
+                name: player.to_owned(),
+                uuid: None,
+            },
         })
+        .collect()
+}
+
+// Example server output:
+// > There are no bans
+// > There are 2 ban(s):Steve was banned by Rcon: Banned by an operator.Alex was banned by Rcon: Griefing
+// Vanilla servers glue the entries together without a separator, so the
+// banned target is recovered from the end of the text preceding each
+// " was banned by " marker.
+fn parse_ban_list(list: &str, split_target: fn(&str) -> (&str, &str)) -> Vec<Ban> {
+    let Some((_, entries)) = list.split_once("ban(s):") else {
+        return vec![];
+    };
+
+    let mut parts = entries.split(" was banned by ").peekable();
+    let mut target = parts
+        .next()
+        .map(|part| split_target(part).1)
+        .unwrap_or_default();
+    let mut bans = vec![];
+
+    while let Some(part) = parts.next() {
+        let (entry, next_target) = if parts.peek().is_some() {
+            split_target(part)
+        } else {
+            (part, "")
+        };
+        let (source, reason) = entry.split_once(": ").unwrap_or((entry, ""));
+
+        bans.push(Ban {
+            target: target.trim().to_owned(),
+            source: source.trim().to_owned(),
+            reason: reason.trim().to_owned(),
+        });
+        target = next_target;
     }
 
-    pub async fn query_tick(&self) -> Result<TickStats, Error> {
-        let tick_stats =
-            run_command(&self.0, actor::Command::Other("tick query".to_string())).await?;
+    bans
+}
+
+fn split_player_name(text: &str) -> (&str, &str) {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, chr)| chr.is_ascii_alphanumeric() || *chr == '_')
+        .last()
+        .map_or(text.len(), |(idx, _)| idx);
 
-        // Example server output:
-        // > The game is running normally
-        // > Target tick rate: 20.0 per second.
-        // > Average time per tick: 0.0ms (Target: 50.0ms)
-        // > Percentiles: P50: 0.0ms P95: 0.0ms P99: 0.1ms. Sample: 100
-        let tick_stats_stripped = tick_stats.replace([':', ',', '(', ')', '.'], " ");
-        let timings: Vec<_> = tick_stats_stripped
-            .split_whitespace()
-            .filter(|w| w.ends_with("ms"))
-            .collect();
-
-        if timings.len() != 5 {
-            Err(Error::TickStats(tick_stats))
+    text.split_at(start)
+}
+
+// The longest suffix that is still a valid address wins, so a reason ending
+// with a hex digit or a dot doesn't get glued to the address.
+fn split_ip_address(text: &str) -> (&str, &str) {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, chr)| chr.is_ascii_hexdigit() || *chr == '.' || *chr == ':')
+        .map(|(idx, _)| idx)
+        .filter(|idx| text[*idx..].parse::<net::IpAddr>().is_ok())
+        .last()
+        .unwrap_or(text.len());
+
+    text.split_at(start)
+}
+
+// Minecraft formatting codes are a `§` followed by a single formatting character, e.g. `§a`.
+// The raw output of `run_command` keeps them, so callers decide whether they need stripping.
+pub fn strip_formatting(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(chr) = chars.next() {
+        if chr == '§' {
+            chars.next();
         } else {
-            Ok(TickStats {
-                average: timings[0].to_string(),
-                target: timings[1].to_string(),
-                p50: timings[2].to_string(),
-                p95: timings[3].to_string(),
-                p99: timings[4].to_string(),
-            })
+            result.push(chr);
         }
     }
+
+    result
 }
 
 async fn run_command(
@@ -123,6 +791,196 @@ async fn run_command(
             }
             e @ rcon::RconError::Connect(_) => Error::Connect(e),
             e @ rcon::RconError::AuthFail => Error::Authenticate(e),
+            e @ rcon::RconError::Timeout(_) => Error::Timeout(e),
+            e @ rcon::RconError::Unavailable(_) => Error::Unavailable(e),
             e => Error::Command(e),
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_names_with_a_line_break_are_rejected() {
+        assert!(matches!(
+            player_name("Steve\nop Alex"),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn player_names_with_spaces_are_rejected() {
+        assert!(matches!(
+            player_name("Steve Alex"),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn player_names_must_fit_the_length_limits() {
+        assert!(player_name("Al").is_err());
+        assert!(player_name("Abcdefghijklmnopq").is_err());
+        assert_eq!(player_name("Steve_1").ok(), Some("Steve_1"));
+    }
+
+    #[test]
+    fn command_arguments_with_a_line_break_are_rejected() {
+        assert!(matches!(
+            command_argument("hello\nstop"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(command_argument("hello there").ok(), Some("hello there"));
+    }
+
+    fn names_and_uuids(players: Vec<Player>) -> Vec<(String, Option<String>)> {
+        players
+            .into_iter()
+            .map(|player| (player.name, player.uuid))
+            .collect()
+    }
+
+    #[test]
+    fn vanilla_player_lists_are_parsed_with_uuids() {
+        let players = parse_players(
+            "There are 2 of a max of 20 players online: Steve (069a79f4-44e9-4726-a5be-fca90e38aaf5), Alex",
+        );
+
+        assert_eq!(
+            names_and_uuids(players),
+            vec![
+                (
+                    "Steve".to_string(),
+                    Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string())
+                ),
+                ("Alex".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn paper_player_lists_are_parsed_from_every_group() {
+        let players = parse_players(
+            "There are 3 out of maximum 20 players online.\nadmins: Steve\ndefault: Alex, Notch",
+        );
+
+        assert_eq!(
+            names_and_uuids(players),
+            vec![
+                ("Steve".to_string(), None),
+                ("Alex".to_string(), None),
+                ("Notch".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_player_lists_have_no_players() {
+        assert!(parse_players("There are 0 of a max of 20 players online: ").is_empty());
+        assert!(parse_players("There are 0 out of maximum 20 players online.").is_empty());
+        assert!(parse_player_list("There are no whitelisted players").is_empty());
+    }
+
+    fn parsed(command: TickCommand, output: &str) -> serde_json::Value {
+        serde_json::to_value(command.parse(output).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn tick_query_output_is_parsed_into_numbers() {
+        let output = "The game is running normally\nTarget tick rate: 20.0 per second.\nAverage time per tick: 13.2ms (Target: 50.0ms)\nPercentiles: P50: 12.5ms P95: 20.1ms P99: 48.7ms. Sample: 100";
+
+        assert_eq!(
+            parsed(TickCommand::TickQuery, output),
+            serde_json::json!({
+                "kind": "tick_times",
+                "average": "13.2ms", "average_ms": 13.2,
+                "target": "50.0ms", "target_ms": 50.0,
+                "p50": "12.5ms", "p50_ms": 12.5,
+                "p95": "20.1ms", "p95_ms": 20.1,
+                "p99": "48.7ms", "p99_ms": 48.7,
+            })
+        );
+    }
+
+    #[test]
+    fn tps_output_is_parsed_with_and_without_the_cap_marker() {
+        let expected = serde_json::json!({
+            "kind": "tps", "last_1m": 20.0, "last_5m": 19.8, "last_15m": 19.5,
+        });
+
+        assert_eq!(
+            parsed(
+                TickCommand::Tps,
+                "TPS from last 1m, 5m, 15m: 20.0, 19.8, 19.5"
+            ),
+            expected
+        );
+        assert_eq!(
+            parsed(
+                TickCommand::Tps,
+                "TPS from last 1m, 5m, 15m: *20.0, 19.8, 19.5"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn forge_tps_output_is_parsed_in_both_formats() {
+        let expected = serde_json::json!({
+            "kind": "mean_tick", "tick_time_ms": 0.684, "tps": 20.0,
+        });
+
+        assert_eq!(
+            parsed(
+                TickCommand::ForgeTps,
+                "Dim 0 : Mean tick time: 0.5 ms. Mean TPS: 20.000\nOverall : Mean tick time: 0.684 ms. Mean TPS: 20.000"
+            ),
+            expected
+        );
+        assert_eq!(
+            parsed(TickCommand::ForgeTps, "Overall: 20.000 TPS (0.684 ms/tick)"),
+            expected
+        );
+    }
+
+    #[test]
+    fn unrecognized_tick_output_is_not_parsed() {
+        assert!(TickCommand::TickQuery.parse("Unknown command").is_none());
+        assert!(TickCommand::Tps.parse("Unknown command").is_none());
+        assert!(TickCommand::ForgeTps.parse("Unknown command").is_none());
+    }
+
+    #[test]
+    fn autosave_replies_of_every_version_are_recognized() {
+        let off = [
+            "Automatic saving is now disabled",
+            "Turned off world auto-saving",
+        ];
+
+        for reply in off {
+            assert!(matches!(
+                parse_autosave_update(reply.to_string(), &off),
+                Ok(Update::Updated(_))
+            ));
+        }
+        assert!(matches!(
+            parse_autosave_update("Saving is already turned off".to_string(), &off),
+            Ok(Update::Unchanged(_))
+        ));
+    }
+
+    #[test]
+    fn unexpected_autosave_replies_are_errors() {
+        let on = [
+            "Automatic saving is now enabled",
+            "Turned on world auto-saving",
+        ];
+
+        for reply in ["Unknown command", "Turned off world auto-saving", ""] {
+            assert!(matches!(
+                parse_autosave_update(reply.to_string(), &on),
+                Err(Error::Autosave(_))
+            ));
+        }
+    }
+}
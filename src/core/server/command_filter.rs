@@ -0,0 +1,123 @@
+// Rules are matched word by word, so a `stop` rule matches `stop` and
+// `/minecraft:stop`, but not `stopwatch`. A rule with several words, e.g.
+// `whitelist list`, only matches commands starting with all of them.
+// Commands run by `execute … run` are checked as well, so a command is only
+// permitted if every command in the chain is.
+#[derive(Clone)]
+pub enum CommandFilter {
+    Any,
+    Allow(Vec<String>),
+    Deny(Vec<String>),
+}
+
+impl CommandFilter {
+    pub fn is_permitted(&self, command: &str) -> bool {
+        let commands = chain(command);
+
+        match self {
+            Self::Any => true,
+            Self::Allow(rules) => commands
+                .iter()
+                .all(|command| rules.iter().any(|rule| matches(rule, command))),
+            Self::Deny(rules) => !commands
+                .iter()
+                .any(|command| rules.iter().any(|rule| matches(rule, command))),
+        }
+    }
+}
+
+fn matches(rule: &str, command: &[String]) -> bool {
+    let rule = normalize(rule);
+
+    !rule.is_empty() && command.len() >= rule.len() && command[..rule.len()] == rule[..]
+}
+
+// Splits `execute as @a run execute run stop` into the `execute as @a run …`,
+// `execute run stop` and `stop` commands.
+fn chain(command: &str) -> Vec<Vec<String>> {
+    let mut commands = vec![normalize(command)];
+
+    while let Some(nested) = commands.last().and_then(|command| nested_command(command)) {
+        commands.push(nested);
+    }
+
+    commands
+}
+
+fn nested_command(command: &[String]) -> Option<Vec<String>> {
+    if command.first().map(String::as_str) != Some("execute") {
+        return None;
+    }
+
+    let run = command.iter().position(|word| word == "run")?;
+    let mut words = command[run + 1..].iter().cloned();
+
+    Some(
+        words
+            .next()
+            .map(|name| strip_namespace(&name))
+            .into_iter()
+            .chain(words)
+            .collect(),
+    )
+}
+
+// The leading slash and the namespace are optional when running a command, so
+// they are dropped from the command name before comparing.
+fn normalize(command: &str) -> Vec<String> {
+    let mut words = command
+        .trim_start()
+        .trim_start_matches('/')
+        .split_whitespace()
+        .map(str::to_lowercase);
+
+    words
+        .next()
+        .map(|name| strip_namespace(&name))
+        .into_iter()
+        .chain(words)
+        .collect()
+}
+
+fn strip_namespace(name: &str) -> String {
+    match name.rsplit_once(':') {
+        Some((_, name)) => name.to_string(),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deny(rules: &[&str]) -> CommandFilter {
+        CommandFilter::Deny(rules.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn rules_match_whole_words() {
+        let filter = deny(&["stop"]);
+
+        assert!(!filter.is_permitted("stop"));
+        assert!(!filter.is_permitted("/minecraft:stop"));
+        assert!(filter.is_permitted("stopwatch create timer"));
+    }
+
+    #[test]
+    fn commands_run_by_execute_are_checked() {
+        let filter = deny(&["stop", "op"]);
+
+        assert!(!filter.is_permitted("execute run op Steve"));
+        assert!(!filter.is_permitted("execute as @a run stop"));
+        assert!(!filter.is_permitted("execute as @a run execute at @s run minecraft:stop"));
+        assert!(filter.is_permitted("execute as @a run say stop"));
+    }
+
+    #[test]
+    fn allowed_execute_only_runs_allowed_commands() {
+        let filter = CommandFilter::Allow(vec!["execute".to_string(), "say".to_string()]);
+
+        assert!(filter.is_permitted("execute as @a run say hi"));
+        assert!(!filter.is_permitted("execute as @a run stop"));
+    }
+}
@@ -1,5 +1,13 @@
 mod actor;
 mod client;
+mod command_filter;
+mod process;
 mod rcon;
 
-pub use client::{Client, TickStats};
+pub use client::{
+    Ban, BanList, Client, DIFFICULTIES, Error, GAME_MODES, KickResult, Player, TickCommand,
+    TickStats, TickTimes, Update, strip_formatting,
+};
+pub use command_filter::CommandFilter;
+pub use process::{StartCommand, StartError, start};
+pub use rcon::Timeouts;
@@ -0,0 +1,60 @@
+use std::{io, os::unix::process::CommandExt, process, thread};
+
+#[derive(Clone)]
+pub struct StartCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl TryFrom<Vec<String>> for StartCommand {
+    type Error = StartError;
+
+    fn try_from(mut command: Vec<String>) -> Result<Self, Self::Error> {
+        if command.is_empty() || command[0].trim().is_empty() {
+            Err(StartError::EmptyCommand)
+        } else {
+            let program = command.remove(0);
+
+            Ok(Self {
+                program,
+                args: command,
+            })
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StartError {
+    #[error("The start command must contain at least the program to run")]
+    EmptyCommand,
+    #[error("Failed to spawn `{program}`: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+pub fn start(command: &StartCommand) -> Result<(), StartError> {
+    // The server is put into its own process group so that signals sent to
+    // this program (e.g. Ctrl+C in a terminal) don't bring it down as well.
+    let mut child = process::Command::new(&command.program)
+        .args(&command.args)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(|source| StartError::Spawn {
+            program: command.program.clone(),
+            source,
+        })?;
+
+    // Reap the child once it exits so it doesn't linger around as a zombie.
+    thread::spawn(move || match child.wait() {
+        Ok(status) => tracing::info!("The Minecraft server process exited with {status}"),
+        Err(err) => tracing::error!("Failed to wait for the Minecraft server process: {err}"),
+    });
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use std::{fmt, io, num};
+use std::{fmt, future, io, num};
 use tokio::{
     io::{AsyncReadExt as _, AsyncWriteExt as _},
-    net,
+    net, time,
 };
 
 use secrecy::ExposeSecret;
@@ -32,6 +32,17 @@ pub enum RconError {
     SizeError(#[source] num::TryFromIntError),
     #[error("Unexpected end of packet")]
     UnexpectedPackedEnd,
+    #[error("The Minecraft server did not respond within {}ms", .0.as_millis())]
+    Timeout(time::Duration),
+    #[error("The Minecraft server is unreachable, retrying in {}s", .0.as_secs().max(1))]
+    Unavailable(time::Duration),
+}
+
+#[derive(Clone, Copy)]
+pub struct Timeouts {
+    pub connect: time::Duration,
+    pub read: time::Duration,
+    pub write: time::Duration,
 }
 
 pub struct Disconnected;
@@ -51,12 +62,14 @@ pub struct Authenticated {
 
 pub struct RconClient<T> {
     state: T,
+    timeouts: Timeouts,
 }
 
 impl RconClient<Disconnected> {
-    pub fn new() -> Self {
+    pub fn new(timeouts: Timeouts) -> Self {
         Self {
             state: Disconnected,
+            timeouts,
         }
     }
 
@@ -64,12 +77,16 @@ impl RconClient<Disconnected> {
         self,
         addr: &std::net::SocketAddr,
     ) -> Result<RconClient<Connected>, RconError> {
-        let stream = net::TcpStream::connect(addr)
-            .await
-            .map_err(RconError::Connect)?;
+        let stream = with_timeout(self.timeouts.connect, async {
+            net::TcpStream::connect(addr)
+                .await
+                .map_err(RconError::Connect)
+        })
+        .await?;
 
         Ok(RconClient {
             state: Connected(stream),
+            timeouts: self.timeouts,
         })
     }
 }
@@ -81,14 +98,9 @@ impl RconClient<Connected> {
     ) -> Result<RconClient<Authenticated>, RconError> {
         let request = RconPacket::authentication(0, password.expose_secret().to_string())?;
 
-        self.state
-            .0
-            .write_all(&request.encode()?)
-            .await
-            .map_err(RconError::Write)?;
+        write_packet(&mut self.state.0, request, self.timeouts.write).await?;
 
-        let size = read_size(&mut self.state.0).await?;
-        let packet = read_packet(&mut self.state.0, size).await?;
+        let (_, packet) = read_message(&mut self.state.0, self.timeouts.read).await?;
 
         if let RconPacketType::Command = packet.packet_type {
             match packet.id {
@@ -98,6 +110,7 @@ impl RconClient<Connected> {
                         inner: self.state,
                         id: 0,
                     },
+                    timeouts: self.timeouts,
                 }),
                 id => Err(RconError::IdMismatch(0, id)),
             }
@@ -112,25 +125,49 @@ impl RconClient<Connected> {
 
 impl RconClient<Authenticated> {
     pub async fn command(&mut self, data: String) -> Result<String, RconError> {
+        let mut result = String::new();
+
+        self.command_fragments(data, |fragment| result.push_str(&fragment))
+            .await?;
+
+        Ok(result)
+    }
+
+    // Responses that don't fit into a single packet are split by the server,
+    // each fragment is handed over as soon as it arrives instead of waiting for
+    // the whole response.
+    pub async fn command_fragments<F: FnMut(String)>(
+        &mut self,
+        data: String,
+        mut on_fragment: F,
+    ) -> Result<(), RconError> {
         let id = self.id();
-        self.state
-            .inner
-            .0
-            .write_all(&RconPacket::command(id, data)?.encode()?)
-            .await
-            .map_err(RconError::Write)?;
+        write_packet(
+            &mut self.state.inner.0,
+            RconPacket::command(id, data)?,
+            self.timeouts.write,
+        )
+        .await?;
 
-        let size = read_size(&mut self.state.inner.0).await?;
-        let packet = read_packet(&mut self.state.inner.0, size).await?;
+        let (size, packet) = read_message(&mut self.state.inner.0, self.timeouts.read).await?;
 
         if packet.id != id {
             Err(RconError::IdMismatch(0, packet.id))
         } else if let RconPacketType::Response = packet.packet_type {
+            on_fragment(packet.payload);
+
             if size == RconPacket::MAX_PACKET_SIZE {
                 let new_id = self.id();
-                read_fragmented(&mut self.state.inner.0, packet.payload, new_id, id).await
+                read_fragmented(
+                    &mut self.state.inner.0,
+                    self.timeouts,
+                    new_id,
+                    id,
+                    on_fragment,
+                )
+                .await
             } else {
-                Ok(packet.payload)
+                Ok(())
             }
         } else {
             Err(RconError::InvalidPacketType(
@@ -155,6 +192,41 @@ impl RconClient<Authenticated> {
     }
 }
 
+async fn with_timeout<T, F>(duration: time::Duration, future: F) -> Result<T, RconError>
+where
+    F: future::Future<Output = Result<T, RconError>>,
+{
+    time::timeout(duration, future)
+        .await
+        .map_err(|_| RconError::Timeout(duration))?
+}
+
+async fn write_packet(
+    stream: &mut net::TcpStream,
+    packet: RconPacket,
+    timeout: time::Duration,
+) -> Result<(), RconError> {
+    let bytes = packet.encode()?;
+
+    with_timeout(timeout, async {
+        stream.write_all(&bytes).await.map_err(RconError::Write)
+    })
+    .await
+}
+
+async fn read_message(
+    stream: &mut net::TcpStream,
+    timeout: time::Duration,
+) -> Result<(usize, RconPacket), RconError> {
+    with_timeout(timeout, async {
+        let size = read_size(stream).await?;
+        let packet = read_packet(stream, size).await?;
+
+        Ok((size, packet))
+    })
+    .await
+}
+
 async fn read_size(stream: &mut net::TcpStream) -> Result<usize, RconError> {
     let mut buf = [0; 4];
     stream.read_exact(&mut buf).await.map_err(RconError::Read)?;
@@ -182,34 +254,25 @@ async fn read_packet(stream: &mut net::TcpStream, size: usize) -> Result<RconPac
     RconPacket::decode(buf)
 }
 
-async fn read_fragmented(
+async fn read_fragmented<F: FnMut(String)>(
     stream: &mut net::TcpStream,
-    mut result: String,
+    timeouts: Timeouts,
     new_id: i32,
     id: i32,
-) -> Result<String, RconError> {
-    stream
-        .write_all(&RconPacket::check(new_id)?.encode()?)
-        .await
-        .map_err(RconError::Write)?;
+    mut on_fragment: F,
+) -> Result<(), RconError> {
+    write_packet(stream, RconPacket::check(new_id)?, timeouts.write).await?;
 
     loop {
-        let size = read_size(stream).await?;
-        let packet = read_packet(stream, size).await?;
+        let (_, packet) = read_message(stream, timeouts.read).await?;
 
+        // The server answers packets in order, so whatever it replies to the
+        // check packet marks the end of the response. The text of the reply
+        // differs between server implementations and languages.
         if packet.id == id {
-            result.push_str(&packet.payload);
+            on_fragment(packet.payload);
         } else if packet.id == new_id {
-            if let RconPacketType::Response = packet.packet_type {
-                if packet.payload == "Unknown request 0" {
-                    break Ok(result);
-                } else {
-                    break Err(RconError::InvalidPacketType(
-                        RconPacketType::Response.to_string(),
-                        packet.packet_type.to_string(),
-                    ));
-                }
-            }
+            break Ok(());
         } else {
             break Err(RconError::IdMismatch(new_id, packet.id));
         }
@@ -258,13 +321,16 @@ impl TryFrom<i32> for RconPacketType {
             0 => Ok(Self::Response),
             2 => Ok(Self::Command),
             _ => Err(RconError::Decode(format!(
-                "Expected message type to be 0, got: {}",
+                "Expected message type to be 0 (response) or 2 (command), got: {}",
                 value
             ))),
         }
     }
 }
 
+// The longest command, in bytes, that fits into a single RCON packet.
+pub const MAX_COMMAND_SIZE: usize = RconPacket::MAX_CLIENT_PAYLOAD_SIZE;
+
 struct RconPacket {
     id: i32,
     packet_type: RconPacketType,
@@ -343,24 +409,25 @@ impl RconPacket {
 
         let message_type = i32::from_le_bytes(*message_type).try_into()?;
 
-        let payload_size = bytes.len() - Self::PACKET_PAD_SIZE;
-
-        let payload = if payload_size > 0 {
-            str::from_utf8(&bytes[0..payload_size]).map_err(|e| {
-                RconError::Decode(format!(
-                    "Failed to convert message body to a UTF-8 string: {e}"
-                ))
-            })?
-        } else {
-            ""
-        };
+        // Splitting instead of indexing keeps a malformed packet from
+        // panicking the actor, no matter what the length checks above let
+        // through.
+        let (payload, padding) = bytes
+            .split_last_chunk::<{ Self::PACKET_PAD_SIZE }>()
+            .ok_or(RconError::UnexpectedPackedEnd)?;
 
-        if bytes[payload_size..payload_size + 2] != [0, 0] {
+        if padding != &[0; Self::PACKET_PAD_SIZE] {
             return Err(RconError::Decode(
                 "Missing padding at the end of the message".to_string(),
             ));
         }
 
+        let payload = str::from_utf8(payload).map_err(|e| {
+            RconError::Decode(format!(
+                "Failed to convert message body to a UTF-8 string: {e}"
+            ))
+        })?;
+
         Ok(Self {
             id,
             payload: payload.to_string(),
@@ -368,3 +435,188 @@ impl RconPacket {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUTS: Timeouts = Timeouts {
+        connect: time::Duration::from_secs(1),
+        read: time::Duration::from_secs(1),
+        write: time::Duration::from_secs(1),
+    };
+
+    fn encode(id: i32, packet_type: i32, payload: &str) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(id.to_le_bytes());
+        bytes.extend(packet_type.to_le_bytes());
+        bytes.extend(payload.as_bytes());
+        bytes.extend([0, 0]);
+
+        let mut packet = vec![];
+        packet.extend(i32::try_from(bytes.len()).unwrap().to_le_bytes());
+        packet.extend(bytes);
+
+        packet
+    }
+
+    // Requests are read by hand, the client sends types it never decodes.
+    async fn read_request_id(stream: &mut net::TcpStream) -> Option<i32> {
+        let size = read_size(stream).await.ok()?;
+        let mut buf = vec![0; size];
+        stream.read_exact(&mut buf).await.ok()?;
+
+        Some(i32::from_le_bytes(*buf.first_chunk::<4>()?))
+    }
+
+    // Accepts a single connection and answers every request read from it with
+    // the replies returned for it, until the client goes away.
+    async fn serve<F>(mut respond: F) -> std::net::SocketAddr
+    where
+        F: FnMut(i32) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        let listener = net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            while let Some(id) = read_request_id(&mut stream).await {
+                for reply in respond(id) {
+                    stream.write_all(&reply).await.unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    async fn authenticate(
+        addr: std::net::SocketAddr,
+    ) -> Result<RconClient<Authenticated>, RconError> {
+        RconClient::new(TIMEOUTS)
+            .connect(&addr)
+            .await?
+            .authenticate(&secrecy::SecretString::from("password"))
+            .await
+    }
+
+    #[tokio::test]
+    async fn authentication_fails_on_the_auth_fail_id() {
+        let addr = serve(|_| vec![encode(-1, 2, "")]).await;
+
+        assert!(matches!(authenticate(addr).await, Err(RconError::AuthFail)));
+    }
+
+    #[tokio::test]
+    async fn authentication_succeeds_on_the_request_id() {
+        let addr = serve(|id| vec![encode(id, 2, "")]).await;
+
+        assert!(authenticate(addr).await.is_ok());
+    }
+
+    #[test]
+    fn unknown_packet_types_are_rejected() {
+        let err = RconPacketType::try_from(3).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Failed to decode the message received from the Minecraft server: Expected message type to be 0 (response) or 2 (command), got: 3"
+        );
+    }
+
+    // The first fragment fills a whole packet, which makes the client send the
+    // check packet, the rest of the response is only sent after it arrives.
+    async fn fragmented_response(terminator: &'static str) -> Vec<String> {
+        let first = "a".repeat(RconPacket::MAX_PACKET_SIZE - RconPacket::MIN_PACKET_SIZE);
+        let addr = serve(move |id| match id {
+            0 => vec![encode(0, 2, "")],
+            1 => vec![encode(1, 0, &first)],
+            _ => vec![
+                encode(1, 0, "b"),
+                encode(1, 0, "c"),
+                encode(id, 0, terminator),
+            ],
+        })
+        .await;
+        let mut client = authenticate(addr).await.unwrap();
+        let mut fragments = vec![];
+
+        client
+            .command_fragments("list".to_string(), |fragment| fragments.push(fragment))
+            .await
+            .unwrap();
+
+        fragments
+    }
+
+    #[tokio::test]
+    async fn fragmented_responses_end_on_any_reply_to_the_check_packet() {
+        for terminator in [
+            "Unknown request 0",
+            "Unknown request 2",
+            "Requête inconnue",
+            "",
+        ] {
+            let fragments = fragmented_response(terminator).await;
+
+            assert_eq!(fragments.len(), 3, "{terminator:?}");
+            assert!(fragments[0].chars().all(|chr| chr == 'a'));
+            assert_eq!(fragments[1..], ["b", "c"]);
+        }
+    }
+
+    #[test]
+    fn every_truncated_packet_is_rejected() {
+        let packet = encode(7, 0, "There are 0 of a max of 20 players online:");
+        let body = &packet[4..];
+
+        for end in 0..body.len() {
+            assert!(RconPacket::decode(body[..end].to_vec()).is_err(), "{end}");
+        }
+        assert!(RconPacket::decode(body.to_vec()).is_ok());
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        let mut missing_padding = encode(7, 0, "abc")[4..].to_vec();
+        missing_padding.pop();
+        missing_padding.push(1);
+        let mut invalid_utf8 = encode(7, 0, "ab")[4..].to_vec();
+        invalid_utf8[8] = 0xff;
+
+        assert!(RconPacket::decode(missing_padding).is_err());
+        assert!(RconPacket::decode(invalid_utf8).is_err());
+        assert!(RconPacket::decode(encode(7, 5, "")[4..].to_vec()).is_err());
+    }
+
+    #[test]
+    fn random_bytes_never_panic() {
+        use rand::RngExt as _;
+
+        let mut rng = rand::rng();
+        for _ in 0..10_000 {
+            let len = rng.random_range(0..64);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.random()).collect();
+
+            let _ = RconPacket::decode(bytes);
+        }
+    }
+
+    #[tokio::test]
+    async fn oversized_and_negative_packet_sizes_are_rejected() {
+        for size in [
+            i32::try_from(RconPacket::MAX_PACKET_SIZE + 1).unwrap(),
+            -1,
+            0,
+            i32::MAX,
+        ] {
+            let addr = serve(move |_| vec![size.to_le_bytes().to_vec()]).await;
+
+            assert!(
+                matches!(authenticate(addr).await, Err(RconError::Decode(_))),
+                "{size}"
+            );
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use rand::distr::{self, SampleString};
+use std::{env, fs, path};
+
+// A directory that only lives as long as the test using it.
+pub struct TempDir(path::PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = env::temp_dir().join(format!(
+            "mctrlrs-test-{}",
+            distr::Alphanumeric.sample_string(&mut rand::rng(), 12)
+        ));
+        fs::create_dir(&path).unwrap();
+
+        Self(path)
+    }
+
+    pub fn path(&self) -> &path::Path {
+        &self.0
+    }
+
+    pub fn write(&self, name: &str, contents: &str) -> path::PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, contents).unwrap();
+
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
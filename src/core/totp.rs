@@ -0,0 +1,148 @@
+use secrecy::ExposeSecret;
+use std::time;
+use subtle::ConstantTimeEq;
+
+#[derive(thiserror::Error, Debug)]
+pub enum TotpError {
+    #[error("Invalid TOTP secret: {0}")]
+    Secret(String),
+    #[error("The system clock is set before the UNIX epoch: {0}")]
+    Clock(#[source] time::SystemTimeError),
+}
+
+#[derive(Clone)]
+pub struct TotpSecret(secrecy::SecretString);
+
+impl TotpSecret {
+    const ISSUER: &'static str = "mctrlrs";
+    const DIGITS: usize = 6;
+    const SKEW: u8 = 1;
+    const STEP: u64 = 30;
+
+    pub fn generate() -> Self {
+        let secret = totp_rs::Secret::generate_secret().to_encoded().to_string();
+
+        Self(secrecy::SecretString::from(secret))
+    }
+
+    pub fn reveal(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    pub fn provisioning_uri<A: AsRef<str>>(&self, account: A) -> Result<String, TotpError> {
+        Ok(self.totp(account)?.get_url())
+    }
+
+    pub fn verify<A: AsRef<str>>(&self, account: A, code: &str) -> Result<bool, TotpError> {
+        self.verify_step(account, code, None)
+            .map(|step| step.is_some())
+    }
+
+    // Returns the time step the code was generated for. The steps up to
+    // `last_step` are skipped, so a code that was accepted once can't be used
+    // again.
+    pub fn verify_step<A: AsRef<str>>(
+        &self,
+        account: A,
+        code: &str,
+        last_step: Option<u64>,
+    ) -> Result<Option<u64>, TotpError> {
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_err(TotpError::Clock)?;
+
+        Ok(Self::find_step(
+            &self.totp(account)?,
+            code.trim(),
+            now.as_secs(),
+            last_step,
+        ))
+    }
+
+    fn find_step(
+        totp: &totp_rs::TOTP,
+        code: &str,
+        time: u64,
+        last_step: Option<u64>,
+    ) -> Option<u64> {
+        let current = time / Self::STEP;
+        let skew = u64::from(Self::SKEW);
+
+        (current.saturating_sub(skew)..=current.saturating_add(skew))
+            .filter(|step| last_step.is_none_or(|last_step| *step > last_step))
+            .find(|step| {
+                totp.generate(step * Self::STEP)
+                    .as_bytes()
+                    .ct_eq(code.as_bytes())
+                    .into()
+            })
+    }
+
+    fn totp<A: AsRef<str>>(&self, account: A) -> Result<totp_rs::TOTP, TotpError> {
+        let secret = totp_rs::Secret::Encoded(self.reveal().to_string())
+            .to_bytes()
+            .map_err(|err| TotpError::Secret(err.to_string()))?;
+
+        totp_rs::TOTP::new(
+            totp_rs::Algorithm::SHA1,
+            Self::DIGITS,
+            Self::SKEW,
+            Self::STEP,
+            secret,
+            Some(Self::ISSUER.to_string()),
+            account.as_ref().to_string(),
+        )
+        .map_err(|err| TotpError::Secret(err.to_string()))
+    }
+}
+
+impl TryFrom<String> for TotpSecret {
+    type Error = TotpError;
+
+    fn try_from(secret: String) -> Result<Self, Self::Error> {
+        let secret = Self(secrecy::SecretString::from(secret));
+        secret.totp("")?;
+
+        Ok(secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: u64 = 1_700_000_000;
+
+    #[test]
+    fn codes_match_the_step_they_were_generated_for() {
+        let totp = TotpSecret::generate().totp("Admin").unwrap();
+        let step = TIME / TotpSecret::STEP;
+        let previous = totp.generate(TIME - TotpSecret::STEP);
+
+        assert_eq!(
+            TotpSecret::find_step(&totp, &totp.generate(TIME), TIME, None),
+            Some(step)
+        );
+        assert_eq!(
+            TotpSecret::find_step(&totp, &previous, TIME, None),
+            Some(step - 1)
+        );
+        assert_eq!(
+            TotpSecret::find_step(&totp, &totp.generate(TIME + 120), TIME, None),
+            None
+        );
+    }
+
+    #[test]
+    fn codes_of_the_last_accepted_step_are_rejected() {
+        let totp = TotpSecret::generate().totp("Admin").unwrap();
+        let step = TIME / TotpSecret::STEP;
+        let code = totp.generate(TIME);
+
+        assert_eq!(TotpSecret::find_step(&totp, &code, TIME, Some(step)), None);
+        assert_eq!(
+            TotpSecret::find_step(&totp, &code, TIME, Some(step - 1)),
+            Some(step)
+        );
+    }
+}
@@ -5,7 +5,11 @@ use argon2::{
 };
 use rand::distr::{self, SampleString};
 use secrecy::ExposeSecret;
-use std::{collections, fmt, fs, io, path};
+use std::{
+    collections, fmt, fs, io,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path, str, time,
+};
 
 trait SafeString {
     fn is_safe(&self) -> bool;
@@ -20,11 +24,11 @@ impl SafeString for String {
 
 #[derive(thiserror::Error, Debug)]
 pub enum InvalidUsernameError {
-    #[error("The username can not be longer than {} characters.", 0)]
+    #[error("The username can not be longer than {} characters.", .0)]
     TooLong(usize),
     #[error("The username can not be empty.")]
     TooShort,
-    #[error(r#"Username "{}" contains invalid characters. Allowed characters are letters "a" to "z", digits "0" to "9" and the underscore "_" character."#, 0)]
+    #[error(r#"Username "{}" contains invalid characters. Allowed characters are letters "a" to "z", digits "0" to "9" and the underscore "_" character."#, .0)]
     InvalidCharacters(String),
 }
 
@@ -33,6 +37,12 @@ pub struct Username(String);
 
 impl Username {
     const MAX_USERNAME_LENGTH: usize = 64;
+
+    // Usernames are case-insensitive, the original form is only kept for
+    // display purposes.
+    fn key(&self) -> String {
+        self.0.to_lowercase()
+    }
 }
 
 impl TryFrom<String> for Username {
@@ -73,6 +83,14 @@ impl PartialEq for EnrollToken {
 impl EnrollToken {
     const TOKEN_LENGTH: usize = 128;
 
+    // Alphanumeric characters are always safe, so a generated token is valid
+    // by construction and doesn't need to go through `TryFrom`.
+    fn generate() -> Self {
+        let token = distr::Alphanumeric.sample_string(&mut rand::rng(), Self::TOKEN_LENGTH);
+
+        Self(secrecy::SecretString::from(token))
+    }
+
     pub fn reveal(&self) -> &str {
         self.0.expose_secret()
     }
@@ -82,7 +100,7 @@ impl TryFrom<String> for EnrollToken {
     type Error = InvalidTokenError;
 
     fn try_from(token: String) -> Result<Self, Self::Error> {
-        if token.is_safe() || token.len() != Self::TOKEN_LENGTH {
+        if token.is_safe() && token.len() == Self::TOKEN_LENGTH {
             Ok(Self(secrecy::SecretString::from(token)))
         } else {
             Err(InvalidTokenError)
@@ -107,12 +125,87 @@ struct UserRecord {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     enroll_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    enroll_token_issued_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    totp_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    totp_last_step: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    password_changed_at: Option<u64>,
+    // Users stored before roles were introduced could do everything but manage
+    // other users, which is what an operator can do.
+    #[serde(default)]
+    role: Role,
 }
 
+// Roles are ordered, each one is allowed everything the previous one is.
+#[derive(
+    serde::Deserialize,
+    serde::Serialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+    Debug,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    #[default]
+    Operator,
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Viewer => write!(f, "viewer"),
+            Self::Operator => write!(f, "operator"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl str::FromStr for Role {
+    type Err = InvalidRoleError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_lowercase().as_str() {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(InvalidRoleError(role.to_string())),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(r#"Unknown role "{0}", expected one of: viewer, operator, admin"#)]
+pub struct InvalidRoleError(String);
+
 pub struct User {
     pub username: Username,
+    pub role: Role,
     password: Option<secrecy::SecretString>,
     enroll_token: Option<EnrollToken>,
+    enroll_token_issued_at: Option<time::SystemTime>,
+    totp_secret: Option<core::TotpSecret>,
+    totp_last_step: Option<u64>,
+    password_changed_at: Option<time::SystemTime>,
+}
+
+pub enum EnrollTokenStatus {
+    Valid(Username),
+    Expired,
+    Invalid,
 }
 
 pub enum PasswordVerifyResult {
@@ -122,6 +215,19 @@ pub enum PasswordVerifyResult {
 }
 
 impl User {
+    fn enrolled(username: Username, role: Role, enroll_token: EnrollToken) -> Self {
+        Self {
+            username,
+            role,
+            password: None,
+            enroll_token: Some(enroll_token),
+            enroll_token_issued_at: Some(time::SystemTime::now()),
+            totp_secret: None,
+            totp_last_step: None,
+            password_changed_at: None,
+        }
+    }
+
     pub fn verify_password(&self, candidate: secrecy::SecretString) -> PasswordVerifyResult {
         match &self.password {
             Some(password) => match argon2::PasswordHash::new(password.expose_secret()) {
@@ -140,6 +246,34 @@ impl User {
             _ => PasswordVerifyResult::Invalid,
         }
     }
+
+    pub fn password_changed_since(&self, instant: time::SystemTime) -> bool {
+        self.password_changed_at
+            .is_some_and(|changed_at| changed_at > instant)
+    }
+
+    pub fn is_enrolled(&self) -> bool {
+        self.password.is_some()
+    }
+
+    pub fn is_deactivated(&self) -> bool {
+        self.password.is_none() && self.enroll_token.is_none()
+    }
+
+    pub fn has_totp(&self) -> bool {
+        self.totp_secret.is_some()
+    }
+
+    // Returns the time step of a valid code, which has to be accepted with
+    // `Users::accept_totp_step` before the code counts.
+    pub fn verify_totp(&self, code: &str) -> Result<Option<u64>, core::TotpError> {
+        match &self.totp_secret {
+            Some(secret) => {
+                secret.verify_step(self.username.to_string(), code, self.totp_last_step)
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 pub struct Users {
@@ -150,11 +284,56 @@ pub struct Users {
 impl Users {
     pub fn load<P: AsRef<path::Path>>(path: P) -> Result<Self, ManageUsersError> {
         let storage_path = path.as_ref().to_owned();
+        let users = parse_users(read_storage(&storage_path)?)?;
+
+        Ok(Self {
+            users,
+            storage_path,
+        })
+    }
+
+    // A single broken record would otherwise lock everybody out of the web
+    // interface, so the records that can't be read are reported and skipped.
+    // Changes always go through the strict `load`, which means a skipped record
+    // is never silently dropped from the file.
+    pub fn load_lenient<P: AsRef<path::Path>>(path: P) -> Result<Self, ManageUsersError> {
+        let storage_path = path.as_ref().to_owned();
+        let records: Vec<serde_yaml_ng::Value> = read_storage(&storage_path)?;
+        let mut users = collections::HashMap::with_capacity(records.len());
+        let mut duplicates = collections::HashSet::new();
+
+        for (index, record) in records.into_iter().enumerate() {
+            let username = record
+                .get("username")
+                .and_then(|username| username.as_str())
+                .unwrap_or("<unknown>")
+                .to_string();
+            let user = serde_yaml_ng::from_value::<UserRecord>(record)
+                .map_err(|err| err.to_string())
+                .and_then(User::try_from);
 
-        let users_file = fs::File::open(&storage_path).map_err(ManageUsersError::LoadStorage)?;
-        let users: Vec<UserRecord> =
-            serde_yaml_ng::from_reader(users_file).map_err(ManageUsersError::Deserialize)?;
-        let users = parse_users(users)?;
+            match user {
+                Ok(user) => {
+                    let key = user.username.key();
+
+                    // There is no telling which one of the records is right.
+                    if duplicates.contains(&key) || users.remove(&key).is_some() {
+                        tracing::warn!(
+                            "Skipped user `{username}` from {}, it is stored more than once",
+                            storage_path.display()
+                        );
+                        duplicates.insert(key);
+                    } else {
+                        users.insert(key, user);
+                    }
+                }
+                Err(err) => tracing::warn!(
+                    "Skipped record #{} (user `{username}`) from {}: {err}",
+                    index + 1,
+                    storage_path.display()
+                ),
+            }
+        }
 
         Ok(Self {
             users,
@@ -162,117 +341,314 @@ impl Users {
         })
     }
 
-    pub fn enroll_user(mut self, username: Username) -> Result<EnrollToken, ManageUsersError> {
-        let password = None;
-        let enroll_token: EnrollToken = {
-            let mut rng = rand::rng();
-            let token_string =
-                distr::Alphanumeric.sample_string(&mut rng, EnrollToken::TOKEN_LENGTH);
-            token_string
-                .try_into()
-                .map_err(ManageUsersError::GenerateToken)?
+    // A missing users file is created without any users in it. That doesn't
+    // turn the authentication off, nobody can log in until a user is enrolled
+    // with the command line tool.
+    pub fn load_or_create<P: AsRef<path::Path>>(path: P) -> Result<Self, ManageUsersError> {
+        let storage_path = path.as_ref().to_owned();
+        let _lock = lock_storage(&storage_path)?;
+
+        match Self::load(&storage_path) {
+            Err(ManageUsersError::LoadStorage(err)) if err.kind() == io::ErrorKind::NotFound => {
+                Self {
+                    users: collections::HashMap::new(),
+                    storage_path: storage_path.clone(),
+                }
+                .persist()?;
+
+                Ok(Self {
+                    users: collections::HashMap::new(),
+                    storage_path,
+                })
+            }
+            result => result,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    // Creates the users file with its first user, which is the only way in on a
+    // fresh installation. Users that are already there are only replaced when
+    // forced, so running it twice can't lock everybody out by accident.
+    pub fn initialize<P: AsRef<path::Path>>(
+        path: P,
+        username: Username,
+        role: Role,
+        force: bool,
+    ) -> Result<EnrollToken, ManageUsersError> {
+        let storage_path = path.as_ref().to_owned();
+        let _lock = lock_storage(&storage_path)?;
+
+        if !force {
+            match Self::load(&storage_path) {
+                Ok(users) if !users.users.is_empty() => {
+                    Err(ManageUsersError::AlreadyInitialized(users.users.len()))?
+                }
+                Ok(_) => {}
+                Err(ManageUsersError::LoadStorage(err))
+                    if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => Err(err)?,
+            }
+        }
+
+        let enroll_token = EnrollToken::generate();
+        let user = User::enrolled(username, role, enroll_token.clone());
+        let users = Self {
+            users: collections::HashMap::from([(user.username.key(), user)]),
+            storage_path,
         };
+        users.persist()?;
+
+        Ok(enroll_token)
+    }
 
-        let enroll_user_token = enroll_token.clone();
-        let enroll_token = enroll_token;
+    pub fn enroll_user(
+        &self,
+        username: Username,
+        role: Role,
+    ) -> Result<EnrollToken, ManageUsersError> {
+        let enroll_token = EnrollToken::generate();
+        let user = User::enrolled(username, role, enroll_token.clone());
 
-        self.users.insert(
-            username.to_string(),
-            User {
-                username,
-                password,
-                enroll_token: Some(enroll_user_token),
-            },
-        );
+        // The check runs under the lock, so enrolling the same user twice at
+        // once can't replace the password of the one enrolled first. Usernames
+        // differing only in the case are the same user.
+        self.modify(|users| match users.entry(user.username.key()) {
+            collections::hash_map::Entry::Occupied(_) => {
+                Err(ManageUsersError::AlreadyExists(user.username.to_string()))
+            }
+            collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(user);
 
-        self.persist()?;
+                Ok(())
+            }
+        })?;
 
         Ok(enroll_token)
     }
 
-    pub fn remove(mut self, username: &Username) -> Result<(), ManageUsersError> {
-        if self.users.remove(&username.0).is_some() {
-            self.persist()
-        } else {
-            Err(ManageUsersError::NoSuchUser(username.0.clone()))
-        }
+    pub fn remove(&self, username: &Username) -> Result<(), ManageUsersError> {
+        self.modify(|users| {
+            ensure_another_admin(users, username)?;
+
+            match users.remove(&username.key()) {
+                Some(_) => Ok(()),
+                None => Err(ManageUsersError::NoSuchUser(username.0.clone())),
+            }
+        })
     }
 
-    pub fn find_username_by_token(&self, token: EnrollToken) -> Option<Username> {
-        self.users
+    // A deactivated user keeps the record, and with it the role and the second
+    // factor, but can't log in until a new password is set. Marking the password
+    // as changed ends the sessions the user still has.
+    pub fn deactivate(&self, username: &Username) -> Result<(), ManageUsersError> {
+        self.modify(|users| {
+            ensure_another_admin(users, username)?;
+
+            match users.get_mut(&username.key()) {
+                Some(user) => {
+                    user.password = None;
+                    user.enroll_token = None;
+                    user.enroll_token_issued_at = None;
+                    user.password_changed_at = Some(time::SystemTime::now());
+
+                    Ok(())
+                }
+                None => Err(ManageUsersError::NoSuchUser(username.to_string())),
+            }
+        })
+    }
+
+    // Tokens issued before the issue time was recorded are considered expired.
+    pub fn verify_enroll_token(
+        &self,
+        token: EnrollToken,
+        ttl: time::Duration,
+    ) -> EnrollTokenStatus {
+        match self
+            .users
             .values()
             .find(|user| user.enroll_token.as_ref() == Some(&token))
-            .map(|user| user.username.to_owned())
+        {
+            Some(user) => match user.enroll_token_issued_at {
+                Some(issued_at) if issued_at.elapsed().is_ok_and(|age| age > ttl) => {
+                    EnrollTokenStatus::Expired
+                }
+                Some(_) => EnrollTokenStatus::Valid(user.username.to_owned()),
+                None => EnrollTokenStatus::Expired,
+            },
+            None => EnrollTokenStatus::Invalid,
+        }
     }
 
     pub fn find_user_by_username(&self, username: &Username) -> Option<&User> {
-        self.users.get(&username.0)
+        self.users.get(&username.key())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &User> {
+        self.users.values()
     }
 
     pub fn update_password(
-        mut self,
+        &self,
         username: &Username,
         password: Password,
     ) -> Result<(), ManageUsersError> {
-        match self.users.get_mut(&username.to_string()) {
+        self.modify_user(username, |user| {
+            user.password = Some(password.0);
+            user.enroll_token = None;
+            user.enroll_token_issued_at = None;
+            user.password_changed_at = Some(time::SystemTime::now());
+        })
+    }
+
+    pub fn set_role(&self, username: &Username, role: Role) -> Result<(), ManageUsersError> {
+        self.modify_user(username, |user| user.role = role)
+    }
+
+    pub fn set_totp_secret(
+        &self,
+        username: &Username,
+        secret: core::TotpSecret,
+    ) -> Result<(), ManageUsersError> {
+        self.modify_user(username, |user| {
+            user.totp_secret = Some(secret);
+            user.totp_last_step = None;
+        })
+    }
+
+    // Every code is only accepted once. The loaded users may be stale, so the
+    // last accepted step is checked again on the stored ones, under the lock.
+    // Returns whether the step was accepted.
+    pub fn accept_totp_step(
+        &self,
+        username: &Username,
+        step: u64,
+    ) -> Result<bool, ManageUsersError> {
+        self.modify(|users| match users.get_mut(&username.key()) {
+            Some(user)
+                if user
+                    .totp_last_step
+                    .is_some_and(|last_step| last_step >= step) =>
+            {
+                Ok(false)
+            }
             Some(user) => {
-                user.password = Some(password.0);
-                user.enroll_token = None;
+                user.totp_last_step = Some(step);
 
-                self.persist()
+                Ok(true)
             }
             None => Err(ManageUsersError::NoSuchUser(username.to_string())),
-        }
+        })
+    }
+
+    fn modify_user<F: FnOnce(&mut User)>(
+        &self,
+        username: &Username,
+        update: F,
+    ) -> Result<(), ManageUsersError> {
+        self.modify(|users| match users.get_mut(&username.key()) {
+            Some(user) => {
+                update(user);
+
+                Ok(())
+            }
+            None => Err(ManageUsersError::NoSuchUser(username.to_string())),
+        })
+    }
+
+    // The loaded users may be cached by the web server for a long time, so
+    // changes are applied to a fresh copy of the file while holding an
+    // exclusive lock on it. Otherwise two concurrent changes, coming either
+    // from the web server or from the command line, would both start from the
+    // same data and the last one to persist would silently discard the other.
+    fn modify<T, F>(&self, update: F) -> Result<T, ManageUsersError>
+    where
+        F: FnOnce(&mut collections::HashMap<String, User>) -> Result<T, ManageUsersError>,
+    {
+        let _lock = lock_storage(&self.storage_path)?;
+        let mut users = Self::load(&self.storage_path)?;
+
+        let result = update(&mut users.users)?;
+        users.persist()?;
+
+        Ok(result)
     }
 
     fn persist(self) -> Result<(), ManageUsersError> {
-        let storage_file = fs::File::create(&self.storage_path)
-            .map_err(|err| ManageUsersError::Persist(err.to_string()))?;
+        let storage_path = self.storage_path.clone();
         let user_records: Vec<UserRecord> = self.into();
-        serde_yaml_ng::to_writer(storage_file, &user_records)
+        // Serializing before touching the file means a failure leaves the
+        // stored users as they were.
+        let data = serde_yaml_ng::to_string(&user_records)
             .map_err(|err| ManageUsersError::Persist(err.to_string()))?;
-
-        Ok(())
+        super::atomic::write(&storage_path, data.as_bytes())
+            .map_err(|err| ManageUsersError::Persist(err.to_string()))
     }
 }
 
 impl TryFrom<UserRecord> for User {
     type Error = String;
 
-    fn try_from(user_record: UserRecord) -> Result<Self, String> {
+    fn try_from(mut user_record: UserRecord) -> Result<Self, String> {
         let username = user_record
             .username
             .try_into()
             .map_err(|err: InvalidUsernameError| err.to_string())?;
 
+        // An enrollment that didn't complete cleanly may leave the token next
+        // to the password. The password is the one the user has chosen, while
+        // the stale token would let anybody with the link replace it, so the
+        // token is dropped and is gone from the file with the next change.
         if user_record.password.is_some() && user_record.enroll_token.is_some() {
-            Err(format!(
-                "User `{}` has both a password and an enroll token set.",
+            tracing::warn!(
+                "User `{}` has both a password and an enroll token set, the enroll token is ignored",
                 username
-            ))
-        } else if user_record.password.is_none() && user_record.enroll_token.is_none() {
-            Err(format!(
-                "User `{}` has no password or an enroll token set.",
-                username
-            ))
-        } else {
-            let enroll_token = match user_record.enroll_token {
-                Some(token) => {
-                    let token = token.try_into().map_err(|err| {
-                        format!("User `{}` has invalid enroll token: {}", username, err)
-                    })?;
+            );
 
-                    Some(token)
-                }
-                _ => None,
-            };
-
-            Ok(Self {
-                username,
-                password: user_record.password.map(secrecy::SecretString::from),
-                enroll_token,
-            })
+            user_record.enroll_token = None;
+            user_record.enroll_token_issued_at = None;
         }
+
+        // A user without a password or an enroll token is deactivated.
+        let enroll_token = match user_record.enroll_token {
+            Some(token) => {
+                let token = token.try_into().map_err(|err| {
+                    format!("User `{}` has invalid enroll token: {}", username, err)
+                })?;
+
+                Some(token)
+            }
+            _ => None,
+        };
+
+        let totp_secret = match user_record.totp_secret {
+            Some(secret) => {
+                let secret = secret.try_into().map_err(|err| {
+                    format!("User `{}` has invalid TOTP secret: {}", username, err)
+                })?;
+
+                Some(secret)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            username,
+            role: user_record.role,
+            password: user_record.password.map(secrecy::SecretString::from),
+            enroll_token,
+            enroll_token_issued_at: user_record
+                .enroll_token_issued_at
+                .map(|secs| time::UNIX_EPOCH + time::Duration::from_secs(secs)),
+            totp_secret,
+            totp_last_step: user_record.totp_last_step,
+            password_changed_at: user_record
+                .password_changed_at
+                .map(|secs| time::UNIX_EPOCH + time::Duration::from_secs(secs)),
+        })
     }
 }
 
@@ -283,26 +659,110 @@ impl From<Users> for Vec<UserRecord> {
             .into_values()
             .map(|user| UserRecord {
                 username: user.username.to_string(),
+                role: user.role,
                 password: user.password.map(|pass| pass.expose_secret().to_string()),
                 enroll_token: user
                     .enroll_token
                     .map(|token| token.0.expose_secret().to_string()),
+                enroll_token_issued_at: user.enroll_token_issued_at.map(|issued_at| {
+                    issued_at
+                        .duration_since(time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                }),
+                totp_secret: user.totp_secret.map(|secret| secret.reveal().to_string()),
+                totp_last_step: user.totp_last_step,
+                password_changed_at: user.password_changed_at.map(|changed_at| {
+                    changed_at
+                        .duration_since(time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                }),
             })
             .collect()
     }
 }
 
+// The users file is replaced on every change, so the lock is taken on a file
+// next to it, which stays in place. The lock is released once the returned
+// file is dropped.
+fn lock_storage(storage_path: &path::Path) -> Result<fs::File, ManageUsersError> {
+    let mut lock_path = storage_path.as_os_str().to_owned();
+    lock_path.push(".lock");
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(lock_path)
+        .map_err(ManageUsersError::Lock)?;
+
+    loop {
+        // SAFETY: the descriptor is owned by `file` and stays open for the duration of the call.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(file);
+        }
+
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(ManageUsersError::Lock(err));
+        }
+    }
+}
+
+// Without an admin that can log in nobody could manage the users from the web
+// interface anymore.
+fn ensure_another_admin(
+    users: &collections::HashMap<String, User>,
+    username: &Username,
+) -> Result<(), ManageUsersError> {
+    let key = username.key();
+    let is_admin = users
+        .get(&key)
+        .is_some_and(|user| user.role == Role::Admin && user.is_enrolled());
+    let has_other_admin = users
+        .iter()
+        .any(|(other, user)| *other != key && user.role == Role::Admin && user.is_enrolled());
+
+    if is_admin && !has_other_admin {
+        Err(ManageUsersError::LastAdmin(username.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn read_storage<T: serde::de::DeserializeOwned>(
+    storage_path: &path::Path,
+) -> Result<Vec<T>, ManageUsersError> {
+    let data = fs::read_to_string(storage_path).map_err(ManageUsersError::LoadStorage)?;
+
+    // An empty YAML document is not an empty list, but that's what an empty
+    // file is meant to be.
+    if data.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        serde_yaml_ng::from_str(&data).map_err(ManageUsersError::Deserialize)
+    }
+}
+
 fn parse_users(
     users: Vec<UserRecord>,
 ) -> Result<collections::HashMap<String, User>, ManageUsersError> {
-    users
-        .into_iter()
-        .map(|user| {
-            user.try_into()
-                .map_err(ManageUsersError::CorruptStorage)
-                .map(|user: User| (user.username.to_string(), user))
-        })
-        .collect()
+    let mut parsed = collections::HashMap::with_capacity(users.len());
+
+    for user in users {
+        let user: User = user.try_into().map_err(ManageUsersError::CorruptStorage)?;
+
+        if let Some(existing) = parsed.insert(user.username.key(), user) {
+            Err(ManageUsersError::CorruptStorage(format!(
+                "User `{}` is stored more than once, usernames are case-insensitive.",
+                existing.username
+            )))?;
+        }
+    }
+
+    Ok(parsed)
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -313,21 +773,76 @@ pub enum ManageUsersError {
     CorruptStorage(String),
     #[error("Failed to deserialize storage data: {}", .0)]
     Deserialize(#[source] serde_yaml_ng::Error),
-    #[error("Failed to generate enroll token: {}", .0)]
-    GenerateToken(#[from] InvalidTokenError),
+    #[error("Failed to lock users storage: {}", .0)]
+    Lock(#[source] io::Error),
     #[error("Failed to persist users data: {}", .0)]
     Persist(String),
     #[error("User not found: {}", .0)]
     NoSuchUser(String),
+    #[error("User `{}` already exists", .0)]
+    AlreadyExists(String),
+    #[error("The users file already contains {} user(s)", .0)]
+    AlreadyInitialized(usize),
+    #[error("User `{}` is the last admin, make another user an admin first", .0)]
+    LastAdmin(String),
 }
 
+#[derive(thiserror::Error, Debug)]
 pub enum PasswordError {
+    #[error("The password must be at least {0} characters long.")]
     Short(usize),
+    #[error("The password must be at most {0} characters long.")]
     Long(usize),
-    Weak,
+    #[error("The password must contain {0}.")]
+    MissingCharacter(CharacterClass),
+    #[error(
+        "The password must contain characters of at least {0} of the following kinds: a lowercase letter, an uppercase letter, a digit and a punctuation character."
+    )]
+    TooFewCharacterClasses(usize),
+    #[error("The password is too easy to guess. {0}")]
+    Guessable(String),
+    #[error("Failed to hash the password: {0}")]
     Hash(password_hash::Error),
 }
 
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Punctuation,
+}
+
+impl CharacterClass {
+    pub const ALL: [Self; 4] = [
+        Self::Lowercase,
+        Self::Uppercase,
+        Self::Digit,
+        Self::Punctuation,
+    ];
+
+    fn matches(self, ch: char) -> bool {
+        match self {
+            Self::Lowercase => ch.is_ascii_lowercase(),
+            Self::Uppercase => ch.is_ascii_uppercase(),
+            Self::Digit => ch.is_ascii_digit(),
+            Self::Punctuation => ch.is_ascii_punctuation(),
+        }
+    }
+}
+
+impl fmt::Display for CharacterClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lowercase => write!(f, "a lowercase letter"),
+            Self::Uppercase => write!(f, "an uppercase letter"),
+            Self::Digit => write!(f, "a digit"),
+            Self::Punctuation => write!(f, "a punctuation character"),
+        }
+    }
+}
+
 pub struct Password(secrecy::SecretString);
 
 impl Password {
@@ -340,8 +855,14 @@ impl Password {
             Err(PasswordError::Short(config.min_password_length))
         } else if pass.len() > config.max_password_length {
             Err(PasswordError::Long(config.max_password_length))
-        } else if !is_strong_password(pass) {
-            Err(PasswordError::Weak)
+        } else if let Err(err) = check_character_classes(
+            pass,
+            &config.password_required_classes,
+            config.password_min_classes,
+        ) {
+            Err(err)
+        } else if let Some(feedback) = check_guessability(pass, config.min_password_score) {
+            Err(PasswordError::Guessable(feedback))
         } else {
             let salt = SaltString::generate(&mut OsRng);
             let argon2 = argon2::Argon2::default();
@@ -354,33 +875,212 @@ impl Password {
     }
 }
 
-fn is_strong_password(password: &str) -> bool {
-    let mut lowercase = 0;
-    let mut uppercase = 0;
-    let mut digit = 0;
-    let mut punctuation = 0;
+// The character class rules above are a cheap pre-check, this one estimates how
+// many guesses it would take to crack the password and explains why it's weak.
+fn check_guessability(password: &str, min_score: Option<zxcvbn::Score>) -> Option<String> {
+    let min_score = min_score?;
+    let entropy = zxcvbn::zxcvbn(password, &[]);
 
-    for ch in password.chars() {
-        if ch.is_ascii_lowercase() {
-            lowercase = 1;
-        }
+    if entropy.score() >= min_score {
+        None
+    } else {
+        Some(
+            entropy
+                .feedback()
+                .map(|feedback| feedback.to_string().trim().to_string())
+                .filter(|feedback| !feedback.is_empty())
+                .unwrap_or_else(|| "Add another word or two.".to_string()),
+        )
+    }
+}
 
-        if ch.is_ascii_uppercase() {
-            uppercase = 1;
-        }
+fn check_character_classes(
+    password: &str,
+    required_classes: &[CharacterClass],
+    min_classes: usize,
+) -> Result<(), PasswordError> {
+    let present = CharacterClass::ALL
+        .into_iter()
+        .filter(|class| password.chars().any(|ch| class.matches(ch)))
+        .collect::<Vec<_>>();
+
+    if let Some(class) = required_classes
+        .iter()
+        .find(|class| !present.contains(class))
+    {
+        Err(PasswordError::MissingCharacter(*class))
+    } else if present.len() < min_classes {
+        Err(PasswordError::TooFewCharacterClasses(min_classes))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    fn username(name: &str) -> Username {
+        name.to_string().try_into().unwrap()
+    }
+
+    #[test]
+    fn enrolling_a_name_differing_only_in_case_is_rejected() {
+        let dir = testing::TempDir::new();
+        let path = dir.path().join("users.yml");
+        let users = Users::load_or_create(&path).unwrap();
+
+        users.enroll_user(username("Admin"), Role::Admin).unwrap();
+
+        assert!(matches!(
+            users.enroll_user(username("admin"), Role::Viewer),
+            Err(ManageUsersError::AlreadyExists(_))
+        ));
+
+        let users = Users::load(&path).unwrap();
+        let user = users.find_user_by_username(&username("ADMIN")).unwrap();
+        assert_eq!(user.username.to_string(), "Admin");
+        assert_eq!(user.role, Role::Admin);
+    }
+
+    #[test]
+    fn concurrent_changes_are_not_lost() {
+        let dir = testing::TempDir::new();
+        let path = dir.path().join("users.yml");
+        Users::load_or_create(&path).unwrap();
 
-        if ch.is_ascii_digit() {
-            digit = 1;
+        // Every thread starts from its own copy loaded before any change, like
+        // the web server and the command line tool would.
+        let copies: Vec<_> = (0..8).map(|_| Users::load(&path).unwrap()).collect();
+        let barrier = std::sync::Barrier::new(copies.len());
+
+        std::thread::scope(|scope| {
+            for (index, users) in copies.iter().enumerate() {
+                let barrier = &barrier;
+
+                scope.spawn(move || {
+                    barrier.wait();
+                    users
+                        .enroll_user(username(&format!("user{index}")), Role::Viewer)
+                        .unwrap();
+                });
+            }
+        });
+
+        let users = Users::load(&path).unwrap();
+        for index in 0..copies.len() {
+            assert!(
+                users
+                    .find_user_by_username(&username(&format!("user{index}")))
+                    .is_some()
+            );
         }
+    }
+
+    #[test]
+    fn totp_steps_are_only_accepted_once() {
+        let dir = testing::TempDir::new();
+        let path = dir.write("users.yml", "- username: Admin\n  password: hash\n");
+        let stale = Users::load(&path).unwrap();
+        let users = Users::load(&path).unwrap();
 
-        if ch.is_ascii_punctuation() {
-            punctuation = 1;
+        assert!(users.accept_totp_step(&username("Admin"), 100).unwrap());
+        // The copy loaded before the code was accepted doesn't know about it.
+        assert!(!stale.accept_totp_step(&username("Admin"), 100).unwrap());
+        assert!(!stale.accept_totp_step(&username("Admin"), 99).unwrap());
+        assert!(stale.accept_totp_step(&username("Admin"), 101).unwrap());
+
+        let users = Users::load(&path).unwrap();
+        let user = users.find_user_by_username(&username("Admin")).unwrap();
+        assert_eq!(user.totp_last_step, Some(101));
+    }
+
+    #[test]
+    fn lenient_load_skips_broken_records() {
+        let dir = testing::TempDir::new();
+        let path = dir.write(
+            "users.yml",
+            "- username: Admin\n  password: hash\n  role: admin\n\
+             - username: Broken\n  totp_secret: not-base32!\n",
+        );
+
+        let users = Users::load_lenient(&path).unwrap();
+
+        assert!(users.find_user_by_username(&username("Admin")).is_some());
+        assert!(users.find_user_by_username(&username("Broken")).is_none());
+        assert!(Users::load(&path).is_err());
+    }
+
+    #[test]
+    fn enroll_token_next_to_a_password_is_dropped() {
+        let dir = testing::TempDir::new();
+        let token = EnrollToken::generate();
+        let path = dir.write(
+            "users.yml",
+            &format!(
+                "- username: Admin\n  password: hash\n  enroll_token: {}\n  \
+                 enroll_token_issued_at: 1700000000\n  role: admin\n",
+                token.reveal()
+            ),
+        );
+
+        let users = Users::load(&path).unwrap();
+
+        let user = users.find_user_by_username(&username("Admin")).unwrap();
+        assert!(user.enroll_token.is_none());
+        assert!(user.enroll_token_issued_at.is_none());
+        assert!(user.password.is_some());
+
+        // The token is gone from the file with the next change.
+        users.enroll_user(username("Other"), Role::Viewer).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains(token.reveal()));
+    }
+
+    #[test]
+    fn enroll_tokens_of_the_wrong_length_are_rejected() {
+        let length = EnrollToken::TOKEN_LENGTH;
+
+        assert!(EnrollToken::try_from("a".repeat(length - 1)).is_err());
+        assert!(EnrollToken::try_from("a".repeat(length + 1)).is_err());
+        assert!(EnrollToken::try_from("").is_err());
+        assert!(EnrollToken::try_from("a".repeat(length)).is_ok());
+    }
+
+    #[test]
+    fn enroll_tokens_with_invalid_characters_are_rejected() {
+        let length = EnrollToken::TOKEN_LENGTH;
+
+        for invalid in ["/", "-", " ", "\n", "é"] {
+            let token = format!("{invalid}{}", "a".repeat(length - invalid.len()));
+
+            assert_eq!(token.len(), length);
+            assert!(EnrollToken::try_from(token).is_err(), "{invalid:?}");
         }
+    }
 
-        if lowercase + uppercase + digit + punctuation >= 3 {
-            return true;
+    #[test]
+    fn generated_enroll_tokens_are_accepted() {
+        for _ in 0..100 {
+            let token = EnrollToken::generate();
+
+            assert!(EnrollToken::try_from(token.reveal()).is_ok_and(|parsed| parsed == token));
         }
     }
 
-    false
+    #[test]
+    fn the_same_password_passes_a_permissive_policy_and_fails_a_strict_one() {
+        let password = "correcthorsebattery";
+
+        assert!(check_character_classes(password, &[], 0).is_ok());
+        assert!(matches!(
+            check_character_classes(password, &CharacterClass::ALL, 4),
+            Err(PasswordError::MissingCharacter(CharacterClass::Uppercase))
+        ));
+        assert!(matches!(
+            check_character_classes(password, &[], 3),
+            Err(PasswordError::TooFewCharacterClasses(3))
+        ));
+        assert!(check_character_classes("Correct-Horse-1", &CharacterClass::ALL, 4).is_ok());
+    }
 }
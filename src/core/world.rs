@@ -1,32 +1,47 @@
 use super::properties;
-use std::{fs, io, path};
+use std::{fs, io, os::fd::AsRawFd, path, time::SystemTime};
+
+const BACKUP_TIMESTAMP: &[time::format_description::BorrowedFormatItem<'_>] =
+    time::macros::format_description!("[year][month][day]-[hour][minute][second]");
 
 pub struct World {
-    id: path::PathBuf,
+    id: String,
     pub is_active: bool,
+    pub is_locked: bool,
+    pub is_valid: bool,
 }
 
 impl World {
-    pub fn id(&self) -> String {
-        format!("{}", self.id.display())
+    pub fn id(&self) -> &str {
+        &self.id
     }
 }
 
+pub struct DiskUsage {
+    pub size_bytes: u64,
+    pub modified: Option<SystemTime>,
+}
+
 pub struct Worlds {
+    path: path::PathBuf,
     worlds: Vec<World>,
     properties: properties::Properties,
     current_world_name: String,
+    level_prefix: String,
+    allow_empty_worlds: bool,
 }
 
 impl Worlds {
+    const MAX_WORLD_NAME_LENGTH: usize = 64;
+
     pub fn new(
         worlds_path: &path::Path,
         server_properties_path: &path::Path,
+        allow_empty_worlds: bool,
     ) -> Result<Self, WorldError> {
         let properties = properties::Properties::parse(server_properties_path)
             .map_err(WorldError::LoadServerProperties)?;
-        let current_world_name = properties.level_name();
-        let current_world = path::PathBuf::from(&current_world_name);
+        let (level_prefix, current_world_name) = split_level_name(&properties.level_name());
 
         let mut worlds = vec![];
 
@@ -39,21 +54,61 @@ impl Worlds {
                 continue;
             }
 
-            let entry_name = entry_path
-                .file_name()
-                .expect("Read the directory entry without a file name");
-            let entry_name: &path::Path = entry_name.as_ref();
+            // The level name in server.properties is text, so a directory
+            // that isn't named in UTF-8 could never be matched or switched to
+            // reliably.
+            let entry_name = match entry.file_name().into_string() {
+                Ok(entry_name) => entry_name,
+                Err(entry_name) => {
+                    tracing::warn!(
+                        "Skipping the world directory {}, its name is not valid UTF-8",
+                        entry_name.display()
+                    );
+
+                    continue;
+                }
+            };
+
+            // A lock that can't be checked is no reason to hide every other
+            // world, so the world is listed as unlocked instead.
+            let is_locked = match is_session_locked(&entry_path.join("session.lock")) {
+                Ok(is_locked) => is_locked,
+                Err(err) => {
+                    tracing::warn!(
+                        "Failed to check the session lock of the world `{entry_name}`: {err}"
+                    );
+
+                    false
+                }
+            };
+
+            // Same for a directory that can't be read, it is listed but can't
+            // be switched to.
+            let is_valid = entry_path.join("level.dat").is_file()
+                || (allow_empty_worlds
+                    && is_empty_dir(&entry_path).unwrap_or_else(|err| {
+                        tracing::warn!(
+                            "Failed to check whether the world `{entry_name}` is empty: {err}"
+                        );
+
+                        false
+                    }));
 
             worlds.push(World {
-                id: entry_name.to_owned(),
-                is_active: entry_name == current_world,
+                is_active: entry_name == current_world_name,
+                id: entry_name,
+                is_locked,
+                is_valid,
             });
         }
 
         Ok(Self {
+            path: worlds_path.to_owned(),
             worlds,
             properties,
             current_world_name,
+            level_prefix,
+            allow_empty_worlds,
         })
     }
 
@@ -61,24 +116,195 @@ impl Worlds {
         &self.worlds
     }
 
-    pub fn switch(self, world_name: String) -> Result<World, WorldError> {
-        if self.current_world_name == world_name {
-            Err(WorldError::AlreadyActive(world_name))
+    // Matches the query anywhere in the world id ignoring the case, spaces
+    // stand in for the underscores used in ids.
+    pub fn search(&self, query: &str) -> impl Iterator<Item = &World> {
+        let query = query.trim().to_lowercase().replace(' ', "_");
+
+        self.worlds
+            .iter()
+            .filter(move |world| world.id().to_lowercase().contains(&query))
+    }
+
+    pub fn disk_usage(&self, world: &World) -> Result<DiskUsage, WorldError> {
+        let mut usage = DiskUsage {
+            size_bytes: 0,
+            modified: None,
+        };
+
+        collect_disk_usage(&self.path.join(&world.id), &mut usage)
+            .map_err(WorldError::DiskUsage)?;
+
+        Ok(usage)
+    }
+
+    pub fn create(&self, name: String) -> Result<World, WorldError> {
+        if name.is_empty()
+            || name.len() > Self::MAX_WORLD_NAME_LENGTH
+            || !name
+                .chars()
+                .all(|chr| chr.is_ascii_alphanumeric() || chr == '_')
+        {
+            Err(WorldError::InvalidName(name))
+        } else if self.path.join(&name).exists() {
+            Err(WorldError::AlreadyExists(name))
         } else {
-            let world_id = path::PathBuf::from(&world_name);
+            fs::create_dir(self.path.join(&name)).map_err(WorldError::Create)?;
 
-            match self.worlds.into_iter().find(|world| world.id == world_id) {
-                Some(world) => {
-                    self.properties
-                        .with_level_name(world_name)
-                        .map_err(WorldError::Switch)?;
+            Ok(World {
+                id: name,
+                is_active: false,
+                is_locked: false,
+                is_valid: self.allow_empty_worlds,
+            })
+        }
+    }
+
+    pub fn delete(self, world_id: String) -> Result<(), WorldError> {
+        let target = path::Path::new(&world_id);
 
-                    Ok(world)
+        // Only a single normal path component is accepted, so the resolved
+        // target can never point outside of the worlds directory.
+        if world_id.is_empty()
+            || target.components().count() != 1
+            || !matches!(target.components().next(), Some(path::Component::Normal(_)))
+        {
+            Err(WorldError::InvalidName(world_id))
+        } else if self.current_world_name == world_id {
+            Err(WorldError::CannotDeleteActive(world_id))
+        } else if !self.worlds.iter().any(|world| world.id == world_id) {
+            Err(WorldError::NoSuchWorld(target.to_owned()))
+        } else {
+            fs::remove_dir_all(self.path.join(target)).map_err(WorldError::Delete)
+        }
+    }
+
+    pub fn backup(&self, backups_path: &path::Path) -> Result<path::PathBuf, WorldError> {
+        let timestamp = time::OffsetDateTime::now_utc()
+            .format(BACKUP_TIMESTAMP)
+            .map_err(|err| WorldError::Backup(io::Error::other(err)))?;
+        let backup_path = backups_path.join(format!("{}-{timestamp}", self.current_world_name));
+
+        copy_dir(&self.path.join(&self.current_world_name), &backup_path)
+            .map_err(WorldError::Backup)?;
+
+        Ok(backup_path)
+    }
+
+    // Everything that would make `switch` refuse the world, so it can be checked
+    // before the server is stopped for it.
+    pub fn check_switch(&self, world_name: &str) -> Result<&World, WorldError> {
+        if self.current_world_name == world_name {
+            Err(WorldError::AlreadyActive(world_name.to_string()))
+        } else {
+            match self.worlds.iter().find(|world| world.id == world_name) {
+                Some(world) if world.is_locked => {
+                    Err(WorldError::WorldLocked(world_name.to_string()))
                 }
-                _ => Err(WorldError::NoSuchWorld(world_id)),
+                Some(world) if !world.is_valid => {
+                    Err(WorldError::NotAWorld(world_name.to_string()))
+                }
+                Some(world) => Ok(world),
+                None => Err(WorldError::NoSuchWorld(path::PathBuf::from(world_name))),
             }
         }
     }
+
+    pub fn switch(self, world_name: String) -> Result<World, WorldError> {
+        self.check_switch(&world_name)?;
+
+        let world = self
+            .worlds
+            .into_iter()
+            .find(|world| world.id == world_name)
+            .ok_or_else(|| WorldError::NoSuchWorld(path::PathBuf::from(&world_name)))?;
+
+        self.properties
+            .with_level_name(format!("{}{world_name}", self.level_prefix))
+            .map_err(WorldError::Switch)?;
+
+        Ok(world)
+    }
+}
+
+// The level name is a path relative to the directory the server runs in, like
+// `worlds/survival`, with the world directory inside `worlds_path` being its
+// last component. The rest of the path is kept as is when switching worlds.
+fn split_level_name(level_name: &str) -> (String, String) {
+    let level_name = level_name.trim_end_matches('/');
+
+    match level_name.rsplit_once('/') {
+        Some((prefix, name)) => (format!("{prefix}/"), name.to_string()),
+        None => (String::new(), level_name.to_string()),
+    }
+}
+
+fn copy_dir(from: &path::Path, to: &path::Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_empty_dir(path: &path::Path) -> Result<bool, WorldError> {
+    Ok(fs::read_dir(path)
+        .map_err(WorldError::ReadWorldDir)?
+        .next()
+        .is_none())
+}
+
+// Minecraft holds a POSIX record lock on `session.lock` while the world is
+// loaded, so the lock is queried with `F_GETLK` rather than `flock`, which
+// doesn't see locks taken with `fcntl` on Linux.
+fn is_session_locked(lock_path: &path::Path) -> io::Result<bool> {
+    let file = match fs::File::open(lock_path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    // SAFETY: `flock` is a plain C struct for which all zeroes is a valid value.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::c_short::try_from(libc::F_WRLCK).map_err(io::Error::other)?;
+    lock.l_whence = libc::c_short::try_from(libc::SEEK_SET).map_err(io::Error::other)?;
+
+    // SAFETY: the descriptor is owned by `file` and stays open for the duration of the
+    // call, and `lock` is a valid, exclusively borrowed `flock` struct.
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(i32::from(lock.l_type) != libc::F_UNLCK)
+    }
+}
+
+fn collect_disk_usage(dir: &path::Path, usage: &mut DiskUsage) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            collect_disk_usage(&entry.path(), usage)?;
+        } else {
+            usage.size_bytes += metadata.len();
+        }
+
+        let modified = metadata.modified()?;
+        if usage.modified.is_none_or(|newest| modified > newest) {
+            usage.modified = Some(modified);
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -93,4 +319,167 @@ pub enum WorldError {
     Switch(#[source] properties::Error),
     #[error("Failed to load server.properties file: {0}")]
     LoadServerProperties(#[source] properties::Error),
+    #[error("Failed to back up the current world: {0}")]
+    Backup(#[source] io::Error),
+    #[error(r#"Invalid world name "{0}". Allowed characters are letters "a" to "z", digits "0" to "9" and the underscore "_" character."#)]
+    InvalidName(String),
+    #[error("World `{0}` already exists")]
+    AlreadyExists(String),
+    #[error("Failed to create the world directory: {0}")]
+    Create(#[source] io::Error),
+    #[error("World `{0}` is currently active and can't be deleted")]
+    CannotDeleteActive(String),
+    #[error("Failed to delete the world directory: {0}")]
+    Delete(#[source] io::Error),
+    #[error("Failed to calculate the world disk usage: {0}")]
+    DiskUsage(#[source] io::Error),
+    #[error("World `{0}` is locked by another process, make sure the Minecraft server is stopped")]
+    WorldLocked(String),
+    #[error("`{0}` doesn't look like a Minecraft world, it has no level.dat file")]
+    NotAWorld(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing;
+
+    fn worlds(dir: &testing::TempDir, level_name: &str, names: &[&str]) -> Worlds {
+        let worlds_path = dir.path().join("worlds");
+        fs::create_dir(&worlds_path).unwrap();
+        for name in names {
+            fs::create_dir(worlds_path.join(name)).unwrap();
+            fs::write(worlds_path.join(name).join("level.dat"), "").unwrap();
+        }
+        let properties = dir.write("server.properties", &format!("level-name={level_name}\n"));
+
+        Worlds::new(&worlds_path, &properties, false).unwrap()
+    }
+
+    #[test]
+    fn delete_rejects_paths_outside_of_the_worlds_directory() {
+        let dir = testing::TempDir::new();
+        fs::create_dir(dir.path().join("outside")).unwrap();
+
+        for world_id in ["../outside", "..", "survival/../../outside", "/tmp", ""] {
+            let worlds = worlds(&dir, "survival", &["survival"]);
+
+            assert!(
+                matches!(
+                    worlds.delete(world_id.to_string()),
+                    Err(WorldError::InvalidName(_))
+                ),
+                "{world_id}"
+            );
+            fs::remove_dir_all(dir.path().join("worlds")).unwrap();
+        }
+
+        assert!(dir.path().join("outside").is_dir());
+    }
+
+    #[test]
+    fn delete_refuses_the_active_world() {
+        let dir = testing::TempDir::new();
+        let worlds = worlds(&dir, "survival", &["survival", "creative"]);
+
+        assert!(matches!(
+            worlds.delete("survival".to_string()),
+            Err(WorldError::CannotDeleteActive(_))
+        ));
+        assert!(dir.path().join("worlds/survival").is_dir());
+    }
+
+    #[test]
+    fn delete_removes_an_inactive_world() {
+        let dir = testing::TempDir::new();
+        let worlds = worlds(&dir, "survival", &["survival", "creative"]);
+
+        worlds.delete("creative".to_string()).unwrap();
+
+        assert!(!dir.path().join("worlds/creative").exists());
+        assert!(dir.path().join("worlds/survival").is_dir());
+    }
+
+    #[test]
+    fn check_switch_refuses_worlds_that_cant_be_switched_to() {
+        let dir = testing::TempDir::new();
+        worlds(&dir, "survival", &["survival", "creative"]);
+        fs::create_dir(dir.path().join("worlds/empty")).unwrap();
+        let worlds = Worlds::new(
+            &dir.path().join("worlds"),
+            &dir.path().join("server.properties"),
+            false,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            worlds.check_switch("survival"),
+            Err(WorldError::AlreadyActive(_))
+        ));
+        assert!(matches!(
+            worlds.check_switch("missing"),
+            Err(WorldError::NoSuchWorld(_))
+        ));
+        assert!(matches!(
+            worlds.check_switch("empty"),
+            Err(WorldError::NotAWorld(_))
+        ));
+        assert_eq!(worlds.check_switch("creative").unwrap().id(), "creative");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("server.properties")).unwrap(),
+            "level-name=survival\n"
+        );
+    }
+
+    #[test]
+    fn worlds_not_named_in_utf8_are_skipped() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = testing::TempDir::new();
+        let worlds_path = dir.path().join("worlds");
+        fs::create_dir(&worlds_path).unwrap();
+        fs::create_dir(worlds_path.join("survival")).unwrap();
+        fs::create_dir(worlds_path.join(std::ffi::OsStr::from_bytes(b"caf\xe9"))).unwrap();
+        let properties = dir.write("server.properties", "level-name=survival\n");
+
+        let worlds = Worlds::new(&worlds_path, &properties, false).unwrap();
+
+        let ids: Vec<_> = worlds.list().iter().map(World::id).collect();
+        assert_eq!(ids, ["survival"]);
+    }
+
+    #[test]
+    fn level_names_with_spaces_are_kept_whole() {
+        assert_eq!(
+            split_level_name("my world"),
+            (String::new(), "my world".to_string())
+        );
+        assert_eq!(
+            split_level_name("saves/my world"),
+            ("saves/".to_string(), "my world".to_string())
+        );
+    }
+
+    #[test]
+    fn nested_level_names_are_split_at_the_last_component() {
+        assert_eq!(
+            split_level_name("servers/main/worlds/survival/"),
+            ("servers/main/worlds/".to_string(), "survival".to_string())
+        );
+
+        let dir = testing::TempDir::new();
+        let worlds = worlds(
+            &dir,
+            "servers/main/worlds/survival",
+            &["survival", "creative"],
+        );
+
+        let active: Vec<_> = worlds
+            .list()
+            .iter()
+            .filter(|world| world.is_active)
+            .map(World::id)
+            .collect();
+        assert_eq!(active, ["survival"]);
+    }
 }
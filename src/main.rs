@@ -6,13 +6,26 @@ use anyhow::Context;
 use clap::Parser;
 use std::path;
 
+// `-V` prints the version alone, `--version` adds the commit and the date of
+// the build, the same commit the web UI shows in its footer.
 #[derive(Parser)]
-#[command()]
+#[command(
+    version,
+    long_version = concat!(
+        env!("CARGO_PKG_VERSION"),
+        " (commit ",
+        env!("MCTRLRS_GIT_HASH"),
+        ", built on ",
+        env!("MCTRLRS_BUILD_DATE"),
+        ")"
+    )
+)]
 struct Args {
     #[command(subcommand)]
     cmd: Commands,
     #[arg(short, long, default_value = "mctrlrs.yaml")]
-    /// Path to the YAML configuration file. If absolute path is provided it will be used as is.
+    /// Path to the configuration file. The format is detected by the extension: ".yaml" or ".yml"
+    /// for YAML and ".toml" for TOML. If absolute path is provided it will be used as is.
     /// The relative path starting from "./" or "../" will be resolved using current working
     /// directory as a base path. The relative path that starts from something other than
     /// "./" or "../" will be resolved against the binary location.
@@ -23,6 +36,17 @@ struct Args {
 enum Commands {
     /// Start a web UI for server management
     Server,
+    /// Validate the configuration file and print a summary of it
+    Check,
+    /// Write an example configuration file documenting every option
+    GenerateConfig {
+        /// Where to write the configuration file
+        #[arg(default_value = "mctrlrs.yaml")]
+        output: path::PathBuf,
+        /// Overwrite the file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
     #[command(subcommand)]
     /// Manage server using command line
     Manage(Manage),
@@ -36,20 +60,49 @@ enum Manage {
     #[command(subcommand)]
     /// Manage worlds
     World(World),
+    /// Show the Minecraft server status
+    Status,
 }
 
 #[derive(clap::Subcommand, Clone)]
 enum User {
+    /// Create the users file with the first user, usually an admin, on a fresh installation
+    Init {
+        /// The username of the first user
+        username: String,
+        /// The role of the first user: "viewer", "operator" or "admin"
+        #[arg(long, default_value = "admin")]
+        role: String,
+        /// Replace the users that are already in the users file
+        #[arg(long)]
+        force: bool,
+    },
     /// Enroll a new user into the system
     Enroll {
         /// The username for a new user
         username: String,
+        /// The role of the new user: "viewer" can only look around, "operator" can also run
+        /// commands and manage worlds, "admin" can also grant operator status and read the audit log
+        #[arg(long, default_value = "operator")]
+        role: String,
     },
     /// Remove a user from the system
     Remove {
         /// The username of the user to remove
         username: String,
     },
+    /// Set a new password for an existing user
+    SetPassword {
+        /// The username of the user to set the password for
+        username: String,
+    },
+    /// Change the role of an existing user
+    SetRole {
+        /// The username of the user to change the role for
+        username: String,
+        /// The new role of the user: "viewer", "operator" or "admin"
+        role: String,
+    },
 }
 
 #[derive(clap::Subcommand, Clone)]
@@ -63,20 +116,54 @@ enum World {
     },
 }
 
-fn real_main(args: Args) -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
+// The logger is configured from the configuration file, so anything that fails
+// before it is loaded is reported with the default text logger.
+fn init_logger(format: core::LogFormat, level: tracing::Level) {
+    let logger = tracing_subscriber::fmt()
         .with_thread_names(true)
         .with_line_number(true)
         .with_level(true)
-        .with_max_level(tracing::Level::INFO)
-        .try_init()
-        .expect("Failed to configure the logger");
+        .with_max_level(level);
 
-    let config =
-        core::Config::load(args.config).with_context(|| "Failed to load configuration file")?;
+    let result = match format {
+        core::LogFormat::Text => logger.try_init(),
+        core::LogFormat::Json => logger.json().with_current_span(true).try_init(),
+    };
+
+    result.expect("Failed to configure the logger");
+}
+
+fn real_main(args: Args) -> anyhow::Result<()> {
+    // The configuration is loaded by the check itself, to report what is wrong
+    // with it in more detail, and there is none yet when generating one.
+    match args.cmd {
+        Commands::Check => {
+            init_logger(core::LogFormat::Text, tracing::Level::INFO);
+
+            return cli::config::check(&args.config).map_err(|err| err.into());
+        }
+        Commands::GenerateConfig { output, force } => {
+            init_logger(core::LogFormat::Text, tracing::Level::INFO);
+
+            return cli::config::generate(&output, force).map_err(|err| err.into());
+        }
+        _ => {}
+    }
+
+    let config = match core::Config::load(args.config) {
+        Ok(config) => config,
+        Err(err) => {
+            init_logger(core::LogFormat::Text, tracing::Level::INFO);
+
+            return Err(err).with_context(|| "Failed to load configuration file");
+        }
+    };
+
+    init_logger(config.log_format, config.log_level);
 
     match Args::parse().cmd {
         Commands::Server => web::start_server(config).with_context(|| "Web server has failed"),
+        Commands::Check | Commands::GenerateConfig { .. } => Ok(()),
         Commands::Manage(command_type) => match command_type {
             Manage::World(world) => match world {
                 World::List => cli::world::list(config.app_config)
@@ -85,11 +172,28 @@ fn real_main(args: Args) -> anyhow::Result<()> {
                     cli::world::switch(config.app_config, world_name).map_err(|err| err.into())
                 }
             },
+            Manage::Status => cli::status::show(config.app_config).map_err(|err| err.into()),
             Manage::User(user_command) => match user_command {
-                User::Enroll { username } => cli::user::enroll(config.app_config, username)
-                    .with_context(|| "Failed to enroll a new user"),
+                User::Init {
+                    username,
+                    role,
+                    force,
+                } => cli::user::init(config.app_config, username, role, force)
+                    .with_context(|| "Failed to create the first user"),
+                User::Enroll { username, role } => {
+                    cli::user::enroll(config.app_config, username, role)
+                        .with_context(|| "Failed to enroll a new user")
+                }
                 User::Remove { username } => cli::user::remove(config.app_config, username)
                     .with_context(|| "Failed to remove a new user"),
+                User::SetPassword { username } => {
+                    cli::user::set_password(config.app_config, username)
+                        .with_context(|| "Failed to set the user password")
+                }
+                User::SetRole { username, role } => {
+                    cli::user::set_role(config.app_config, username, role)
+                        .with_context(|| "Failed to set the user role")
+                }
             },
         },
     }
@@ -0,0 +1,273 @@
+use actix_web::{dev, http::header, web};
+use std::{collections::HashMap, fmt, fs, future, io, path};
+
+// Every key is expected to be in the English catalog, so it is the last resort
+// for keys missing in the other ones.
+const FALLBACK_LOCALE: &str = "en";
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogError {
+    #[error("Unable to read the locales directory: {0}")]
+    ReadDir(#[source] io::Error),
+    #[error("Unable to read the `{}` catalog: {source}", path.display())]
+    Read {
+        path: path::PathBuf,
+        source: io::Error,
+    },
+    #[error("Unable to parse the `{}` catalog: {source}", path.display())]
+    Parse {
+        path: path::PathBuf,
+        source: serde_yaml_ng::Error,
+    },
+    #[error("There is no catalog for the `{0}` locale")]
+    MissingLocale(String),
+}
+
+// A message is either looked up by its key in the catalog of the locale, or is
+// a text that can't be translated, like a response of the Minecraft server.
+pub enum Message {
+    Key {
+        key: &'static str,
+        params: Vec<(&'static str, String)>,
+    },
+    Text(String),
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self::Key {
+            key,
+            params: vec![],
+        }
+    }
+
+    pub fn text<T: Into<String>>(text: T) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn with<V: fmt::Display>(mut self, name: &'static str, value: V) -> Self {
+        if let Self::Key { params, .. } = &mut self {
+            params.push((name, value.to_string()));
+        }
+
+        self
+    }
+}
+
+impl From<&'static str> for Message {
+    fn from(key: &'static str) -> Self {
+        Self::new(key)
+    }
+}
+
+pub struct Catalogs {
+    default_locale: String,
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalogs {
+    // Each `<locale>.yml` file in the directory is a flat map of message keys to
+    // the translated messages, the file name being the language tag.
+    pub fn load(locales_path: &path::Path, default_locale: &str) -> Result<Self, CatalogError> {
+        let mut catalogs = HashMap::new();
+
+        for entry in fs::read_dir(locales_path).map_err(CatalogError::ReadDir)? {
+            let path = entry.map_err(CatalogError::ReadDir)?.path();
+
+            if path.extension().is_none_or(|extension| extension != "yml") {
+                continue;
+            }
+
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let locale = locale.to_lowercase();
+
+            let data = fs::read_to_string(&path).map_err(|source| CatalogError::Read {
+                path: path.clone(),
+                source,
+            })?;
+            let catalog = serde_yaml_ng::from_str(&data)
+                .map_err(|source| CatalogError::Parse { path, source })?;
+
+            catalogs.insert(locale, catalog);
+        }
+
+        let default_locale = default_locale.to_lowercase();
+        for locale in [FALLBACK_LOCALE, &default_locale] {
+            if !catalogs.contains_key(locale) {
+                return Err(CatalogError::MissingLocale(locale.to_string()));
+            }
+        }
+
+        Ok(Self {
+            default_locale,
+            catalogs,
+        })
+    }
+
+    // Picks the most preferred language of the Accept-Language header there is a
+    // catalog for, a regional variant falls back to the language itself.
+    fn negotiate(&self, accept_language: Option<&str>) -> String {
+        let mut languages: Vec<_> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|language| {
+                let mut parts = language.split(';');
+                let tag = parts.next()?.trim().to_lowercase();
+                let quality = parts
+                    .find_map(|part| part.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok())?;
+
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        languages
+            .into_iter()
+            .find_map(|(tag, _)| {
+                let language = tag.split('-').next().unwrap_or_default().to_string();
+
+                [tag, language]
+                    .into_iter()
+                    .find(|locale| self.catalogs.contains_key(locale))
+            })
+            .unwrap_or_else(|| self.default_locale.clone())
+    }
+
+    fn lookup(&self, locale: &str, key: &str) -> Option<&str> {
+        [locale, &self.default_locale, FALLBACK_LOCALE]
+            .into_iter()
+            .find_map(|locale| self.catalogs.get(locale)?.get(key))
+            .map(String::as_str)
+    }
+
+    fn translate(&self, locale: &str, key: &str, params: &[(&str, String)]) -> String {
+        match self.lookup(locale, key) {
+            Some(template) => interpolate(template, params),
+            None => {
+                tracing::warn!("There is no message for the `{key}` key in any catalog");
+
+                key.to_string()
+            }
+        }
+    }
+}
+
+// Replaces `{name}` placeholders with the parameter values in a single pass, so
+// the values themselves are never interpreted as placeholders.
+fn interpolate(template: &str, params: &[(&str, String)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some((before, after)) = rest.split_once('{') {
+        result.push_str(before);
+
+        match after.split_once('}') {
+            Some((name, after)) => {
+                match params.iter().find(|(param, _)| *param == name) {
+                    Some((_, value)) => result.push_str(value),
+                    None => {
+                        result.push('{');
+                        result.push_str(name);
+                        result.push('}');
+                    }
+                }
+
+                rest = after;
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+
+    result
+}
+
+#[derive(Clone)]
+pub struct Translator {
+    catalogs: web::Data<Catalogs>,
+    locale: String,
+}
+
+impl Translator {
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn translate<M: Into<Message>>(&self, message: M) -> String {
+        match message.into() {
+            Message::Key { key, params } => self.catalogs.translate(&self.locale, key, &params),
+            Message::Text(text) => text,
+        }
+    }
+
+    pub fn for_request(req: &actix_web::HttpRequest) -> Result<Self, actix_web::Error> {
+        let Some(catalogs) = req.app_data::<web::Data<Catalogs>>() else {
+            tracing::error!("The locale catalogs are not available to the request");
+
+            return Err(super::internal_server_error().into());
+        };
+        let accept_language = req
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok());
+
+        Ok(Self {
+            locale: catalogs.negotiate(accept_language),
+            catalogs: catalogs.clone(),
+        })
+    }
+}
+
+impl actix_web::FromRequest for Translator {
+    type Error = actix_web::Error;
+    type Future = future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &actix_web::HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        future::ready(Self::for_request(req))
+    }
+}
+
+// `{{t "key" name=value}}` renders a message in the locale of the page, which
+// is taken from the `locale` field of the template content.
+pub fn helper(catalogs: web::Data<Catalogs>) -> impl handlebars::HelperDef + Send + Sync {
+    move |helper: &handlebars::Helper,
+          _: &handlebars::Handlebars,
+          context: &handlebars::Context,
+          _: &mut handlebars::RenderContext,
+          out: &mut dyn handlebars::Output|
+          -> handlebars::HelperResult {
+        let key = helper
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or_else(|| handlebars::RenderErrorReason::ParamNotFoundForIndex("t", 0))?;
+        let locale = context
+            .data()
+            .get("locale")
+            .and_then(|locale| locale.as_str())
+            .unwrap_or(FALLBACK_LOCALE);
+        let params: Vec<_> = helper
+            .hash()
+            .iter()
+            .map(|(name, value)| {
+                let value = match value.value() {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+
+                (*name, value)
+            })
+            .collect();
+
+        let message = catalogs.translate(locale, key, &params);
+        out.write(&handlebars::html_escape(&message))?;
+
+        Ok(())
+    }
+}
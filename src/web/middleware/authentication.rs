@@ -1,3 +1,4 @@
+use crate::web;
 use actix_web::{FromRequest, body, dev, http};
 use std::{future, marker, pin, rc};
 
@@ -9,11 +10,26 @@ pub trait AuthSession: FromRequest {
     fn save_redirect(&self, location: String) -> Result<(), Self::SaveRedirectError>;
 }
 
-pub struct AuthMiddleware<A: AuthSession>(String, marker::PhantomData<A>);
+pub struct AuthMiddleware<A: AuthSession> {
+    login_path: String,
+    unauthorized_prefix: Option<String>,
+    auth_session: marker::PhantomData<A>,
+}
 
 impl<A: AuthSession> AuthMiddleware<A> {
     pub fn new<P: AsRef<str>>(login_path: P) -> Self {
-        Self(login_path.as_ref().to_owned(), marker::PhantomData)
+        Self {
+            login_path: login_path.as_ref().to_owned(),
+            unauthorized_prefix: None,
+            auth_session: marker::PhantomData,
+        }
+    }
+
+    // Clients of these paths can't follow a redirect to the login form, so
+    // they get a 401 response instead, just like the ones asking for JSON.
+    pub fn with_unauthorized_prefix<P: AsRef<str>>(mut self, prefix: P) -> Self {
+        self.unauthorized_prefix = Some(prefix.as_ref().to_owned());
+        self
     }
 }
 
@@ -38,7 +54,8 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         future::ready(Ok(InnerAuthMiddleware {
             service: rc::Rc::new(service),
-            login_path: self.0.clone(),
+            login_path: self.login_path.clone(),
+            unauthorized_prefix: self.unauthorized_prefix.clone(),
             auth_session: marker::PhantomData,
         }))
     }
@@ -47,6 +64,7 @@ where
 pub struct InnerAuthMiddleware<S, A> {
     service: rc::Rc<S>,
     login_path: String,
+    unauthorized_prefix: Option<String>,
     auth_session: marker::PhantomData<A>,
 }
 
@@ -70,6 +88,7 @@ where
     fn call(&self, mut req: dev::ServiceRequest) -> Self::Future {
         let svc = self.service.clone();
         let login_path = self.login_path.clone();
+        let unauthorized_prefix = self.unauthorized_prefix.clone();
 
         Box::pin(async move {
             let session = {
@@ -81,8 +100,33 @@ where
 
             let is_authenticated = session.is_authenticated()?;
 
-            if !is_authenticated && req.path() != login_path {
-                session.save_redirect(req.path().to_string())?;
+            if !is_authenticated
+                && (unauthorized_prefix
+                    .as_ref()
+                    .is_some_and(|prefix| req.path().starts_with(prefix))
+                    || super::accepts_json(req.headers()))
+            {
+                let response = actix_web::HttpResponse::Unauthorized()
+                    .insert_header((http::header::WWW_AUTHENTICATE, "Bearer"))
+                    .finish()
+                    .map_into_right_body();
+
+                let (http_request, _) = req.into_parts();
+
+                Ok(dev::ServiceResponse::new(http_request, response))
+            } else if !is_authenticated && req.path() != login_path {
+                // Only a page can be opened again after logging in, the body of
+                // any other request is gone by then.
+                if req.method() == http::Method::GET {
+                    let location = req
+                        .uri()
+                        .path_and_query()
+                        .map_or(req.path(), |path| path.as_str());
+
+                    if web::is_local_path(location) {
+                        session.save_redirect(location.to_string())?;
+                    }
+                }
 
                 let response = actix_web::HttpResponse::Found()
                     .insert_header((http::header::LOCATION, login_path))
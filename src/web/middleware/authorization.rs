@@ -0,0 +1,79 @@
+use crate::{
+    core,
+    web::{self, session},
+};
+use actix_web::{body, dev, http, middleware};
+
+// Granting operator status on the Minecraft server is as good as handing out
+// the console, the audit log shows what everyone else did, and enrolling users
+// hands out access to the panel itself. The menu in `page.hbs` leaves out the
+// pages a role can't reach, keep it in line with these.
+const ADMIN_PATHS: [&str; 4] = ["/audit", "/ops/grant", "/ops/revoke", "/users"];
+// Every user manages their own account, whatever their role is.
+const ACCOUNT_PATHS: [&str; 3] = ["/account", "/login", "/logout"];
+// The console runs commands without ever submitting a form.
+const OPERATOR_PATHS: [&str; 2] = ["/console", "/ws/console"];
+
+// Viewers can look at every page, but changing anything on the server or the
+// worlds takes an operator.
+fn required_role(req: &dev::ServiceRequest) -> core::Role {
+    let path = req.path();
+
+    if ADMIN_PATHS.iter().any(|prefix| path.starts_with(prefix)) {
+        core::Role::Admin
+    } else if ACCOUNT_PATHS.iter().any(|prefix| path.starts_with(prefix)) {
+        core::Role::Viewer
+    } else if req.method() != http::Method::GET
+        || OPERATOR_PATHS.iter().any(|prefix| path.starts_with(prefix))
+    {
+        core::Role::Operator
+    } else {
+        core::Role::Viewer
+    }
+}
+
+// Requests without a user in the session are left to the authentication
+// middleware, which either turns them away or lets them through with an API
+// token.
+pub async fn authorize(
+    mut req: dev::ServiceRequest,
+    next: middleware::Next<impl body::MessageBody + 'static>,
+) -> Result<dev::ServiceResponse<impl body::MessageBody>, actix_web::Error> {
+    let required = required_role(&req);
+
+    if required > core::Role::Viewer {
+        let user_session = req.extract::<session::UserSession>().await?;
+        let role = user_session
+            .get_current_user()
+            .map_err(|err| {
+                tracing::error!("Failed to fetch session state: {err}");
+
+                web::internal_server_error()
+            })?
+            .map(|user| user.role);
+
+        if role.is_some_and(|role| role < required) {
+            tracing::warn!(
+                "User `{}` is not allowed to access {} {}",
+                user_session.current_username().unwrap_or_default(),
+                req.method(),
+                req.path()
+            );
+
+            let response = if super::accepts_json(req.headers()) {
+                actix_web::HttpResponse::Forbidden().finish()
+            } else {
+                let flash_messages = req.extract::<session::FlashMessages>().await?;
+                flash_messages.error("auth.forbidden");
+
+                web::redirect("/")
+            };
+
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(dev::ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,66 @@
+use crate::{core, web};
+use actix_web::{body, dev, http, middleware, web as aweb};
+
+// Strips the prefix the panel is hosted under from the path of the request and
+// adds it back to the redirects, so the routes, the authentication and the
+// roles all keep matching the paths of the app itself and can't be sidestepped
+// by a path that only differs in the prefix. Requests outside of the prefix
+// don't belong to the panel.
+pub async fn strip_base_path(
+    mut req: dev::ServiceRequest,
+    next: middleware::Next<impl body::MessageBody + 'static>,
+) -> Result<dev::ServiceResponse<impl body::MessageBody>, actix_web::Error> {
+    let base_path = req
+        .app_data::<aweb::Data<core::AppConfig>>()
+        .map(|config| config.base_path.clone())
+        .unwrap_or_default();
+
+    if base_path.is_empty() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let Some(path) = strip_prefix(req.path(), &base_path) else {
+        return Ok(req
+            .into_response(actix_web::HttpResponse::NotFound().finish())
+            .map_into_boxed_body());
+    };
+
+    let path_and_query = match req.query_string() {
+        "" => path.to_string(),
+        query => format!("{path}?{query}"),
+    };
+    let mut parts = req.head().uri.clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .map_err(|_| actix_web::error::ErrorBadRequest("Invalid request path"))?,
+    );
+    let uri = http::Uri::from_parts(parts)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid request path"))?;
+    req.match_info_mut().get_mut().update(&uri);
+    req.head_mut().uri = uri;
+
+    let mut res = next.call(req).await?;
+
+    let location = res
+        .headers()
+        .get(http::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .filter(|location| web::is_local_path(location))
+        .and_then(|location| {
+            http::header::HeaderValue::try_from(format!("{base_path}{location}")).ok()
+        });
+    if let Some(location) = location {
+        res.headers_mut().insert(http::header::LOCATION, location);
+    }
+
+    Ok(res.map_into_boxed_body())
+}
+
+fn strip_prefix<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    match path.strip_prefix(base_path)? {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
@@ -0,0 +1,50 @@
+use actix_web::{body, dev, http::header, middleware};
+
+// API clients ask for JSON explicitly, browsers never list it in their defaults.
+pub fn accepts_json(headers: &header::HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_type| media_type.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+// Error pages and bodies are replaced with a JSON object for the clients asking
+// for it. Server errors don't expose the cause, it is only ever logged.
+pub async fn json_errors(
+    req: dev::ServiceRequest,
+    next: middleware::Next<impl body::MessageBody + 'static>,
+) -> Result<dev::ServiceResponse<impl body::MessageBody>, actix_web::Error> {
+    let wants_json = accepts_json(req.headers());
+    let res = next.call(req).await?;
+    let status = res.status();
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if !wants_json || is_json || !(status.is_client_error() || status.is_server_error()) {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let error = match res.response().error() {
+        Some(err) if status.is_client_error() => err.to_string(),
+        _ => status
+            .canonical_reason()
+            .unwrap_or("Something Went Wrong")
+            .to_string(),
+    };
+    let error = serde_json::json!({ "error": error }).to_string();
+
+    Ok(res.map_body(|head, _| {
+        head.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        head.headers_mut().remove(header::CONTENT_LENGTH);
+
+        body::BoxBody::new(error)
+    }))
+}
@@ -1,5 +1,19 @@
 mod authentication;
+mod authorization;
+mod base_path;
 mod conditional;
+mod json_errors;
+mod rate_limit;
+mod remember_me;
+mod request_logger;
+mod security_headers;
 
 pub use authentication::{AuthMiddleware, AuthSession};
+pub use authorization::authorize;
+pub use base_path::strip_base_path;
 pub use conditional::ConditionalMiddleware;
+pub use json_errors::{accepts_json, json_errors};
+pub use rate_limit::{RateLimitMiddleware, RateLimiter};
+pub use remember_me::{RememberMeMiddleware, mark_remembered_session};
+pub use request_logger::RequestLoggerMiddleware;
+pub use security_headers::SecurityHeadersMiddleware;
@@ -0,0 +1,228 @@
+use actix_web::{body, dev, http};
+use std::{
+    collections::{HashMap, VecDeque},
+    future, net, num, pin, rc, sync, time,
+};
+
+const WINDOW: time::Duration = time::Duration::from_secs(60);
+const PRUNE_THRESHOLD: usize = 1024;
+// Addresses are cheap to come by, with IPv6 in particular, so the number of
+// tracked clients is capped no matter how many of them are still active.
+const MAX_CLIENTS: usize = 16 * PRUNE_THRESHOLD;
+
+pub struct RateLimiter {
+    requests_per_minute: Option<num::NonZeroU32>,
+    trusted_proxy_header: Option<http::header::HeaderName>,
+    trusted_proxies: Vec<net::IpAddr>,
+    clients: sync::Mutex<HashMap<net::IpAddr, VecDeque<time::Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        requests_per_minute: Option<num::NonZeroU32>,
+        trusted_proxy_header: Option<http::header::HeaderName>,
+        trusted_proxies: Vec<net::IpAddr>,
+    ) -> Self {
+        Self {
+            requests_per_minute,
+            trusted_proxy_header,
+            trusted_proxies,
+            clients: sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    // A proxy appends the address it received the request from, so the last
+    // entry is the only one that can't be forged by the client. The header is
+    // only taken from the proxies, anybody else could put any address in it.
+    fn client_ip(&self, req: &dev::ServiceRequest) -> Option<net::IpAddr> {
+        let peer_ip = req.peer_addr().map(|addr| addr.ip());
+
+        self.trusted_proxy_header
+            .as_ref()
+            .filter(|_| peer_ip.is_some_and(|ip| self.trusted_proxies.contains(&ip)))
+            .and_then(|header| req.headers().get(header))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|value| value.trim().parse().ok())
+            .or(peer_ip)
+    }
+
+    // Returns how long the client has to wait if it exceeded the budget.
+    fn check(&self, ip: net::IpAddr) -> Option<time::Duration> {
+        let limit = self.requests_per_minute?;
+        let limit = usize::try_from(limit.get()).unwrap_or(usize::MAX);
+        let now = time::Instant::now();
+        let mut clients = self
+            .clients
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner);
+
+        if clients.len() > PRUNE_THRESHOLD {
+            clients.retain(|_, requests| {
+                requests
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < WINDOW)
+            });
+        }
+
+        // Forgetting the client seen the longest time ago lets it start over,
+        // which is better than turning away every new one.
+        if clients.len() >= MAX_CLIENTS
+            && !clients.contains_key(&ip)
+            && let Some(oldest) = clients
+                .iter()
+                .min_by_key(|(_, requests)| requests.back().copied())
+                .map(|(ip, _)| *ip)
+        {
+            clients.remove(&oldest);
+        }
+
+        let requests = clients.entry(ip).or_default();
+        while requests
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= WINDOW)
+        {
+            requests.pop_front();
+        }
+
+        if requests.len() >= limit {
+            requests
+                .front()
+                .map(|first| WINDOW.saturating_sub(now.duration_since(*first)))
+        } else {
+            requests.push_back(now);
+
+            None
+        }
+    }
+}
+
+pub struct RateLimitMiddleware(sync::Arc<RateLimiter>);
+
+impl RateLimitMiddleware {
+    pub fn new(limiter: sync::Arc<RateLimiter>) -> Self {
+        Self(limiter)
+    }
+}
+
+impl<S, B> dev::Transform<S, dev::ServiceRequest> for RateLimitMiddleware
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<body::EitherBody<B>>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = InnerRateLimitMiddleware<S>;
+    type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ready(Ok(InnerRateLimitMiddleware {
+            service: rc::Rc::new(service),
+            limiter: self.0.clone(),
+        }))
+    }
+}
+
+pub struct InnerRateLimitMiddleware<S> {
+    service: rc::Rc<S>,
+    limiter: sync::Arc<RateLimiter>,
+}
+
+impl<S, B> dev::Service<dev::ServiceRequest> for InnerRateLimitMiddleware<S>
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<body::EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = pin::Pin<Box<dyn future::Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, req: dev::ServiceRequest) -> Self::Future {
+        let retry_after = self
+            .limiter
+            .client_ip(&req)
+            .and_then(|ip| self.limiter.check(ip));
+
+        if let Some(retry_after) = retry_after {
+            let response = actix_web::HttpResponse::TooManyRequests()
+                .insert_header((
+                    http::header::RETRY_AFTER,
+                    retry_after.as_secs().max(1).to_string(),
+                ))
+                .finish()
+                .map_into_right_body();
+            let (http_request, _) = req.into_parts();
+
+            Box::pin(async move { Ok(dev::ServiceResponse::new(http_request, response)) })
+        } else {
+            let res = self.service.call(req);
+
+            Box::pin(async move { res.await.map(dev::ServiceResponse::map_into_left_body) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: &str = "203.0.113.7";
+
+    fn limiter(requests_per_minute: u32) -> RateLimiter {
+        RateLimiter::new(
+            num::NonZeroU32::new(requests_per_minute),
+            Some(http::header::HeaderName::from_static("x-forwarded-for")),
+            vec!["127.0.0.1".parse().unwrap()],
+        )
+    }
+
+    fn request(peer: &str) -> dev::ServiceRequest {
+        actix_web::test::TestRequest::default()
+            .peer_addr(net::SocketAddr::new(peer.parse().unwrap(), 40000))
+            .insert_header(("X-Forwarded-For", format!("198.51.100.1, {CLIENT}")))
+            .to_srv_request()
+    }
+
+    #[test]
+    fn the_header_is_only_taken_from_trusted_proxies() {
+        let limiter = limiter(1);
+
+        assert_eq!(
+            limiter.client_ip(&request("127.0.0.1")),
+            CLIENT.parse().ok()
+        );
+        assert_eq!(
+            limiter.client_ip(&request("192.0.2.1")),
+            "192.0.2.1".parse().ok()
+        );
+    }
+
+    #[test]
+    fn the_number_of_tracked_clients_is_capped() {
+        let limiter = limiter(1);
+
+        for index in 0..MAX_CLIENTS + 10 {
+            let ip = net::IpAddr::V6(net::Ipv6Addr::from(u128::try_from(index).unwrap()));
+
+            assert_eq!(limiter.check(ip), None);
+        }
+
+        assert_eq!(limiter.clients.lock().unwrap().len(), MAX_CLIENTS);
+        // The client seen last is still limited.
+        let last = u128::try_from(MAX_CLIENTS + 9).unwrap();
+        assert!(limiter.check(net::IpAddr::V6(last.into())).is_some());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::web::session;
+use actix_session::SessionExt;
+use actix_web::{body, cookie, dev, http::header, middleware};
+use std::{future, pin, rc};
+
+// Marks the responses of remembered sessions, it has to run inside the session
+// middleware because the session state is gone once that one is done with it.
+pub async fn mark_remembered_session(
+    req: dev::ServiceRequest,
+    next: middleware::Next<impl body::MessageBody>,
+) -> Result<dev::ServiceResponse<impl body::MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+
+    if res
+        .request()
+        .get_session()
+        .contains_key(session::REMEMBER_ME_KEY)
+    {
+        res.response_mut()
+            .extensions_mut()
+            .insert(RememberedSession);
+    }
+
+    Ok(res)
+}
+
+struct RememberedSession;
+
+// The session middleware issues browser session cookies, which are turned into
+// persistent ones for remembered sessions so they survive a browser restart.
+pub struct RememberMeMiddleware {
+    cookie_name: &'static str,
+    max_age: cookie::time::Duration,
+}
+
+impl RememberMeMiddleware {
+    pub fn new(cookie_name: &'static str, max_age: cookie::time::Duration) -> Self {
+        Self {
+            cookie_name,
+            max_age,
+        }
+    }
+}
+
+impl<S, B> dev::Transform<S, dev::ServiceRequest> for RememberMeMiddleware
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = InnerRememberMeMiddleware<S>;
+    type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ready(Ok(InnerRememberMeMiddleware {
+            service: rc::Rc::new(service),
+            cookie_name: self.cookie_name,
+            max_age: self.max_age,
+        }))
+    }
+}
+
+pub struct InnerRememberMeMiddleware<S> {
+    service: rc::Rc<S>,
+    cookie_name: &'static str,
+    max_age: cookie::time::Duration,
+}
+
+impl<S, B> dev::Service<dev::ServiceRequest> for InnerRememberMeMiddleware<S>
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = pin::Pin<Box<dyn future::Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, req: dev::ServiceRequest) -> Self::Future {
+        let res = self.service.call(req);
+        let prefix = format!("{}=", self.cookie_name);
+        let max_age = format!("; Max-Age={}", self.max_age.whole_seconds());
+
+        Box::pin(async move {
+            let mut res = res.await?;
+
+            if res
+                .response()
+                .extensions()
+                .get::<RememberedSession>()
+                .is_none()
+            {
+                return Ok(res);
+            }
+
+            let headers = res.headers_mut();
+            let cookies = headers
+                .get_all(header::SET_COOKIE)
+                .map(|value| match value.to_str() {
+                    Ok(cookie) if cookie.starts_with(&prefix) && !cookie.contains("Max-Age") => {
+                        header::HeaderValue::from_str(&format!("{cookie}{max_age}"))
+                            .unwrap_or_else(|_| value.clone())
+                    }
+                    _ => value.clone(),
+                })
+                .collect::<Vec<_>>();
+
+            headers.remove(header::SET_COOKIE);
+            for cookie in cookies {
+                headers.append(header::SET_COOKIE, cookie);
+            }
+
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,108 @@
+use actix_web::dev;
+use std::{future, pin, rc, time};
+use tracing::Instrument;
+
+const REDACTED_QUERY_PARAMS: &[&str] = &["token"];
+
+pub struct RequestLoggerMiddleware;
+
+impl<S, B> dev::Transform<S, dev::ServiceRequest> for RequestLoggerMiddleware
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = InnerRequestLoggerMiddleware<S>;
+    type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ready(Ok(InnerRequestLoggerMiddleware {
+            service: rc::Rc::new(service),
+        }))
+    }
+}
+
+pub struct InnerRequestLoggerMiddleware<S> {
+    service: rc::Rc<S>,
+}
+
+impl<S, B> dev::Service<dev::ServiceRequest> for InnerRequestLoggerMiddleware<S>
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = pin::Pin<Box<dyn future::Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    dev::forward_ready!(service);
+
+    // The username is recorded on the span by the user session once the
+    // request is authenticated, so it is only known after the inner call.
+    fn call(&self, req: dev::ServiceRequest) -> Self::Future {
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = req.path(),
+            query = tracing::field::Empty,
+            username = tracing::field::Empty,
+        );
+
+        if !req.query_string().is_empty() {
+            span.record("query", redact_query(req.query_string()));
+        }
+
+        let start = time::Instant::now();
+        let res = span.in_scope(|| self.service.call(req));
+
+        Box::pin(
+            async move {
+                let res = res.await;
+                let duration = start.elapsed();
+
+                match &res {
+                    Ok(res) => tracing::info!(
+                        status = res.status().as_u16(),
+                        ?duration,
+                        "Request completed"
+                    ),
+                    Err(err) => tracing::warn!(
+                        status = err.as_response_error().status_code().as_u16(),
+                        ?duration,
+                        "Request failed: {err}"
+                    ),
+                }
+
+                res
+            }
+            .instrument(span),
+        )
+    }
+}
+
+// Enrollment links carry the token in the query string, it must never end up
+// in the logs.
+fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if REDACTED_QUERY_PARAMS.contains(&name) => {
+                format!("{name}=[redacted]")
+            }
+            _ => param.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -0,0 +1,86 @@
+use actix_web::{dev, http::header};
+use std::{future, pin, rc};
+
+pub struct SecurityHeadersMiddleware(header::HeaderValue);
+
+impl SecurityHeadersMiddleware {
+    pub fn new(content_security_policy: header::HeaderValue) -> Self {
+        Self(content_security_policy)
+    }
+}
+
+impl<S, B> dev::Transform<S, dev::ServiceRequest> for SecurityHeadersMiddleware
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = InnerSecurityHeadersMiddleware<S>;
+    type Future = future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ready(Ok(InnerSecurityHeadersMiddleware {
+            service: rc::Rc::new(service),
+            content_security_policy: self.0.clone(),
+        }))
+    }
+}
+
+pub struct InnerSecurityHeadersMiddleware<S> {
+    service: rc::Rc<S>,
+    content_security_policy: header::HeaderValue,
+}
+
+impl<S, B> dev::Service<dev::ServiceRequest> for InnerSecurityHeadersMiddleware<S>
+where
+    S: dev::Service<
+            dev::ServiceRequest,
+            Response = dev::ServiceResponse<B>,
+            Error = actix_web::Error,
+        > + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = dev::ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = pin::Pin<Box<dyn future::Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, req: dev::ServiceRequest) -> Self::Future {
+        let res = self.service.call(req);
+        let content_security_policy = self.content_security_policy.clone();
+
+        Box::pin(async move {
+            let mut res = res.await?;
+            let headers = res.headers_mut();
+
+            headers.insert(
+                header::X_CONTENT_TYPE_OPTIONS,
+                header::HeaderValue::from_static("nosniff"),
+            );
+            headers.insert(
+                header::X_FRAME_OPTIONS,
+                header::HeaderValue::from_static("DENY"),
+            );
+            // Pages that handle secrets in their URL can opt into a stricter
+            // policy.
+            if !headers.contains_key(header::REFERRER_POLICY) {
+                headers.insert(
+                    header::REFERRER_POLICY,
+                    header::HeaderValue::from_static("same-origin"),
+                );
+            }
+            headers.insert(header::CONTENT_SECURITY_POLICY, content_security_policy);
+
+            Ok(res)
+        })
+    }
+}
@@ -1,15 +1,20 @@
+mod i18n;
 mod middleware;
 mod route;
 mod session;
 mod template;
+#[cfg(test)]
+mod testing;
+mod tls;
+
+pub use tls::ClientCertificate;
 
 use crate::core::{self, server};
 use actix_session::config;
-use actix_web::{
-    cookie::{self, time},
-    error, http, web,
-};
-use std::{fs, io, net};
+use actix_web::{cookie, dev, error, http, web};
+use secrecy::ExposeSecret;
+use std::{io, net, path, sync::Arc};
+use subtle::ConstantTimeEq;
 use tokio::signal::unix;
 use tokio_util::sync;
 
@@ -20,8 +25,17 @@ pub enum Error {
         socket: net::SocketAddr,
         source: io::Error,
     },
-    #[error("Failed to load handlebars template")]
-    Template(#[from] handlebars::TemplateError),
+    #[error("Failed to load the templates: {0}")]
+    Template(#[from] template::LoadError),
+    #[error("Failed to load the locale catalogs: {0}")]
+    Locales(#[from] i18n::CatalogError),
+    #[error(
+        "The users file {} doesn't exist, create it with `mctrlrs manage user init` or enable `create_users_file`",
+        .0.display()
+    )]
+    MissingUsersFile(path::PathBuf),
+    #[error("Failed to load the users: {0}")]
+    Users(#[from] core::ManageUsersError),
     #[error("Actix web server failed: {0}")]
     Actix(#[from] io::Error),
     #[error("Failed to configure TLS: {0}")]
@@ -32,6 +46,16 @@ pub enum Error {
     SignalHandler(io::Error, unix::SignalKind),
 }
 
+const SESSION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+const SESSION_COOKIE_NAME: &str = "id";
+const RCON_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// Login attempts and RCON backed console commands are the expensive routes
+// worth protecting from abuse.
+const RATE_LIMITED_PATHS: [&str; 3] = ["/login", "/console", "/ws/console"];
+const PUBLIC_PATHS: [&str; 6] = [
+    "/static", "/enroll", "/login", "/logout", "/healthz", "/readyz",
+];
+
 fn run_signal_handler(
     signal_token: sync::CancellationToken,
 ) -> Result<tokio::task::JoinHandle<()>, Error> {
@@ -58,6 +82,24 @@ fn run_signal_handler(
     }))
 }
 
+// Renewed certificates are picked up on SIGHUP without restarting the server.
+fn run_tls_reloader(
+    resolver: Arc<tls::CertResolver>,
+    cancel: sync::CancellationToken,
+) -> Result<tokio::task::JoinHandle<()>, Error> {
+    let mut sighup = unix::signal(unix::SignalKind::hangup())
+        .map_err(|e| Error::SignalHandler(e, unix::SignalKind::hangup()))?;
+
+    Ok(tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = sighup.recv() => resolver.reload(),
+                _ = cancel.cancelled() => break,
+            }
+        }
+    }))
+}
+
 pub fn start_server(config: core::Config) -> Result<(), Error> {
     // create a cancel token
     // start a persistent session store task
@@ -75,10 +117,18 @@ pub fn start_server(config: core::Config) -> Result<(), Error> {
         let _guard = root_token.drop_guard_ref();
 
         let signal_task = run_signal_handler(root_token.clone())?;
-        let session_file_store =
-            session::FileStore::new(&config.session_store_path, root_token.clone());
+        let session_file_store = config
+            .session_store_path
+            .as_ref()
+            .map(|path| session::FileStore::new(path, root_token.clone()));
 
-        let session_store = session::SessionStore::new(session_file_store, root_token.clone());
+        let session_store = session::SessionStore::new(
+            session_file_store,
+            SESSION_SWEEP_INTERVAL,
+            config.remember_me_ttl.unsigned_abs(),
+            config.session_idle_timeout,
+            root_token.clone(),
+        );
 
         match run_server(config, session_store.clone(), root_token.clone()).await {
             Err(err) => tracing::error!("The web server exited due to a failure: {err}"),
@@ -102,76 +152,281 @@ fn internal_server_error() -> error::InternalError<&'static str> {
     )
 }
 
+// The action has already happened by the time it is recorded, so a failure to
+// write the audit log is only reported and never fails the request.
+fn audit(
+    audit_log: &core::AuditLog,
+    username: Option<&str>,
+    action: &str,
+    details: &[(&str, &str)],
+) {
+    // Requests authenticated with the API token don't have a session user.
+    let username = username.unwrap_or("<api token>");
+
+    if let Err(err) = audit_log.record(username, action, details) {
+        tracing::error!("Failed to record `{action}` by `{username}`: {err}");
+    }
+}
+
+// Browsers treat `//host` and `/\host` as links to another site, anything else
+// starting with a slash stays on this one.
+fn is_local_path(location: &str) -> bool {
+    location.starts_with('/')
+        && !location.starts_with("//")
+        && !location.starts_with("/\\")
+        && !location.chars().any(char::is_control)
+}
+
+// Redirects never leave the site, even if a location that came from the user
+// slips through, so the panel can't be used to send people to a phishing page.
 fn redirect<P: AsRef<str>>(path: P) -> actix_web::HttpResponse {
+    let path = path.as_ref();
+    let location = if is_local_path(path) {
+        path
+    } else {
+        tracing::warn!("Refused to redirect to `{path}`, it is not a path on this site");
+
+        "/"
+    };
+
     actix_web::HttpResponse::Found()
-        .insert_header((http::header::LOCATION, path.as_ref()))
+        .insert_header((http::header::LOCATION, location))
         .finish()
 }
 
+fn requires_authentication(req: &dev::ServiceRequest, config: &core::AppConfig) -> bool {
+    !PUBLIC_PATHS.iter().any(|path| req.path().starts_with(path)) && !has_api_token(req, config)
+}
+
+// The token is compared in constant time, so the time it takes to turn a guess
+// away doesn't tell how much of it was right.
+fn has_api_token(req: &dev::ServiceRequest, config: &core::AppConfig) -> bool {
+    match &config.api_token {
+        Some(token) if req.path().starts_with("/api/") => req
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| {
+                bool::from(value.as_bytes().ct_eq(token.expose_secret().as_bytes()))
+            }),
+        _ => false,
+    }
+}
+
+// Without the users file every login would fail with an internal error, so the
+// server doesn't start until there is one.
+fn check_users_file(config: &core::AppConfig) -> Result<(), Error> {
+    if !config.users_file_path.exists() {
+        if config.create_users_file {
+            core::Users::load_or_create(&config.users_file_path)?;
+        } else {
+            return Err(Error::MissingUsersFile(config.users_file_path.clone()));
+        }
+    }
+
+    let users = core::Users::load_lenient(&config.users_file_path)?;
+
+    if users.is_empty() {
+        tracing::warn!(
+            "There are no users in {}, nobody can log in until one is enrolled with `mctrlrs manage user`",
+            config.users_file_path.display()
+        );
+    }
+
+    Ok(())
+}
+
 async fn run_server(
     config: core::Config,
     session_store: session::SessionStore,
     cancel: sync::CancellationToken,
 ) -> Result<(), Error> {
-    let mut templates = handlebars::Handlebars::new();
-    templates.register_templates_directory(
-        "./templates/",
-        handlebars::DirectorySourceOptions::default(),
-    )?;
+    let mut templates = template::load(&config.templates_path, config.reload_templates)?;
+    let catalogs = web::Data::new(i18n::Catalogs::load(
+        &config.locales_path,
+        &config.default_locale,
+    )?);
+    templates.register_helper("t", Box::new(i18n::helper(catalogs.clone())));
     let templates = web::Data::new(templates);
     let secret_key = config.cookie_key().ok_or(Error::CookieKey)?;
+    let audit_log = web::Data::new(core::AuditLog::new(
+        config.app_config.audit_log_path.clone(),
+    ));
+    check_users_file(&config.app_config)?;
     let app_config = web::Data::new(config.app_config);
+    let sessions = web::Data::new(session_store.clone());
+    let users = web::Data::new(session::UsersStore::new(
+        app_config.users_file_path.clone(),
+        cancel.clone(),
+    ));
     let client = web::Data::new(server::Client::new(
         app_config.rcon_address,
         app_config.rcon_password.clone(),
+        app_config.rcon_timeouts,
         cancel.clone(),
     ));
+    let rcon_closed = client.closed();
+
+    let rate_limiter = Arc::new(middleware::RateLimiter::new(
+        config.rate_limit,
+        config.trusted_proxy_header,
+        config.trusted_proxies,
+    ));
+
+    let content_security_policy = config.content_security_policy;
+    let static_path = config.static_path;
+    let session_ttl = config.session_ttl;
+    let remember_me_ttl = config.remember_me_ttl;
+    let session_ttl_extension = match config.session_ttl_extension {
+        core::SessionTtlExtension::OnEveryRequest => config::TtlExtensionPolicy::OnEveryRequest,
+        core::SessionTtlExtension::OnStateChanges => config::TtlExtensionPolicy::OnStateChanges,
+    };
 
     let server = actix_web::HttpServer::new(move || {
+        let auth_config = app_config.clone();
+
         actix_web::App::new()
             .app_data(templates.clone())
+            .app_data(catalogs.clone())
             .app_data(app_config.clone())
             .app_data(client.clone())
-            .service(actix_files::Files::new("/static", "./static/"))
+            .app_data(audit_log.clone())
+            .app_data(sessions.clone())
+            .app_data(users.clone())
+            .service(actix_files::Files::new("/static", &static_path))
+            .wrap(actix_web::middleware::from_fn(middleware::authorize))
             .wrap(middleware::ConditionalMiddleware::new(
-                middleware::AuthMiddleware::<session::UserSession>::new("/login"),
-                |req: &actix_web::dev::ServiceRequest| {
-                    !["/static", "/enroll", "/login"]
+                middleware::AuthMiddleware::<session::UserSession>::new("/login")
+                    .with_unauthorized_prefix("/api/"),
+                move |req: &dev::ServiceRequest| requires_authentication(req, &auth_config),
+            ))
+            .wrap(middleware::ConditionalMiddleware::new(
+                middleware::RateLimitMiddleware::new(rate_limiter.clone()),
+                |req: &dev::ServiceRequest| {
+                    RATE_LIMITED_PATHS
                         .iter()
                         .any(|path| req.path().starts_with(path))
                 },
             ))
+            .wrap(actix_web::middleware::from_fn(
+                middleware::mark_remembered_session,
+            ))
             .wrap(
                 actix_session::SessionMiddleware::builder(
                     session_store.clone(),
                     secret_key.clone(),
                 )
+                .cookie_name(SESSION_COOKIE_NAME.to_string())
                 .cookie_http_only(true)
                 .cookie_same_site(cookie::SameSite::Strict)
                 .session_lifecycle(config::SessionLifecycle::BrowserSession(
                     config::BrowserSession::default()
-                        .state_ttl(time::Duration::minutes(15))
-                        .state_ttl_extension_policy(config::TtlExtensionPolicy::OnEveryRequest),
+                        .state_ttl(session_ttl)
+                        .state_ttl_extension_policy(session_ttl_extension.clone()),
                 ))
                 .build(),
             )
+            .wrap(middleware::RememberMeMiddleware::new(
+                SESSION_COOKIE_NAME,
+                remember_me_ttl,
+            ))
+            .wrap(actix_web::middleware::from_fn(middleware::json_errors))
+            .wrap(middleware::SecurityHeadersMiddleware::new(
+                content_security_policy.clone(),
+            ))
+            .wrap(actix_web::middleware::from_fn(middleware::strip_base_path))
+            .wrap(middleware::RequestLoggerMiddleware)
             .route("/", web::get().to(route::index_get))
+            .route("/players/kick", web::post().to(route::index_kick_post))
+            .route("/say", web::post().to(route::index_say_post))
             .route("/login", web::get().to(route::login_get))
             .route("/login", web::post().to(route::login_post))
+            .route("/logout", web::post().to(route::logout_post))
+            .route("/login/2fa", web::get().to(route::totp_login_get))
+            .route("/login/2fa", web::post().to(route::totp_login_post))
             .route("/enroll", web::get().to(route::enroll_get))
             .route("/enroll", web::post().to(route::enroll_post))
             .route("/worlds", web::get().to(route::worlds_get))
             .route("/worlds", web::post().to(route::worlds_post))
+            .route("/worlds/create", web::post().to(route::worlds_create_post))
+            .route("/worlds/delete", web::post().to(route::worlds_delete_post))
+            .route("/server/start", web::post().to(route::server_start_post))
+            .route(
+                "/server/restart",
+                web::post().to(route::server_restart_post),
+            )
+            .route("/console", web::get().to(route::console_get))
+            .route("/console", web::post().to(route::console_post))
+            .route("/ws/console", web::get().to(route::console_ws))
+            .route("/logs", web::get().to(route::logs_get))
+            .route("/ws/logs", web::get().to(route::logs_ws))
+            .route("/whitelist", web::get().to(route::whitelist_get))
+            .route("/whitelist/add", web::post().to(route::whitelist_add))
+            .route("/whitelist/remove", web::post().to(route::whitelist_remove))
+            .route("/bans", web::get().to(route::bans_get))
+            .route("/bans/add", web::post().to(route::bans_add))
+            .route("/bans/remove", web::post().to(route::bans_remove))
+            .route("/ops", web::get().to(route::ops_get))
+            .route("/ops/grant", web::post().to(route::ops_grant_post))
+            .route("/ops/revoke", web::post().to(route::ops_revoke_post))
+            .route("/audit", web::get().to(route::audit_get))
+            .route("/users", web::get().to(route::users_get))
+            .route("/users/enroll", web::post().to(route::users_enroll_post))
+            .route("/users/remove", web::post().to(route::users_remove_post))
+            .route(
+                "/users/deactivate",
+                web::post().to(route::users_deactivate_post),
+            )
+            .route("/settings", web::get().to(route::settings_get))
+            .route("/maintenance", web::get().to(route::maintenance_get))
+            .route(
+                "/maintenance/save",
+                web::post().to(route::maintenance_save_post),
+            )
+            .route(
+                "/maintenance/autosave",
+                web::post().to(route::maintenance_autosave_post),
+            )
+            .route(
+                "/settings/difficulty",
+                web::post().to(route::settings_difficulty_post),
+            )
+            .route(
+                "/settings/gamemode",
+                web::post().to(route::settings_gamemode_post),
+            )
+            .route(
+                "/account/password",
+                web::get().to(route::account_password_get),
+            )
+            .route(
+                "/account/password",
+                web::post().to(route::account_password_post),
+            )
+            .route(
+                "/account/sessions",
+                web::get().to(route::account_sessions_get),
+            )
+            .route(
+                "/account/sessions/revoke",
+                web::post().to(route::account_sessions_revoke_post),
+            )
+            .route("/account/2fa", web::get().to(route::totp_setup_get))
+            .route("/account/2fa", web::post().to(route::totp_setup_post))
+            .route("/api/status", web::get().to(route::api_status_get))
+            .route("/healthz", web::get().to(route::healthz_get))
+            .route("/readyz", web::get().to(route::readyz_get))
     });
 
-    let server = if let Some(worker_count) = config.worker_count {
-        server.workers(worker_count.get())
-    } else {
-        server
-    };
+    let server = server.on_connect(tls::client_certificate);
+
+    let server = server.workers(config.worker_count.get());
 
     let server = if let Some(tls) = config.tls {
-        let tls_config = configure_tls(tls).map_err(Error::Tls)?;
+        let (tls_config, resolver) = tls::configure(tls).map_err(Error::Tls)?;
+        run_tls_reloader(resolver, cancel.clone())?;
+
         server.bind_rustls_0_23(config.listen_on, tls_config)
     } else {
         server.bind(config.listen_on)
@@ -181,59 +436,65 @@ async fn run_server(
         source: err,
     })?;
 
+    tracing::info!(
+        "Listening on {} with {} workers",
+        config.listen_on,
+        config.worker_count
+    );
+
     let server = server.shutdown_signal(async move { cancel.cancelled().await });
 
     server.run().await?;
 
+    tracing::info!("Shutting down, closing the RCON connection");
+
+    if tokio::time::timeout(RCON_SHUTDOWN_TIMEOUT, rcon_closed)
+        .await
+        .is_err()
+    {
+        tracing::warn!("Timed out waiting for the RCON connection to close");
+    }
+
     Ok(())
 }
 
-fn configure_tls(tls: core::TlsConfig) -> Result<rustls::ServerConfig, String> {
-    rustls::crypto::ring::default_provider()
-        .install_default()
-        .map_err(|_| "Failed to install the default TLS provider to ring".to_string())?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let config = rustls::ServerConfig::builder().with_no_client_auth();
+    #[test]
+    fn only_paths_on_this_site_are_local() {
+        assert!(is_local_path("/worlds"));
+        assert!(is_local_path("/worlds?q=survival&page=2"));
 
-    let key_file = fs::File::open(&tls.key).map_err(|e| {
-        format!(
-            "Failed to open a private key file `{}`: {e}",
-            tls.key.display()
-        )
-    })?;
-    let key_file = &mut io::BufReader::new(key_file);
-
-    let chain_file = fs::File::open(&tls.chain).map_err(|e| {
-        format!(
-            "Failed to open a certificate chain file `{}`: {e}",
-            tls.chain.display()
-        )
-    })?;
-    let chain_file = &mut io::BufReader::new(chain_file);
-
-    let cert_chain = rustls_pemfile::certs(chain_file)
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| {
-            format!(
-                "Failed to parse a certificate chain file `{}`: {e}",
-                tls.chain.display()
-            )
-        })?;
+        for location in [
+            "//evil.com",
+            "/\\evil.com",
+            "https://evil.com",
+            "evil.com",
+            "/worlds\r\nSet-Cookie: id=x",
+            "/\tevil.com",
+            "",
+        ] {
+            assert!(!is_local_path(location), "{location:?}");
+        }
+    }
 
-    let key = rustls_pemfile::private_key(key_file).map_err(|e| {
-        format!(
-            "Failed to parse a private key file `{}`: {e}",
-            tls.key.display()
-        )
-    })?;
-    let key = key.ok_or_else(|| {
-        format!(
-            "No keys found in a private key file `{}`",
-            tls.key.display()
-        )
-    })?;
+    #[test]
+    fn redirects_never_leave_the_site() {
+        let location = |path| {
+            redirect(path)
+                .headers()
+                .get(http::header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
 
-    config
-        .with_single_cert(cert_chain, key)
-        .map_err(|e| format!("Invalid certificate/key pair: {e}"))
+        assert_eq!(location("/worlds"), "/worlds");
+        assert_eq!(location("//evil.com"), "/");
+        assert_eq!(location("https://evil.com"), "/");
+        assert_eq!(location("/\\evil.com"), "/");
+    }
 }
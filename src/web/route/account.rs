@@ -0,0 +1,198 @@
+use super::{enroll, worlds};
+use crate::{
+    core,
+    web::{self, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct PasswordContent {}
+
+pub async fn password_get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let content = template::Content::new(&config, flash_messages, PasswordContent {})
+        .with_menu(template::ActiveMenu::Account, &user_session);
+
+    template::render_response(&templates, "password", &content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordForm {
+    current_password: secrecy::SecretString,
+    password: secrecy::SecretString,
+    repassword: secrecy::SecretString,
+}
+
+pub async fn password_post(
+    request: aweb::Form<PasswordForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    config: aweb::Data<core::AppConfig>,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let request = request.into_inner();
+    let user = match user_session.get_current_user() {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to fetch session state: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    };
+
+    match user.verify_password(request.current_password) {
+        core::PasswordVerifyResult::Valid => {}
+        core::PasswordVerifyResult::Invalid => {
+            flash_messages.error("account.incorrect_password");
+
+            return Ok(web::redirect("/account/password"));
+        }
+        core::PasswordVerifyResult::Error(err) => {
+            tracing::error!(
+                "Failed to parse PHC hash for the `{}` password: {err}",
+                user.username
+            );
+
+            return Err(web::internal_server_error().into());
+        }
+    }
+
+    let password = match enroll::verify_password(&config, request.password, request.repassword) {
+        Ok(password) => password,
+        Err(enroll::PasswordError::BadPassword(err)) => {
+            flash_messages.error(err);
+
+            return Ok(web::redirect("/account/password"));
+        }
+        Err(enroll::PasswordError::HashFailed(err)) => {
+            tracing::error!("Failed to hash the password: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    };
+
+    let updated = users.load().await.and_then(|users| {
+        users
+            .update_password(&user.username, password)
+            .map_err(Into::into)
+    });
+
+    if let Err(err) = updated {
+        tracing::error!(
+            "Failed to update the password for `{}`: {err}",
+            user.username
+        );
+
+        return Err(web::internal_server_error().into());
+    }
+
+    // Other sessions of the user are no longer valid after the change, so this
+    // one is authenticated again to keep the user logged in.
+    if let Err(err) = user_session.authenticate(user) {
+        tracing::error!("Failed to update the session state: {err}");
+
+        return Err(web::internal_server_error().into());
+    }
+
+    web::audit(
+        &audit_log,
+        Some(&user.username.to_string()),
+        "password.change",
+        &[],
+    );
+
+    flash_messages.info("account.password_changed");
+
+    Ok(web::redirect("/account/password"))
+}
+
+#[derive(serde::Serialize)]
+struct SessionView {
+    id: String,
+    last_seen: String,
+    is_current: bool,
+}
+
+#[derive(serde::Serialize)]
+struct SessionsContent {
+    sessions: Vec<SessionView>,
+}
+
+pub async fn sessions_get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    session_store: aweb::Data<session::SessionStore>,
+) -> impl actix_web::Responder {
+    let Some(username) = user_session.current_username() else {
+        return Ok(web::redirect("/login"));
+    };
+
+    let sessions = match session_store.list_user_sessions(username).await {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            tracing::error!("Failed to list the user sessions: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    };
+
+    let current_id = user_session.session_id();
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionView {
+            is_current: current_id.as_ref() == Some(&session.id),
+            last_seen: worlds::format_modified(session.last_seen),
+            id: session.id,
+        })
+        .collect();
+
+    let content = template::Content::new(&config, flash_messages, SessionsContent { sessions })
+        .with_menu(template::ActiveMenu::Account, &user_session);
+
+    template::render_response(&templates, "sessions", &content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeSessionForm {
+    session_id: String,
+}
+
+pub async fn sessions_revoke_post(
+    request: aweb::Form<RevokeSessionForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    session_store: aweb::Data<session::SessionStore>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let Some(username) = user_session.current_username() else {
+        return Ok(web::redirect("/login"));
+    };
+
+    if user_session.session_id().as_ref() == Some(&request.session_id) {
+        flash_messages.error("account.revoke_current_session");
+
+        return Ok(web::redirect("/account/sessions"));
+    }
+
+    match session_store
+        .revoke_user_session(username, request.into_inner().session_id)
+        .await
+    {
+        Ok(true) => flash_messages.info("account.session_revoked"),
+        Ok(false) => flash_messages.warning("account.session_ended"),
+        Err(err) => {
+            tracing::error!("Failed to revoke the user session: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    }
+
+    Ok(web::redirect("/account/sessions"))
+}
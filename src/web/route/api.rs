@@ -0,0 +1,65 @@
+use crate::{
+    core::{self, server},
+    web::template,
+};
+use actix_web::{http, web};
+
+#[derive(serde::Serialize)]
+struct Status {
+    version: &'static str,
+    commit: &'static str,
+    players: Vec<String>,
+    player_count: usize,
+    tick_stats: server::TickStats,
+}
+
+#[derive(serde::Serialize)]
+struct ErrorResponse {
+    error: &'static str,
+}
+
+async fn fetch_status(
+    client: &server::Client,
+    tick_command: server::TickCommand,
+) -> Result<Status, server::Error> {
+    let players = client.list().await?;
+    let tick_stats = client.query_tick(tick_command).await?;
+
+    Ok(Status {
+        version: template::APP_VERSION,
+        commit: template::GIT_HASH,
+        player_count: players.len(),
+        players,
+        tick_stats,
+    })
+}
+
+pub async fn status_get(
+    config: web::Data<core::AppConfig>,
+    client: web::Data<server::Client>,
+) -> impl actix_web::Responder {
+    match fetch_status(&client, config.tick_command).await {
+        Ok(status) => actix_web::HttpResponse::Ok().json(status),
+        Err(err) if err.is_unreachable() => {
+            tracing::warn!("The Minecraft server is unreachable: {err}");
+
+            let mut response = actix_web::HttpResponse::ServiceUnavailable();
+            if let Some(wait) = err.retry_in() {
+                response
+                    .insert_header((http::header::RETRY_AFTER, wait.as_secs().max(1).to_string()));
+            }
+
+            response.json(ErrorResponse {
+                error: "The Minecraft server is unreachable",
+            })
+        }
+        // The server answered, but not with anything that could be understood.
+        Err(err) => {
+            tracing::error!("Failed to fetch the server status: {err}");
+
+            actix_web::HttpResponse::BadGateway().json(ErrorResponse {
+                error: "Failed to communicate with the Minecraft server",
+            })
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    core,
+    web::{session, template},
+};
+use actix_web::web as aweb;
+
+const ENTRIES_PER_PAGE: usize = 50;
+
+#[derive(serde::Serialize)]
+struct AuditContent {
+    is_configured: bool,
+    entries: Vec<AuditEntryView>,
+    previous_page: Option<usize>,
+    next_page: Option<usize>,
+}
+
+#[derive(serde::Serialize)]
+struct AuditEntryView {
+    timestamp: String,
+    username: String,
+    action: String,
+    details: String,
+}
+
+impl From<core::AuditEntry> for AuditEntryView {
+    fn from(entry: core::AuditEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            username: entry.username,
+            action: entry.action,
+            details: entry
+                .details
+                .iter()
+                .map(|(key, value)| format!("{key}: {value}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct AuditQuery {
+    page: Option<usize>,
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    audit_log: aweb::Data<core::AuditLog>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    query: aweb::Query<AuditQuery>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1).saturating_mul(ENTRIES_PER_PAGE);
+
+    // One extra entry is requested to find out whether there is a next page.
+    let mut entries = match audit_log.recent(offset, ENTRIES_PER_PAGE + 1) {
+        Ok(entries) => entries,
+        Err(err) => {
+            tracing::error!("Failed to read the audit log: {err}");
+
+            flash_messages.error("audit.read_failed");
+
+            vec![]
+        }
+    };
+    let has_next_page = entries.len() > ENTRIES_PER_PAGE;
+    entries.truncate(ENTRIES_PER_PAGE);
+
+    let content = AuditContent {
+        is_configured: audit_log.is_enabled(),
+        entries: entries.into_iter().map(AuditEntryView::from).collect(),
+        previous_page: Some(page - 1).filter(|page| *page > 0),
+        next_page: has_next_page.then_some(page + 1),
+    };
+    let content = template::Content::new(&config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Audit, &user_session);
+
+    template::render_response(&templates, "audit", &content)
+}
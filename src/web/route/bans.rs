@@ -0,0 +1,155 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct BansContent {
+    players: Vec<server::Ban>,
+    ips: Vec<server::Ban>,
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    client: aweb::Data<server::Client>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let bans = match client.banlist().await {
+        Ok(bans) => bans,
+        Err(err) => {
+            tracing::error!("Failed to get the ban list: {err}");
+
+            flash_messages.error("bans.fetch_failed");
+
+            server::BanList {
+                players: vec![],
+                ips: vec![],
+            }
+        }
+    };
+
+    let content = BansContent {
+        players: bans.players,
+        ips: bans.ips,
+    };
+
+    let content = template::Content::new(&config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Bans, &user_session);
+
+    template::render_response(&templates, "bans", &content)
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BanKind {
+    Player,
+    Ip,
+}
+
+impl BanKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Player => "player",
+            Self::Ip => "ip",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct BanForm {
+    kind: BanKind,
+    target: String,
+    #[serde(default)]
+    reason: String,
+}
+
+pub async fn add(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<BanForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let target = request.target.trim();
+    let result = match request.kind {
+        BanKind::Player => {
+            let reason = Some(request.reason.trim()).filter(|reason| !reason.is_empty());
+
+            client.ban(target, reason).await
+        }
+        BanKind::Ip => client.ban_ip(target).await,
+    };
+
+    match result {
+        Ok(server::Update::Updated(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "ban.add",
+                &[
+                    ("kind", request.kind.as_str()),
+                    ("target", target),
+                    ("reason", request.reason.trim()),
+                ],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to ban {target}: {err}");
+
+            flash_messages.error("bans.update_failed");
+        }
+    }
+
+    web::redirect("/bans")
+}
+
+pub async fn remove(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<BanForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let target = request.target.trim();
+    let result = match request.kind {
+        BanKind::Player => client.pardon(target).await,
+        BanKind::Ip => client.pardon_ip(target).await,
+    };
+
+    match result {
+        Ok(server::Update::Updated(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "ban.remove",
+                &[("kind", request.kind.as_str()), ("target", target)],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to unban {target}: {err}");
+
+            flash_messages.error("bans.update_failed");
+        }
+    }
+
+    web::redirect("/bans")
+}
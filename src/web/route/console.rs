@@ -0,0 +1,158 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize, Default)]
+struct ConsoleContent {
+    command: String,
+    output: Option<String>,
+}
+
+impl ConsoleContent {
+    fn with_command(command: String) -> Self {
+        Self {
+            command,
+            output: None,
+        }
+    }
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let content = template::Content::new(&config, flash_messages, ConsoleContent::default())
+        .with_menu(template::ActiveMenu::Console, &user_session);
+
+    template::render_response(&templates, "console", &content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct CommandForm {
+    command: String,
+}
+
+pub async fn post(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<CommandForm>,
+    flash_messages: session::FlashMessages,
+    audit_log: aweb::Data<core::AuditLog>,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let command = request.into_inner().command.trim().to_string();
+
+    if command.is_empty() {
+        flash_messages.error("console.empty_command");
+
+        return Ok(web::redirect("/console"));
+    }
+
+    if !config.console_filter.is_permitted(&command) {
+        flash_messages.error("console.not_permitted");
+
+        let content = template::Content::new(
+            &config,
+            flash_messages,
+            ConsoleContent::with_command(command),
+        )
+        .with_menu(template::ActiveMenu::Console, &user_session);
+
+        return template::render_response(&templates, "console", &content);
+    }
+
+    web::audit(
+        &audit_log,
+        user_session.current_username().as_deref(),
+        "console.command",
+        &[("command", &command)],
+    );
+
+    let output = match client.command(command.clone()).await {
+        Ok(output) => Some(server::strip_formatting(&output)),
+        Err(err) => {
+            tracing::error!("Failed to run the console command: {err}");
+
+            flash_messages.error("console.run_failed");
+
+            None
+        }
+    };
+
+    let content =
+        template::Content::new(&config, flash_messages, ConsoleContent { command, output })
+            .with_menu(template::ActiveMenu::Console, &user_session);
+
+    template::render_response(&templates, "console", &content)
+}
+
+// RCON is a request/response protocol, so the socket only carries the output
+// of the commands sent through it and never unsolicited server log lines.
+pub async fn ws(
+    request: actix_web::HttpRequest,
+    body: aweb::Payload,
+    config: aweb::Data<core::AppConfig>,
+    client: aweb::Data<server::Client>,
+    audit_log: aweb::Data<core::AuditLog>,
+    user_session: session::UserSession,
+    translator: i18n::Translator,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let (response, mut session, mut stream) = actix_ws::handle(&request, body)?;
+    let client = client.into_inner();
+    let audit_log = audit_log.into_inner();
+    let username = user_session.current_username();
+    let console_filter = config.console_filter.clone();
+
+    actix_web::rt::spawn(async move {
+        while let Some(Ok(message)) = stream.recv().await {
+            let sent = match message {
+                actix_ws::Message::Text(command) => {
+                    let command = command.trim();
+                    let output = if command.is_empty() {
+                        translator.translate("console.empty_command")
+                    } else if !console_filter.is_permitted(command) {
+                        translator.translate("console.not_permitted")
+                    } else {
+                        web::audit(
+                            &audit_log,
+                            username.as_deref(),
+                            "console.command",
+                            &[("command", command)],
+                        );
+
+                        match client.command(command.to_string()).await {
+                            Ok(output) => server::strip_formatting(&output),
+                            Err(err) => {
+                                tracing::error!("Failed to run the console command: {err}");
+
+                                translator.translate("console.run_failed")
+                            }
+                        }
+                    };
+
+                    session.text(output).await
+                }
+                actix_ws::Message::Ping(bytes) => session.pong(&bytes).await,
+                actix_ws::Message::Close(reason) => {
+                    let _ = session.close(reason).await;
+
+                    return;
+                }
+                _ => Ok(()),
+            };
+
+            if sent.is_err() {
+                return;
+            }
+        }
+
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
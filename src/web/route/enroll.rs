@@ -1,76 +1,101 @@
 use crate::web::{
-    self as core_web, core, internal_server_error, middleware::AuthSession, session, template,
+    self, core, i18n, internal_server_error, middleware::AuthSession, session, template,
 };
-use actix_web::web;
+use actix_web::{Responder, http::header, web as aweb};
 use secrecy::ExposeSecret;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
-    token: String,
+    token: Option<String>,
 }
 
 enum TokenState {
     Valid(String),
+    Expired,
     Invalid,
     Error,
 }
 
 #[derive(serde::Serialize)]
 struct EnrollForm {
-    token: String,
     username: String,
 }
 
+// The token is only ever sent in the enrollment link, after that it is kept in
+// the session so it doesn't end up in redirects or the referrer of the page.
+fn no_referrer<R: Responder>(response: R) -> actix_web::CustomizeResponder<R> {
+    response
+        .customize()
+        .insert_header((header::REFERRER_POLICY, "no-referrer"))
+}
+
 pub async fn get(
     session: session::UserSession,
-    query: web::Query<Parameters>,
-    templates: web::Data<handlebars::Handlebars<'_>>,
-    config: web::Data<core::AppConfig>,
+    query: aweb::Query<Parameters>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    config: aweb::Data<core::AppConfig>,
+    users: aweb::Data<session::UsersStore>,
     flash_messages: session::FlashMessages,
 ) -> impl actix_web::Responder {
-    let query = query.into_inner();
-    match session.is_authenticated() {
-        Ok(true) => {
-            flash_messages.warning("You are already authenticated, no need to re-enroll.");
+    let token = query.into_inner().token.or_else(|| session.enroll_token());
+    let response = match (session.is_authenticated(), token) {
+        (Ok(true), _) => {
+            flash_messages.warning("enroll.already_authenticated");
 
-            Ok(core_web::redirect("/"))
+            Ok(web::redirect("/"))
         }
-        Ok(false) => match validate_token(&config.into_inner(), &query.token) {
-            TokenState::Valid(username) => {
-                let content = template::Content::new(
-                    flash_messages,
-                    EnrollForm {
-                        token: query.token,
-                        username,
-                    },
-                );
-                template::render_response(&templates, "enroll", &content)
+        (Ok(false), None) => {
+            flash_messages.error("enroll.invalid_token");
+            Ok(web::redirect("/login"))
+        }
+        (Ok(false), Some(token)) => match validate_token(&config, &users, &token).await {
+            TokenState::Valid(username) => match session.save_enroll_token(&token) {
+                Ok(()) => {
+                    let content =
+                        template::Content::new(&config, flash_messages, EnrollForm { username });
+                    template::render_response(&templates, "enroll", &content)
+                }
+                Err(err) => {
+                    tracing::error!("Failed to save the enroll token in the session: {err}");
+
+                    Err(web::internal_server_error().into())
+                }
+            },
+            TokenState::Expired => {
+                session.forget_enroll_token();
+                flash_messages.error("enroll.expired_token");
+                Ok(web::redirect("/login"))
             }
             TokenState::Invalid => {
-                flash_messages.error("Provided enroll token is invalid.");
-                Ok(core_web::redirect("/login"))
+                session.forget_enroll_token();
+                flash_messages.error("enroll.invalid_token");
+                Ok(web::redirect("/login"))
             }
-            TokenState::Error => Err(core_web::internal_server_error().into()),
+            TokenState::Error => Err(web::internal_server_error().into()),
         },
-        Err(err) => {
+        (Err(err), _) => {
             tracing::error!("Failed to fetch session state: {err}");
 
-            Err(core_web::internal_server_error().into())
+            Err(web::internal_server_error().into())
         }
-    }
+    };
+
+    no_referrer(response)
 }
 
-fn validate_token(config: &core::AppConfig, token: &str) -> TokenState {
+async fn validate_token(
+    config: &core::AppConfig,
+    users: &session::UsersStore,
+    token: &str,
+) -> TokenState {
     let token_result = token.try_into();
     match token_result {
-        Ok(token) => match core::Users::load(&config.users_file_path) {
-            Ok(users) => {
-                if let Some(username) = users.find_username_by_token(token) {
-                    TokenState::Valid(username.to_string())
-                } else {
-                    TokenState::Invalid
-                }
-            }
+        Ok(token) => match users.load().await {
+            Ok(users) => match users.verify_enroll_token(token, config.enroll_token_ttl) {
+                core::EnrollTokenStatus::Valid(username) => TokenState::Valid(username.to_string()),
+                core::EnrollTokenStatus::Expired => TokenState::Expired,
+                core::EnrollTokenStatus::Invalid => TokenState::Invalid,
+            },
             Err(err) => {
                 tracing::error!("Failed to load users to verify enroll token: {err}");
 
@@ -83,27 +108,44 @@ fn validate_token(config: &core::AppConfig, token: &str) -> TokenState {
 
 #[derive(serde::Deserialize)]
 pub struct EnrollRequest {
-    token: String,
     password: secrecy::SecretString,
     repassword: secrecy::SecretString,
 }
 
 pub async fn post(
-    request: web::Form<EnrollRequest>,
+    session: session::UserSession,
+    request: aweb::Form<EnrollRequest>,
     flash_messages: session::FlashMessages,
-    config: web::Data<core::AppConfig>,
+    config: aweb::Data<core::AppConfig>,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
 ) -> impl actix_web::Responder {
     let request = request.into_inner();
+    let Some(token) = session.enroll_token() else {
+        flash_messages.error("enroll.invalid_token");
 
-    match verify_password(&config, request.password, request.repassword) {
-        Ok(password) => match change_password(&config, request.token, password) {
-            EnrollResult::Ok => {
-                flash_messages.info("The user was successfully enrolled.");
-                Ok(core_web::redirect("/login"))
+        return no_referrer(Ok(web::redirect("/login")));
+    };
+
+    let response = match verify_password(&config, request.password, request.repassword) {
+        Ok(password) => match change_password(&config, &users, token, password).await {
+            EnrollResult::Ok(username) => {
+                session.forget_enroll_token();
+                web::audit(&audit_log, Some(&username.to_string()), "enroll", &[]);
+
+                flash_messages.info("enroll.enrolled");
+                flash_messages.info("enroll.totp_hint");
+                Ok(web::redirect("/login"))
             }
             EnrollResult::BadToken => {
-                flash_messages.error("Provided enroll token is invalid.");
-                Ok(core_web::redirect("/login"))
+                session.forget_enroll_token();
+                flash_messages.error("enroll.invalid_token");
+                Ok(web::redirect("/login"))
+            }
+            EnrollResult::ExpiredToken => {
+                session.forget_enroll_token();
+                flash_messages.error("enroll.expired_token");
+                Ok(web::redirect("/login"))
             }
             EnrollResult::Other(reason) => {
                 tracing::error!("Failed to enroll the user: {reason}");
@@ -119,38 +161,40 @@ pub async fn post(
             }
             PasswordError::BadPassword(err) => {
                 flash_messages.error(err);
-                Ok(core_web::redirect(format!(
-                    "/enroll?token={}",
-                    request.token
-                )))
+                Ok(web::redirect("/enroll"))
             }
         },
-    }
+    };
+
+    no_referrer(response)
 }
 
 enum EnrollResult {
-    Ok,
+    Ok(core::Username),
     BadToken,
+    ExpiredToken,
     Other(String),
 }
 
-fn change_password(
+async fn change_password(
     config: &core::AppConfig,
+    users: &session::UsersStore,
     token: String,
     password: core::Password,
 ) -> EnrollResult {
     match token.try_into() {
-        Ok(token) => match core::Users::load(&config.users_file_path) {
-            Ok(users) => match users.find_username_by_token(token) {
-                Some(username) => {
+        Ok(token) => match users.load().await {
+            Ok(users) => match users.verify_enroll_token(token, config.enroll_token_ttl) {
+                core::EnrollTokenStatus::Valid(username) => {
                     let username = username.clone();
                     if let Err(err) = users.update_password(&username, password) {
                         EnrollResult::Other(err.to_string())
                     } else {
-                        EnrollResult::Ok
+                        EnrollResult::Ok(username)
                     }
                 }
-                _ => EnrollResult::BadToken,
+                core::EnrollTokenStatus::Expired => EnrollResult::ExpiredToken,
+                core::EnrollTokenStatus::Invalid => EnrollResult::BadToken,
             },
             Err(err) => EnrollResult::Other(format!("{err}")),
         },
@@ -158,41 +202,50 @@ fn change_password(
     }
 }
 
-enum PasswordError {
-    BadPassword(String),
+pub(super) enum PasswordError {
+    BadPassword(i18n::Message),
     HashFailed(String),
 }
 
 impl From<core::PasswordError> for PasswordError {
     fn from(value: core::PasswordError) -> Self {
         match value {
-            core::PasswordError::Short(len) => Self::BadPassword(format!(
-                "A password must be longer than {} characters. Please use a longer password!",
-                len
-            )),
-            core::PasswordError::Long(len) => Self::BadPassword(format!(
-                "A password must be shorter than {} characters. Please use a shorter password!",
-                len
-            )),
-            core::PasswordError::Weak => Self::BadPassword(
-                "A password must contain a lowercase letter, an uppercase letter, \
-            a digit and a punctuation character. Please use another password!"
-                    .to_string(),
+            core::PasswordError::Short(len) => {
+                Self::BadPassword(i18n::Message::new("password.too_short").with("length", len))
+            }
+            core::PasswordError::Long(len) => {
+                Self::BadPassword(i18n::Message::new("password.too_long").with("length", len))
+            }
+            core::PasswordError::MissingCharacter(class) => {
+                Self::BadPassword(i18n::Message::new(match class {
+                    core::CharacterClass::Lowercase => "password.missing_lowercase",
+                    core::CharacterClass::Uppercase => "password.missing_uppercase",
+                    core::CharacterClass::Digit => "password.missing_digit",
+                    core::CharacterClass::Punctuation => "password.missing_punctuation",
+                }))
+            }
+            core::PasswordError::TooFewCharacterClasses(count) => Self::BadPassword(
+                i18n::Message::new("password.too_few_classes").with("count", count),
+            ),
+            // The feedback comes from the strength estimator, which only speaks
+            // English.
+            core::PasswordError::Guessable(feedback) => Self::BadPassword(
+                i18n::Message::new("password.guessable").with("feedback", feedback),
             ),
             core::PasswordError::Hash(err) => Self::HashFailed(err.to_string()),
         }
     }
 }
 
-fn verify_password(
+pub(super) fn verify_password(
     config: &core::AppConfig,
     password: secrecy::SecretString,
     repassword: secrecy::SecretString,
 ) -> Result<core::Password, PasswordError> {
     if password.expose_secret() != repassword.expose_secret() {
-        Err(PasswordError::BadPassword(
-            "Passwords do not match. Please try again!".to_string(),
-        ))
+        Err(PasswordError::BadPassword(i18n::Message::new(
+            "password.mismatch",
+        )))
     } else {
         Ok(core::Password::new(password, config)?)
     }
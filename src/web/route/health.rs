@@ -0,0 +1,59 @@
+use crate::core::{self, server};
+use actix_web::web;
+use std::{fs, time};
+
+const RCON_CHECK_TIMEOUT: time::Duration = time::Duration::from_secs(2);
+
+// Liveness only tells whether the web server is able to respond at all, so it
+// must not depend on anything else.
+pub async fn healthz_get() -> impl actix_web::Responder {
+    actix_web::HttpResponse::Ok()
+        .content_type("text/plain")
+        .body("ok")
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReadyQuery {
+    #[serde(default)]
+    rcon: bool,
+}
+
+// The RCON check is opt-in, the web interface is still useful for starting a
+// Minecraft server that is down.
+pub async fn readyz_get(
+    config: web::Data<core::AppConfig>,
+    client: web::Data<server::Client>,
+    query: web::Query<ReadyQuery>,
+) -> impl actix_web::Responder {
+    if let Err(err) = fs::File::open(&config.users_file_path) {
+        tracing::warn!("Readiness check failed, the users file is not readable: {err}");
+
+        return not_ready("users file is not readable");
+    }
+
+    if query.rcon {
+        match tokio::time::timeout(RCON_CHECK_TIMEOUT, client.list()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                tracing::warn!("Readiness check failed, RCON is not available: {err}");
+
+                return not_ready("rcon is not available");
+            }
+            Err(_) => {
+                tracing::warn!("Readiness check failed, RCON did not respond in time");
+
+                return not_ready("rcon did not respond in time");
+            }
+        }
+    }
+
+    actix_web::HttpResponse::Ok()
+        .content_type("text/plain")
+        .body("ok")
+}
+
+fn not_ready(reason: &'static str) -> actix_web::HttpResponse {
+    actix_web::HttpResponse::ServiceUnavailable()
+        .content_type("text/plain")
+        .body(reason)
+}
@@ -1,62 +1,162 @@
 use crate::{
-    core::server,
-    web::{session, template},
+    core::{self, server},
+    web::{self, i18n, session, template},
 };
-use actix_web::web;
+use actix_web::web as aweb;
 
 #[derive(serde::Serialize)]
 struct IndexContent {
     players: Vec<String>,
-    player_summary: String,
+    player_summary: &'static str,
+    player_count: usize,
     tick_stats: Option<server::TickStats>,
+    is_offline: bool,
+    retry_in: Option<u64>,
+    can_start: bool,
 }
 
 pub async fn get(
-    templates: web::Data<handlebars::Handlebars<'_>>,
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
     flash_messages: session::FlashMessages,
-    client: web::Data<server::Client>,
+    user_session: session::UserSession,
+    client: aweb::Data<server::Client>,
 ) -> impl actix_web::Responder {
-    let (player_summary, players) = match client.list().await {
+    let (player_summary, players, is_offline, retry_in) = match client.list().await {
         Ok(players) => {
             let summary = match players.len() {
-                0 => "There are no players online".to_string(),
-                1 => "There is 1 player online".to_string(),
-                len => format!("There are {len} players online"),
+                0 => "index.no_players",
+                1 => "index.one_player",
+                _ => "index.players_online",
             };
 
-            (summary, players)
+            (summary, players, false, None)
+        }
+        Err(err) if err.is_unreachable() => {
+            tracing::warn!("The Minecraft server is unreachable: {err}");
+
+            let retry_in = err.retry_in().map(|wait| wait.as_secs().max(1));
+
+            ("index.offline", vec![], true, retry_in)
         }
         Err(err) => {
             tracing::error!("Failed to get the list of players: {err}");
 
-            flash_messages.error("Failed to communicate with the Minecraft server.");
+            flash_messages.error("server.communication_failed");
 
-            (
-                String::from("Unable to fetch a list of online players"),
-                vec![],
-            )
+            ("index.list_failed", vec![], false, None)
         }
     };
 
-    let tick_stats = match client.query_tick().await {
-        Ok(stats) => Some(stats),
-        Err(err) => {
-            tracing::error!("Failed to query tick stats from the server: {err}");
+    // There is no point in paying for another connection attempt when the
+    // server is known to be down.
+    let tick_stats = if is_offline {
+        None
+    } else {
+        match client.query_tick(config.tick_command).await {
+            Ok(stats) => Some(stats),
+            Err(err) => {
+                tracing::error!("Failed to query tick stats from the server: {err}");
 
-            flash_messages.error("Failed to fetch tick stats from the Minecraft server.");
+                flash_messages.error("index.tick_stats_failed");
 
-            None
+                None
+            }
         }
     };
 
     let content = IndexContent {
         player_summary,
+        player_count: players.len(),
         players,
         tick_stats,
+        is_offline,
+        retry_in,
+        can_start: config.start_command.is_some(),
     };
 
-    let content =
-        template::Content::new(flash_messages, content).with_menu(template::ActiveMenu::Home);
+    let content = template::Content::new(&config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Home, &user_session);
 
     template::render_response(templates.as_ref(), "index", &content)
 }
+
+#[derive(serde::Deserialize)]
+pub struct KickForm {
+    player: String,
+    reason: String,
+}
+
+pub async fn kick_post(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<KickForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let reason = Some(request.reason.trim()).filter(|reason| !reason.is_empty());
+
+    let player = request.player.trim();
+    match client.kick_player(player, reason).await {
+        Ok(server::KickResult::Kicked(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "player.kick",
+                &[("player", player), ("reason", reason.unwrap_or_default())],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::KickResult::NotFound(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to kick the player: {err}");
+
+            flash_messages.error("index.kick_failed");
+        }
+    }
+
+    web::redirect("/")
+}
+
+#[derive(serde::Deserialize)]
+pub struct SayForm {
+    message: String,
+}
+
+pub async fn say_post(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<SayForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let message = request.message.trim();
+    match client.say(message).await {
+        Ok(()) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "server.say",
+                &[("message", message)],
+            );
+
+            flash_messages.info("index.say_sent")
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to broadcast the message: {err}");
+
+            flash_messages.error("index.say_failed");
+        }
+    }
+
+    web::redirect("/")
+}
@@ -1,33 +1,32 @@
 use crate::{
     core,
-    web::{self as core_web, middleware::AuthSession, session, template},
+    web::{self, middleware::AuthSession, session, template},
 };
-use actix_web::web;
+use actix_web::web as aweb;
 use std::fmt;
 
 #[derive(serde::Serialize)]
 struct LoginForm {}
 
 pub async fn get(
-    templates: web::Data<handlebars::Handlebars<'_>>,
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
     flash_messages: session::FlashMessages,
     user_session: session::UserSession,
 ) -> impl actix_web::Responder {
+    // Logging out takes the logout button, visiting the login page again only
+    // takes the user back to where they were going.
     match user_session.is_authenticated() {
-        Ok(true) => {
-            user_session.purge();
-
-            Ok(core_web::redirect("/login"))
-        }
+        Ok(true) => Ok(web::redirect(user_session.take_redirect_location())),
         Ok(false) => {
-            let data = template::Content::new(flash_messages, LoginForm {});
+            let data = template::Content::new(&config, flash_messages, LoginForm {});
 
             template::render_response(&templates, "login", &data)
         }
         Err(err) => {
             tracing::error!("Failed to render the login page: {err}");
 
-            Err(core_web::internal_server_error().into())
+            Err(web::internal_server_error().into())
         }
     }
 }
@@ -36,24 +35,46 @@ pub async fn get(
 pub struct LoginRequest {
     username: String,
     password: secrecy::SecretString,
+    #[serde(default)]
+    remember_me: Option<String>,
 }
 
 pub async fn post(
-    request: web::Form<LoginRequest>,
+    http_request: actix_web::HttpRequest,
+    request: aweb::Form<LoginRequest>,
     flash_messages: session::FlashMessages,
-    config: web::Data<core::AppConfig>,
     session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
 ) -> impl actix_web::Responder {
     let request = request.into_inner();
+    let remember_me = request.remember_me.is_some();
     match request.username.try_into() {
-        Ok(username) => match core::Users::load(&config.users_file_path) {
+        Ok(username) => match users.load().await {
             Ok(users) => match users.find_user_by_username(&username) {
                 Some(user) => match user.verify_password(request.password) {
+                    core::PasswordVerifyResult::Valid if user.has_totp() => {
+                        log_client_certificate(&http_request, &user.username);
+
+                        if session.start_second_factor(user).is_err()
+                            || session.remember(remember_me).is_err()
+                        {
+                            Err(internal_server_error("Failed to update the session state"))
+                        } else {
+                            Ok(web::redirect("/login/2fa"))
+                        }
+                    }
                     core::PasswordVerifyResult::Valid => {
-                        if session.authenticate(user).is_err() {
+                        log_client_certificate(&http_request, &user.username);
+
+                        if session.authenticate(user).is_err()
+                            || session.remember(remember_me).is_err()
+                        {
                             Err(internal_server_error("Failed to update the session state"))
                         } else {
-                            Ok(core_web::redirect(session.get_redirect_location()))
+                            web::audit(&audit_log, Some(&user.username.to_string()), "login", &[]);
+
+                            Ok(web::redirect(session.take_redirect_location()))
                         }
                     }
                     core::PasswordVerifyResult::Error(err) => Err(internal_server_error(format!(
@@ -72,14 +93,110 @@ pub async fn post(
     }
 }
 
+// Logging out only takes a POST request, which other sites can't make with the
+// session cookie as it is limited to same-site requests, so a link or an image
+// elsewhere can't end the session.
+pub async fn logout_post(
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    if let Some(username) = user_session.current_username() {
+        web::audit(&audit_log, Some(&username), "logout", &[]);
+    }
+
+    user_session.purge();
+
+    web::redirect("/login")
+}
+
+fn log_client_certificate(http_request: &actix_web::HttpRequest, username: &core::Username) {
+    if let Some(cert) = http_request.conn_data::<web::ClientCertificate>() {
+        tracing::info!(
+            "User `{username}` logged in using a client certificate for `{}`",
+            cert.subject
+        );
+    }
+}
+
 fn bad_credentials(flash_messages: &session::FlashMessages) -> actix_web::HttpResponse {
-    flash_messages.error("Invalid username or password. Please try again.");
+    flash_messages.error("login.bad_credentials");
 
-    core_web::redirect("/login")
+    web::redirect("/login")
 }
 
 fn internal_server_error(log: impl fmt::Display) -> actix_web::Error {
     tracing::error!("{log}");
 
-    core_web::internal_server_error().into()
+    web::internal_server_error().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::testing;
+    use actix_web::{http, test};
+
+    #[actix_web::test]
+    async fn login_page_redirects_authenticated_users_and_keeps_the_session() {
+        let env = testing::TestEnv::new("");
+        let app = test::init_service(
+            env.app()
+                .route("/", aweb::get().to(|| async { "home" }))
+                .route("/login", aweb::get().to(get))
+                .route("/login", aweb::post().to(post)),
+        )
+        .await;
+
+        let res = test::call_service(&app, testing::login_request(None).to_request()).await;
+        assert_eq!(testing::location(&res), Some("/"));
+        let session = testing::session_cookie(&res).unwrap();
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/login")
+                .cookie(session.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), http::StatusCode::FOUND);
+        assert_eq!(testing::location(&res), Some("/"));
+        let session = testing::session_cookie(&res).unwrap_or(session);
+        assert!(!session.value().is_empty());
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/")
+                .cookie(session)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn login_returns_to_the_page_with_its_query() {
+        let env = testing::TestEnv::new("");
+        let app = test::init_service(
+            env.app()
+                .route("/worlds", aweb::get().to(|| async { "worlds" }))
+                .route("/login", aweb::post().to(post)),
+        )
+        .await;
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/worlds?filter=x")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(testing::location(&res), Some("/login"));
+        let session = testing::session_cookie(&res).unwrap();
+
+        let res =
+            test::call_service(&app, testing::login_request(Some(&session)).to_request()).await;
+        assert_eq!(testing::location(&res), Some("/worlds?filter=x"));
+    }
 }
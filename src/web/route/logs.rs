@@ -0,0 +1,106 @@
+use crate::{
+    core,
+    web::{session, template},
+};
+use actix_web::web as aweb;
+use std::time;
+
+const DEFAULT_LINES: usize = 100;
+const MAX_LINES: usize = 1000;
+const FOLLOW_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+#[derive(serde::Serialize)]
+struct LogsContent {
+    is_configured: bool,
+    lines: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LogsQuery {
+    lines: Option<usize>,
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    query: aweb::Query<LogsQuery>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let max_lines = query.lines.unwrap_or(DEFAULT_LINES).min(MAX_LINES);
+    let lines = match &config.log_path {
+        Some(log_path) => match core::tail_log(log_path, max_lines) {
+            Ok(lines) => lines,
+            Err(err) => {
+                tracing::error!("Failed to read the server log: {err}");
+
+                flash_messages.error("logs.read_failed");
+
+                vec![]
+            }
+        },
+        None => vec![],
+    };
+
+    let content = LogsContent {
+        is_configured: config.log_path.is_some(),
+        lines,
+    };
+    let content = template::Content::new(&config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Logs, &user_session);
+
+    template::render_response(&templates, "logs", &content)
+}
+
+pub async fn ws(
+    config: aweb::Data<core::AppConfig>,
+    request: actix_web::HttpRequest,
+    body: aweb::Payload,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let Some(log_path) = config.log_path.clone() else {
+        return Ok(actix_web::HttpResponse::NotFound().finish());
+    };
+
+    let mut follower = core::LogFollower::new(&log_path, MAX_LINES).map_err(|err| {
+        tracing::error!("Failed to open the server log: {err}");
+
+        actix_web::error::ErrorInternalServerError("Failed to open the server log")
+    })?;
+    let (response, mut session, mut stream) = actix_ws::handle(&request, body)?;
+
+    actix_web::rt::spawn(async move {
+        let mut interval = tokio::time::interval(FOLLOW_INTERVAL);
+
+        loop {
+            let sent = tokio::select! {
+                message = stream.recv() => match message {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => session.pong(&bytes).await,
+                    Some(Ok(actix_ws::Message::Close(reason))) => {
+                        let _ = session.close(reason).await;
+
+                        return;
+                    }
+                    Some(Ok(_)) => Ok(()),
+                    Some(Err(_)) | None => break,
+                },
+                _ = interval.tick() => match follower.read_new_lines() {
+                    Ok(lines) if lines.is_empty() => Ok(()),
+                    Ok(lines) => session.text(lines.join("\n")).await,
+                    Err(err) => {
+                        tracing::warn!("Failed to follow the server log: {err}");
+
+                        Ok(())
+                    }
+                },
+            };
+
+            if sent.is_err() {
+                return;
+            }
+        }
+
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,107 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let content = template::Content::new(&config, flash_messages, ())
+        .with_menu(template::ActiveMenu::Maintenance, &user_session);
+
+    template::render_response(&templates, "maintenance", &content)
+}
+
+pub async fn save_post(
+    client: aweb::Data<server::Client>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    match client.save_all_flush().await {
+        Ok(()) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "world.save",
+                &[],
+            );
+
+            flash_messages.info("maintenance.saved")
+        }
+        Err(err @ server::Error::Save(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to save the world: {err}");
+
+            flash_messages.error("maintenance.save_failed");
+        }
+    }
+
+    web::redirect("/maintenance")
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Autosave {
+    On,
+    Off,
+}
+
+impl Autosave {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct AutosaveForm {
+    value: Autosave,
+}
+
+pub async fn autosave_post(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<AutosaveForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let result = match request.value {
+        Autosave::On => client.save_on().await,
+        Autosave::Off => client.save_off().await,
+    };
+
+    match result {
+        Ok(server::Update::Updated(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "world.autosave",
+                &[("value", request.value.as_str())],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::Autosave(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to change automatic saving: {err}");
+
+            flash_messages.error("maintenance.autosave_failed");
+        }
+    }
+
+    web::redirect("/maintenance")
+}
@@ -1,9 +1,55 @@
+mod account;
+mod api;
+mod audit;
+mod bans;
+mod console;
 mod enroll;
+mod health;
 mod index;
 mod login;
+mod logs;
+mod maintenance;
+mod ops;
+mod server;
+mod settings;
+mod totp;
+mod users;
+mod whitelist;
 mod worlds;
 
+pub use account::{
+    password_get as account_password_get, password_post as account_password_post,
+    sessions_get as account_sessions_get, sessions_revoke_post as account_sessions_revoke_post,
+};
+pub use api::status_get as api_status_get;
+pub use audit::get as audit_get;
+pub use bans::{add as bans_add, get as bans_get, remove as bans_remove};
+pub use console::{get as console_get, post as console_post, ws as console_ws};
 pub use enroll::{get as enroll_get, post as enroll_post};
-pub use index::get as index_get;
-pub use login::{get as login_get, post as login_post};
-pub use worlds::{get as worlds_get, post as worlds_post};
+pub use health::{healthz_get, readyz_get};
+pub use index::{get as index_get, kick_post as index_kick_post, say_post as index_say_post};
+pub use login::{get as login_get, logout_post, post as login_post};
+pub use logs::{get as logs_get, ws as logs_ws};
+pub use maintenance::{
+    autosave_post as maintenance_autosave_post, get as maintenance_get,
+    save_post as maintenance_save_post,
+};
+pub use ops::{get as ops_get, grant_post as ops_grant_post, revoke_post as ops_revoke_post};
+pub use server::{restart_post as server_restart_post, start_post as server_start_post};
+pub use settings::{
+    difficulty_post as settings_difficulty_post, gamemode_post as settings_gamemode_post,
+    get as settings_get,
+};
+pub use totp::{
+    login_get as totp_login_get, login_post as totp_login_post, setup_get as totp_setup_get,
+    setup_post as totp_setup_post,
+};
+pub use users::{
+    deactivate_post as users_deactivate_post, enroll_post as users_enroll_post, get as users_get,
+    remove_post as users_remove_post,
+};
+pub use whitelist::{add as whitelist_add, get as whitelist_get, remove as whitelist_remove};
+pub use worlds::{
+    create_post as worlds_create_post, delete_post as worlds_delete_post, get as worlds_get,
+    post as worlds_post,
+};
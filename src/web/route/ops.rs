@@ -0,0 +1,159 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct OpsContent {
+    operators: Vec<core::Operator>,
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let operators = match core::load_operators(&config.ops_path) {
+        Ok(operators) => operators,
+        Err(err) => {
+            tracing::error!("Failed to load the operators list: {err}");
+
+            flash_messages.error("ops.fetch_failed");
+
+            vec![]
+        }
+    };
+
+    let content = template::Content::new(&config, flash_messages, OpsContent { operators })
+        .with_menu(template::ActiveMenu::Ops, &user_session);
+
+    template::render_response(&templates, "ops", &content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct GrantForm {
+    player: String,
+    password: secrecy::SecretString,
+}
+
+// Operators can run any command on the server, so granting the status requires
+// the panel user to confirm their password first.
+pub async fn grant_post(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<GrantForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let request = request.into_inner();
+    let user = match user_session.get_current_user() {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to get the current user: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    };
+
+    match user.verify_password(request.password) {
+        core::PasswordVerifyResult::Valid => {}
+        core::PasswordVerifyResult::Invalid => {
+            tracing::warn!(
+                "User `{}` failed the password confirmation to grant operator status",
+                user.username
+            );
+
+            flash_messages.error("ops.invalid_password");
+
+            return Ok(web::redirect("/ops"));
+        }
+        core::PasswordVerifyResult::Error(err) => {
+            tracing::error!(
+                "Failed to parse PHC hash for the `{}` password: {err}",
+                user.username
+            );
+
+            return Err(web::internal_server_error().into());
+        }
+    }
+
+    let player = request.player.trim();
+    match client.op(player).await {
+        Ok(server::Update::Updated(message)) => {
+            tracing::warn!(
+                "User `{}` granted operator status to `{player}`",
+                user.username
+            );
+            web::audit(
+                &audit_log,
+                Some(&user.username.to_string()),
+                "op.grant",
+                &[("player", player)],
+            );
+
+            flash_messages.info(i18n::Message::text(message));
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to grant operator status: {err}");
+
+            flash_messages.error("ops.grant_failed");
+        }
+    }
+
+    Ok(web::redirect("/ops"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeForm {
+    player: String,
+}
+
+pub async fn revoke_post(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<RevokeForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let player = request.player.trim();
+    match client.deop(player).await {
+        Ok(server::Update::Updated(message)) => {
+            let username = user_session.current_username();
+
+            tracing::warn!(
+                "User `{}` revoked operator status from `{player}`",
+                username.as_deref().unwrap_or("<api token>")
+            );
+            web::audit(
+                &audit_log,
+                username.as_deref(),
+                "op.revoke",
+                &[("player", player)],
+            );
+
+            flash_messages.info(i18n::Message::text(message));
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to revoke operator status: {err}");
+
+            flash_messages.error("ops.revoke_failed");
+        }
+    }
+
+    web::redirect("/ops")
+}
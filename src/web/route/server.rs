@@ -0,0 +1,147 @@
+use crate::{
+    core::{self, server},
+    web::{self, session},
+};
+use actix_web::web as aweb;
+use std::time;
+
+const SHUTDOWN_GRACE_PERIOD: time::Duration = time::Duration::from_secs(60);
+const SHUTDOWN_POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+pub async fn start_post(
+    config: aweb::Data<core::AppConfig>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    match &config.start_command {
+        Some(command) => {
+            if start(command, &flash_messages) {
+                web::audit(
+                    &audit_log,
+                    user_session.current_username().as_deref(),
+                    "server.start",
+                    &[],
+                );
+            }
+        }
+        None => flash_messages.error("server.start_not_configured"),
+    }
+
+    web::redirect("/")
+}
+
+pub async fn restart_post(
+    config: aweb::Data<core::AppConfig>,
+    client: aweb::Data<server::Client>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let Some(command) = &config.start_command else {
+        flash_messages.error("server.start_not_configured");
+
+        return web::redirect("/");
+    };
+
+    match client.save_all().await {
+        Ok(()) => {
+            flash_messages.info("server.world_saved");
+
+            match client.stop().await {
+                // The server may drop the connection before replying to `stop`.
+                Ok(()) | Err(server::Error::BrokenConnection(_)) => {
+                    flash_messages.info("server.stopping")
+                }
+                Err(err) => {
+                    tracing::error!("{err}");
+
+                    flash_messages.error("server.stop_failed");
+
+                    return web::redirect("/");
+                }
+            }
+        }
+        Err(err) if err.is_unreachable() => {
+            tracing::warn!("{err}");
+
+            flash_messages.warning("server.not_running");
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+
+            flash_messages.error("server.save_failed");
+
+            return web::redirect("/");
+        }
+    }
+
+    // The server is down from here on, whether it comes back up or not.
+    web::audit(
+        &audit_log,
+        user_session.current_username().as_deref(),
+        "server.restart",
+        &[],
+    );
+
+    if wait_for_shutdown(&config).await {
+        start(command, &flash_messages);
+    } else {
+        flash_messages.error("server.shutdown_timeout");
+    }
+
+    web::redirect("/")
+}
+
+// The active world stays locked until the server process has finished writing
+// it to disk and exited, so the lock is a reliable signal that it is gone.
+pub(super) async fn wait_for_shutdown(config: &core::AppConfig) -> bool {
+    let deadline = time::Instant::now() + SHUTDOWN_GRACE_PERIOD;
+
+    loop {
+        let is_locked = match core::Worlds::new(
+            &config.worlds_path,
+            &config.server_properties_path,
+            config.allow_empty_worlds,
+        ) {
+            Ok(worlds) => worlds
+                .list()
+                .iter()
+                .any(|world| world.is_active && world.is_locked),
+            Err(err) => {
+                tracing::error!("Failed to load worlds: {err}");
+
+                return false;
+            }
+        };
+
+        if !is_locked {
+            return true;
+        } else if time::Instant::now() >= deadline {
+            return false;
+        }
+
+        tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+    }
+}
+
+// Returns whether the server was started.
+pub(super) fn start(
+    command: &server::StartCommand,
+    flash_messages: &session::FlashMessages,
+) -> bool {
+    match server::start(command) {
+        Ok(()) => {
+            flash_messages.info("server.starting");
+
+            true
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+
+            flash_messages.error("server.start_failed");
+
+            false
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct SettingOption {
+    value: &'static str,
+    label: String,
+    selected: bool,
+}
+
+#[derive(serde::Serialize)]
+struct SettingsContent {
+    difficulties: Vec<SettingOption>,
+    game_modes: Vec<SettingOption>,
+}
+
+// The labels are looked up in the locale catalogs, e.g. `settings.difficulty_easy`.
+fn options(
+    label_prefix: &str,
+    values: &[&'static str],
+    current: Option<&str>,
+) -> Vec<SettingOption> {
+    values
+        .iter()
+        .map(|&value| SettingOption {
+            value,
+            label: format!("{label_prefix}_{value}"),
+            selected: current == Some(value),
+        })
+        .collect()
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    match core::Properties::parse(&config.server_properties_path) {
+        Ok(properties) => {
+            let content = SettingsContent {
+                difficulties: options(
+                    "settings.difficulty",
+                    &server::DIFFICULTIES,
+                    properties.get("difficulty"),
+                ),
+                game_modes: options(
+                    "settings.game_mode",
+                    &server::GAME_MODES,
+                    properties.get("gamemode"),
+                ),
+            };
+            let content = template::Content::new(&config, flash_messages, content)
+                .with_menu(template::ActiveMenu::Settings, &user_session);
+
+            template::render_response(&templates, "settings", &content)
+        }
+        Err(err) => {
+            tracing::error!("Failed to load server.properties: {err}");
+
+            Err(web::internal_server_error().into())
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct SettingForm {
+    value: String,
+}
+
+pub async fn difficulty_post(
+    config: aweb::Data<core::AppConfig>,
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<SettingForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let response = client.set_difficulty(&request.value).await;
+
+    update_setting(
+        &config,
+        "difficulty",
+        &request.value,
+        response,
+        &flash_messages,
+        &audit_log,
+        user_session.current_username().as_deref(),
+    )
+}
+
+pub async fn gamemode_post(
+    config: aweb::Data<core::AppConfig>,
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<SettingForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let response = client.set_default_gamemode(&request.value).await;
+
+    update_setting(
+        &config,
+        "gamemode",
+        &request.value,
+        response,
+        &flash_messages,
+        &audit_log,
+        user_session.current_username().as_deref(),
+    )
+}
+
+// The RCON commands only change the running server, so the value is also
+// written to server.properties to survive restarts and show up on the page.
+fn update_setting(
+    config: &core::AppConfig,
+    key: &str,
+    value: &str,
+    response: Result<String, server::Error>,
+    flash_messages: &session::FlashMessages,
+    audit_log: &core::AuditLog,
+    username: Option<&str>,
+) -> actix_web::HttpResponse {
+    match response {
+        Ok(message) => {
+            web::audit(
+                audit_log,
+                username,
+                &format!("settings.{key}"),
+                &[("value", value)],
+            );
+
+            flash_messages.info(i18n::Message::text(message));
+
+            if let Err(err) = core::Properties::parse(&config.server_properties_path)
+                .and_then(|properties| properties.set(key, value))
+            {
+                tracing::error!("Failed to update server.properties: {err}");
+
+                flash_messages.warning("settings.save_failed");
+            }
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to update the {key} setting: {err}");
+
+            flash_messages.error("server.communication_failed");
+        }
+    }
+
+    web::redirect("/settings")
+}
@@ -0,0 +1,233 @@
+use crate::{
+    core,
+    web::{self, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct TotpLoginForm {}
+
+pub async fn login_get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    match user_session.get_second_factor_user() {
+        Ok(Some(_)) => {
+            let content = template::Content::new(&config, flash_messages, TotpLoginForm {});
+
+            template::render_response(&templates, "totp_login", &content)
+        }
+        Ok(None) => Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to fetch session state: {err}");
+
+            Err(web::internal_server_error().into())
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TotpForm {
+    code: String,
+}
+
+pub async fn login_post(
+    request: aweb::Form<TotpForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let user = match user_session.get_second_factor_user() {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to fetch session state: {err}");
+
+            return Err(web::internal_server_error());
+        }
+    };
+
+    let is_valid = match user.verify_totp(&request.code) {
+        Ok(Some(step)) => match users.load().await.and_then(|users| {
+            users
+                .accept_totp_step(&user.username, step)
+                .map_err(Into::into)
+        }) {
+            Ok(is_accepted) => is_accepted,
+            Err(err) => {
+                tracing::error!(
+                    "Failed to record the authentication code for `{}`: {err}",
+                    user.username
+                );
+
+                return Err(web::internal_server_error());
+            }
+        },
+        Ok(None) => false,
+        Err(err) => {
+            tracing::error!(
+                "Failed to verify the authentication code for `{}`: {err}",
+                user.username
+            );
+
+            return Err(web::internal_server_error());
+        }
+    };
+
+    if !is_valid {
+        return match user_session.second_factor_failed() {
+            Ok(true) => {
+                flash_messages.error("totp.invalid_code");
+
+                Ok(web::redirect("/login/2fa"))
+            }
+            Ok(false) => {
+                tracing::warn!(
+                    "Too many invalid authentication codes for `{}`, the password is required again",
+                    user.username
+                );
+
+                flash_messages.error("totp.too_many_attempts");
+
+                Ok(web::redirect("/login"))
+            }
+            Err(err) => {
+                tracing::error!("Failed to update the session state: {err}");
+
+                Err(web::internal_server_error())
+            }
+        };
+    }
+
+    if let Err(err) = user_session.authenticate(user) {
+        tracing::error!("Failed to update the session state: {err}");
+
+        Err(web::internal_server_error())
+    } else {
+        web::audit(
+            &audit_log,
+            Some(&user.username.to_string()),
+            "login",
+            &[("second_factor", "totp")],
+        );
+
+        Ok(web::redirect(user_session.take_redirect_location()))
+    }
+}
+
+#[derive(serde::Serialize, Default)]
+struct TotpSetupContent {
+    enabled: bool,
+    secret: String,
+    uri: String,
+}
+
+pub async fn setup_get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let user = match user_session.get_current_user() {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to fetch session state: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+    };
+
+    let content = if user.has_totp() {
+        TotpSetupContent {
+            enabled: true,
+            ..Default::default()
+        }
+    } else {
+        let secret = core::TotpSecret::generate();
+
+        let uri = match secret.provisioning_uri(user.username.to_string()) {
+            Ok(uri) => uri,
+            Err(err) => {
+                tracing::error!("Failed to generate the TOTP provisioning URI: {err}");
+
+                return Err(web::internal_server_error().into());
+            }
+        };
+
+        if let Err(err) = user_session.save_totp_setup_secret(&secret) {
+            tracing::error!("Failed to update the session state: {err}");
+
+            return Err(web::internal_server_error().into());
+        }
+
+        TotpSetupContent {
+            enabled: false,
+            secret: secret.reveal().to_string(),
+            uri,
+        }
+    };
+
+    let content = template::Content::new(&config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Account, &user_session);
+
+    template::render_response(&templates, "totp_setup", &content)
+}
+
+pub async fn setup_post(
+    request: aweb::Form<TotpForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+) -> impl actix_web::Responder {
+    let username = match user_session.get_current_user() {
+        Ok(Some(user)) => user.username.clone(),
+        Ok(None) => return Ok(web::redirect("/login")),
+        Err(err) => {
+            tracing::error!("Failed to fetch session state: {err}");
+
+            return Err(web::internal_server_error());
+        }
+    };
+
+    let Some(secret) = user_session.take_totp_setup_secret() else {
+        flash_messages.error("totp.setup_expired");
+
+        return Ok(web::redirect("/account/2fa"));
+    };
+
+    match secret.verify(username.to_string(), &request.code) {
+        Ok(true) => match users.load().await {
+            Ok(users) => match users.set_totp_secret(&username, secret) {
+                Ok(()) => {
+                    flash_messages.info("totp.enabled");
+
+                    Ok(web::redirect("/"))
+                }
+                Err(err) => {
+                    tracing::error!("Failed to save the TOTP secret for `{username}`: {err}");
+
+                    Err(web::internal_server_error())
+                }
+            },
+            Err(err) => {
+                tracing::error!("Failed to load users: {err}");
+
+                Err(web::internal_server_error())
+            }
+        },
+        Ok(false) => {
+            flash_messages.error("totp.setup_invalid_code");
+
+            Ok(web::redirect("/account/2fa"))
+        }
+        Err(err) => {
+            tracing::error!("Failed to verify the authentication code for `{username}`: {err}");
+
+            Err(web::internal_server_error())
+        }
+    }
+}
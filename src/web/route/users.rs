@@ -0,0 +1,312 @@
+use crate::{
+    core,
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct UserView {
+    username: String,
+    role_label: String,
+    is_enrolled: bool,
+    is_deactivated: bool,
+    has_totp: bool,
+}
+
+impl From<&core::User> for UserView {
+    fn from(user: &core::User) -> Self {
+        Self {
+            username: user.username.to_string(),
+            role_label: format!("users.role_{}", user.role),
+            is_enrolled: user.is_enrolled(),
+            is_deactivated: user.is_deactivated(),
+            has_totp: user.has_totp(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct UsersContent {
+    users: Vec<UserView>,
+    enroll_url: Option<String>,
+    enroll_link_hours: u64,
+}
+
+async fn render(
+    config: &core::AppConfig,
+    templates: &handlebars::Handlebars<'_>,
+    flash_messages: session::FlashMessages,
+    user_session: &session::UserSession,
+    users: &session::UsersStore,
+    enroll_url: Option<url::Url>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let mut users = match users.load().await {
+        Ok(users) => users.iter().map(UserView::from).collect(),
+        Err(err) => {
+            tracing::error!("Failed to load the users: {err}");
+
+            flash_messages.error("users.fetch_failed");
+
+            vec![]
+        }
+    };
+    users.sort_by_key(|user: &UserView| user.username.to_lowercase());
+
+    let content = UsersContent {
+        users,
+        enroll_url: enroll_url.map(String::from),
+        enroll_link_hours: config.enroll_token_ttl.as_secs() / 60 / 60,
+    };
+    let content = template::Content::new(config, flash_messages, content)
+        .with_menu(template::ActiveMenu::Users, user_session);
+
+    template::render_response(templates, "users", &content)
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    render(
+        &config,
+        &templates,
+        flash_messages,
+        &user_session,
+        &users,
+        None,
+    )
+    .await
+}
+
+#[derive(serde::Deserialize)]
+pub struct EnrollForm {
+    username: String,
+    role: String,
+}
+
+// The page with the enrollment link is rendered right away instead of
+// redirecting to it, so the token is shown only once and is never kept in the
+// session.
+pub async fn enroll_post(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    request: aweb::Form<EnrollForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> Result<actix_web::HttpResponse, actix_web::Error> {
+    let request = request.into_inner();
+    let username: core::Username = match request.username.trim().to_string().try_into() {
+        Ok(username) => username,
+        Err(err) => {
+            flash_messages.error(i18n::Message::text(err.to_string()));
+
+            return Ok(web::redirect("/users"));
+        }
+    };
+    let role: core::Role = match request.role.parse() {
+        Ok(role) => role,
+        Err(err) => {
+            flash_messages.error(i18n::Message::text(err.to_string()));
+
+            return Ok(web::redirect("/users"));
+        }
+    };
+
+    let enrolled = match users.load().await {
+        Ok(users) => users.enroll_user(username.clone(), role),
+        Err(err) => {
+            tracing::error!("Failed to load the users: {err}");
+
+            flash_messages.error("users.enroll_failed");
+
+            return Ok(web::redirect("/users"));
+        }
+    };
+
+    // Enrolling an existing user again would throw away their password, the
+    // check for it runs under the lock of the users file.
+    let token = match enrolled {
+        Ok(token) => token,
+        Err(core::ManageUsersError::AlreadyExists(_)) => {
+            flash_messages
+                .error(i18n::Message::new("users.already_exists").with("username", &username));
+
+            return Ok(web::redirect("/users"));
+        }
+        Err(err) => {
+            tracing::error!("Failed to enroll `{username}`: {err}");
+
+            flash_messages.error("users.enroll_failed");
+
+            return Ok(web::redirect("/users"));
+        }
+    };
+
+    let admin = user_session.current_username();
+    tracing::warn!(
+        "User `{}` enrolled `{username}` as {role}",
+        admin.as_deref().unwrap_or("<api token>")
+    );
+    web::audit(
+        &audit_log,
+        admin.as_deref(),
+        "user.enroll",
+        &[
+            ("username", &username.to_string()),
+            ("role", &role.to_string()),
+        ],
+    );
+
+    flash_messages.info(i18n::Message::new("users.enrolled").with("username", &username));
+
+    render(
+        &config,
+        &templates,
+        flash_messages,
+        &user_session,
+        &users,
+        Some(config.enroll_url(&token)),
+    )
+    .await
+}
+
+#[derive(serde::Deserialize)]
+pub struct ManageForm {
+    username: String,
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Remove,
+    Deactivate,
+}
+
+impl Action {
+    fn audit_action(self) -> &'static str {
+        match self {
+            Self::Remove => "user.remove",
+            Self::Deactivate => "user.deactivate",
+        }
+    }
+
+    fn done_message(self) -> &'static str {
+        match self {
+            Self::Remove => "users.removed",
+            Self::Deactivate => "users.deactivated",
+        }
+    }
+}
+
+async fn manage(
+    action: Action,
+    request: ManageForm,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: &session::UsersStore,
+    audit_log: &core::AuditLog,
+) -> actix_web::HttpResponse {
+    if request.confirm.is_none() {
+        flash_messages.error("users.confirm");
+
+        return web::redirect("/users");
+    }
+
+    let username: core::Username = match request.username.try_into() {
+        Ok(username) => username,
+        Err(err) => {
+            flash_messages.error(i18n::Message::text(err.to_string()));
+
+            return web::redirect("/users");
+        }
+    };
+
+    let result = match users.load().await {
+        Ok(users) => match action {
+            Action::Remove => users.remove(&username),
+            Action::Deactivate => users.deactivate(&username),
+        },
+        Err(err) => {
+            tracing::error!("Failed to load the users: {err}");
+
+            flash_messages.error("users.update_failed");
+
+            return web::redirect("/users");
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            let admin = user_session.current_username();
+            tracing::warn!(
+                "User `{}` performed `{}` on `{username}`",
+                admin.as_deref().unwrap_or("<api token>"),
+                action.audit_action()
+            );
+            web::audit(
+                audit_log,
+                admin.as_deref(),
+                action.audit_action(),
+                &[("username", &username.to_string())],
+            );
+
+            flash_messages
+                .info(i18n::Message::new(action.done_message()).with("username", &username));
+        }
+        Err(core::ManageUsersError::NoSuchUser(_)) => {
+            flash_messages.error(i18n::Message::new("users.not_found").with("username", &username))
+        }
+        Err(core::ManageUsersError::LastAdmin(_)) => {
+            flash_messages.error(i18n::Message::new("users.last_admin").with("username", &username))
+        }
+        Err(err) => {
+            tracing::error!("Failed to update `{username}`: {err}");
+
+            flash_messages.error("users.update_failed");
+        }
+    }
+
+    web::redirect("/users")
+}
+
+pub async fn remove_post(
+    request: aweb::Form<ManageForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    manage(
+        Action::Remove,
+        request.into_inner(),
+        flash_messages,
+        user_session,
+        &users,
+        &audit_log,
+    )
+    .await
+}
+
+pub async fn deactivate_post(
+    request: aweb::Form<ManageForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    users: aweb::Data<session::UsersStore>,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    manage(
+        Action::Deactivate,
+        request.into_inner(),
+        flash_messages,
+        user_session,
+        &users,
+        &audit_log,
+    )
+    .await
+}
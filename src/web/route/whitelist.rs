@@ -0,0 +1,109 @@
+use crate::{
+    core::{self, server},
+    web::{self, i18n, session, template},
+};
+use actix_web::web as aweb;
+
+#[derive(serde::Serialize)]
+struct WhitelistContent {
+    players: Vec<String>,
+}
+
+pub async fn get(
+    config: aweb::Data<core::AppConfig>,
+    templates: aweb::Data<handlebars::Handlebars<'_>>,
+    client: aweb::Data<server::Client>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    let players = match client.whitelist_list().await {
+        Ok(players) => players,
+        Err(err) => {
+            tracing::error!("Failed to get the whitelist: {err}");
+
+            flash_messages.error("whitelist.fetch_failed");
+
+            vec![]
+        }
+    };
+
+    let content = template::Content::new(&config, flash_messages, WhitelistContent { players })
+        .with_menu(template::ActiveMenu::Whitelist, &user_session);
+
+    template::render_response(&templates, "whitelist", &content)
+}
+
+#[derive(serde::Deserialize)]
+pub struct WhitelistForm {
+    player: String,
+}
+
+pub async fn add(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<WhitelistForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let player = request.player.trim();
+    match client.whitelist_add(player).await {
+        Ok(server::Update::Updated(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "whitelist.add",
+                &[("player", player)],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to add a player to the whitelist: {err}");
+
+            flash_messages.error("whitelist.add_failed");
+        }
+    }
+
+    web::redirect("/whitelist")
+}
+
+pub async fn remove(
+    client: aweb::Data<server::Client>,
+    request: aweb::Form<WhitelistForm>,
+    flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
+    audit_log: aweb::Data<core::AuditLog>,
+) -> impl actix_web::Responder {
+    let player = request.player.trim();
+    match client.whitelist_remove(player).await {
+        Ok(server::Update::Updated(message)) => {
+            web::audit(
+                &audit_log,
+                user_session.current_username().as_deref(),
+                "whitelist.remove",
+                &[("player", player)],
+            );
+
+            flash_messages.info(i18n::Message::text(message))
+        }
+        Ok(server::Update::Unchanged(message)) => {
+            flash_messages.warning(i18n::Message::text(message))
+        }
+        Err(err @ server::Error::InvalidArgument(_)) => {
+            flash_messages.error(i18n::Message::text(err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!("Failed to remove a player from the whitelist: {err}");
+
+            flash_messages.error("whitelist.remove_failed");
+        }
+    }
+
+    web::redirect("/whitelist")
+}
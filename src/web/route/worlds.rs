@@ -1,36 +1,234 @@
 use crate::{
     core::{self, server},
-    web::{self, session, template},
+    web::{self, i18n, session, template},
 };
 use actix_web::web as aweb;
+use std::{cmp, io, time};
 
-type WorldsList = Vec<World>;
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+const MODIFIED: &[::time::format_description::BorrowedFormatItem<'_>] =
+    ::time::macros::format_description!("[year]-[month]-[day] [hour]:[minute] UTC");
 
 #[derive(serde::Serialize)]
 struct World {
     id: String,
     is_current: bool,
     name: String,
+    size: Option<String>,
+    modified: Option<String>,
+    is_locked: bool,
+    is_valid: bool,
 }
 
-impl From<core::Worlds> for WorldsList {
-    fn from(worlds: core::Worlds) -> Self {
-        let mut list = vec![];
+impl World {
+    fn new(world: &core::World, usage: Option<core::DiskUsage>) -> Self {
+        Self {
+            name: id_to_name(world.id()),
+            id: world.id().to_string(),
+            is_current: world.is_active,
+            is_locked: world.is_locked,
+            is_valid: world.is_valid,
+            size: usage.as_ref().map(|usage| format_size(usage.size_bytes)),
+            modified: usage.and_then(|usage| usage.modified).map(format_modified),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WorldSort {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl WorldSort {
+    const ALL: [Self; 3] = [Self::Name, Self::Size, Self::Modified];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Modified => "modified",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Name => "worlds.sort_name",
+            Self::Size => "worlds.sort_size",
+            Self::Modified => "worlds.sort_modified",
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SortOption {
+    value: &'static str,
+    label: &'static str,
+    selected: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorldsQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    sort: WorldSort,
+    page: Option<usize>,
+    size: Option<usize>,
+}
+
+impl WorldsQuery {
+    // Only the query, so the links stay on the current page under the base path.
+    fn page_url(&self, page: usize) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+
+        if !self.q.is_empty() {
+            query.append_pair("q", &self.q);
+        }
+        if let Some(size) = self.size {
+            query.append_pair("size", &size.to_string());
+        }
+
+        query
+            .append_pair("sort", self.sort.as_str())
+            .append_pair("page", &page.to_string());
+
+        format!("?{}", query.finish())
+    }
+}
+
+#[derive(serde::Serialize)]
+struct WorldsContent {
+    current: Option<World>,
+    worlds: Vec<World>,
+    query: String,
+    sorts: Vec<SortOption>,
+    total: usize,
+    previous_page: Option<String>,
+    next_page: Option<String>,
+}
+
+impl WorldsContent {
+    // Collecting the disk usage walks the whole world directory, so unless the
+    // list is sorted by it, it's only collected for the worlds on the page.
+    fn new(worlds: &core::Worlds, query: &WorldsQuery) -> Self {
+        let page_size = query
+            .size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let page = query.page.unwrap_or(1).max(1);
+        let offset = (page - 1).saturating_mul(page_size);
+
+        let mut matching: Vec<_> = worlds
+            .search(&query.q)
+            .filter(|world| !world.is_active)
+            .collect();
+        matching.sort_by_cached_key(|world| id_to_name(world.id()));
+        let total = matching.len();
 
-        for world in worlds.list() {
-            list.push(World {
-                name: id_to_name(&world.id()),
-                id: world.id(),
-                is_current: world.is_active,
-            })
+        let page_worlds = if query.sort == WorldSort::Name {
+            matching
+                .into_iter()
+                .skip(offset)
+                .take(page_size)
+                .map(|world| World::new(world, disk_usage(worlds, world)))
+                .collect()
+        } else {
+            let mut with_usage: Vec<_> = matching
+                .into_iter()
+                .map(|world| (world, disk_usage(worlds, world)))
+                .collect();
+
+            if query.sort == WorldSort::Size {
+                with_usage.sort_by_key(|(_, usage)| {
+                    cmp::Reverse(usage.as_ref().map(|usage| usage.size_bytes))
+                });
+            } else {
+                with_usage.sort_by_key(|(_, usage)| {
+                    cmp::Reverse(usage.as_ref().and_then(|usage| usage.modified))
+                });
+            }
+
+            with_usage
+                .into_iter()
+                .skip(offset)
+                .take(page_size)
+                .map(|(world, usage)| World::new(world, usage))
+                .collect()
+        };
+
+        Self {
+            current: worlds
+                .list()
+                .iter()
+                .find(|world| world.is_active)
+                .map(|world| World::new(world, None)),
+            worlds: page_worlds,
+            query: query.q.clone(),
+            sorts: WorldSort::ALL
+                .into_iter()
+                .map(|sort| SortOption {
+                    value: sort.as_str(),
+                    label: sort.label(),
+                    selected: sort == query.sort,
+                })
+                .collect(),
+            total,
+            previous_page: (page > 1).then(|| query.page_url(page - 1)),
+            next_page: (offset.saturating_add(page_size) < total).then(|| query.page_url(page + 1)),
         }
+    }
+}
 
-        list.sort_by(|a, b| a.name.cmp(&b.name));
+// The active world is constantly written to by the server and its usage is not
+// worth walking the directory on every page load.
+fn disk_usage(worlds: &core::Worlds, world: &core::World) -> Option<core::DiskUsage> {
+    if world.is_active {
+        None
+    } else {
+        match worlds.disk_usage(world) {
+            Ok(usage) => Some(usage),
+            Err(err) => {
+                tracing::warn!("{err}");
 
-        list
+                None
+            }
+        }
     }
 }
 
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut unit = 0;
+    let mut divisor = 1u64;
+
+    while bytes / divisor >= 1024 && unit < UNITS.len() - 1 {
+        divisor *= 1024;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[unit])
+    } else {
+        let tenths = u128::from(bytes) * 10 / u128::from(divisor);
+
+        format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+    }
+}
+
+// Formatting into a string only fails for descriptions that need an offset or
+// a date the value doesn't have, which this one doesn't.
+pub(super) fn format_modified(modified: time::SystemTime) -> String {
+    ::time::OffsetDateTime::from(modified)
+        .format(MODIFIED)
+        .unwrap_or_default()
+}
+
 fn id_to_name(id: &str) -> String {
     id.split('_')
         .map(title_case_word)
@@ -55,16 +253,30 @@ fn title_case_word(word: &str) -> String {
 pub async fn get(
     config: aweb::Data<core::AppConfig>,
     templates: aweb::Data<handlebars::Handlebars<'_>>,
+    query: aweb::Query<WorldsQuery>,
     flash_messages: session::FlashMessages,
+    user_session: session::UserSession,
 ) -> impl actix_web::Responder {
-    match core::Worlds::new(&config.worlds_path, &config.server_properties_path) {
-        Ok(worlds) => {
-            let worlds: WorldsList = worlds.into();
-            let content = template::Content::new(flash_messages, worlds)
-                .with_menu(template::ActiveMenu::Worlds);
+    match core::Worlds::new(
+        &config.worlds_path,
+        &config.server_properties_path,
+        config.allow_empty_worlds,
+    ) {
+        // Walking the world directories for their disk usage takes a while with
+        // many or large worlds, so it doesn't hold up the worker.
+        Ok(worlds) => match aweb::block(move || WorldsContent::new(&worlds, &query)).await {
+            Ok(content) => {
+                let content = template::Content::new(&config, flash_messages, content)
+                    .with_menu(template::ActiveMenu::Worlds, &user_session);
 
-            template::render_response(&templates, "worlds", &content)
-        }
+                template::render_response(&templates, "worlds", &content)
+            }
+            Err(err) => {
+                tracing::error!("Failed to collect the worlds: {err}");
+
+                Err(web::internal_server_error().into())
+            }
+        },
         Err(err) => {
             tracing::error!("Failed to load worlds: {err}");
 
@@ -83,38 +295,78 @@ pub async fn post(
     client: aweb::Data<server::Client>,
     request: aweb::Form<WorldSwitchForm>,
     flash_messages: session::FlashMessages,
+    audit_log: aweb::Data<core::AuditLog>,
+    user_session: session::UserSession,
 ) -> impl actix_web::Responder {
-    match core::Worlds::new(&config.worlds_path, &config.server_properties_path) {
+    match core::Worlds::new(
+        &config.worlds_path,
+        &config.server_properties_path,
+        config.allow_empty_worlds,
+    ) {
         Ok(worlds) => {
-            if let Err(err) = client.save_all().await {
-                tracing::error!("{err}");
+            if let Err(err) = worlds.check_switch(&request.world_id) {
+                flash_messages.error(error_message(err));
+
+                return Ok(web::redirect("/worlds"));
+            }
 
-                flash_messages.error("Failed to save the current world.");
+            let autosave_off = match save_world(&client, config.flush_before_switch).await {
+                Ok(autosave_off) => autosave_off,
+                Err(err) => {
+                    tracing::error!("{err}");
 
-                Ok(web::redirect("/worlds"))
-            } else if let Err(err) = client.stop().await {
+                    flash_messages.error("server.save_failed");
+
+                    return Ok(web::redirect("/worlds"));
+                }
+            };
+
+            if let Err(err) = client.stop().await {
                 tracing::error!("{err}");
 
-                flash_messages.error("Failed to stop the Minecraft server.");
+                if autosave_off {
+                    restore_autosave(&client).await;
+                }
+
+                flash_messages.error("server.stop_failed");
 
                 Ok(web::redirect("/worlds"))
             } else {
-                flash_messages.warning("The Minecraft server was restarted.");
+                flash_messages.warning("worlds.server_restarted");
+
+                let Some(worlds) = backup_world(&config, worlds, &flash_messages).await else {
+                    return Ok(web::redirect("/worlds"));
+                };
 
                 match worlds.switch(request.world_id.to_string()) {
                     Ok(world) => {
-                        flash_messages.info(format!(
-                            r#""{}" is now the active world."#,
-                            id_to_name(&world.id())
-                        ));
+                        web::audit(
+                            &audit_log,
+                            user_session.current_username().as_deref(),
+                            "world.switch",
+                            &[("world", world.id())],
+                        );
+
+                        flash_messages.info(
+                            i18n::Message::new("worlds.switched")
+                                .with("world", id_to_name(world.id())),
+                        );
+
+                        if config.auto_start
+                            && let Some(command) = &config.start_command
+                        {
+                            super::server::start(command, &flash_messages);
+                        }
 
                         Ok(web::redirect("/worlds"))
                     }
-                    Err(core::WorldError::NoSuchWorld(id)) => {
-                        flash_messages.error(format!(
-                            r#"World with id "{}" is not available."#,
-                            id.display()
-                        ));
+                    Err(
+                        err @ (core::WorldError::NoSuchWorld(_)
+                        | core::WorldError::WorldLocked(_)
+                        | core::WorldError::NotAWorld(_)
+                        | core::WorldError::AlreadyActive(_)),
+                    ) => {
+                        flash_messages.error(error_message(err));
 
                         Ok(web::redirect("/worlds"))
                     }
@@ -133,3 +385,272 @@ pub async fn post(
         }
     }
 }
+
+// Only the errors caused by the request are shown to the user, the rest are
+// only logged.
+fn error_message(err: core::WorldError) -> i18n::Message {
+    match err {
+        core::WorldError::NoSuchWorld(id) => {
+            i18n::Message::new("worlds.not_available").with("world", id.display())
+        }
+        core::WorldError::AlreadyActive(id) => {
+            i18n::Message::new("worlds.already_active").with("world", id)
+        }
+        core::WorldError::InvalidName(id) => {
+            i18n::Message::new("worlds.invalid_name").with("world", id)
+        }
+        core::WorldError::AlreadyExists(id) => {
+            i18n::Message::new("worlds.already_exists").with("world", id)
+        }
+        core::WorldError::CannotDeleteActive(id) => {
+            i18n::Message::new("worlds.cannot_delete_active").with("world", id)
+        }
+        core::WorldError::WorldLocked(id) => i18n::Message::new("worlds.locked").with("world", id),
+        core::WorldError::NotAWorld(id) => {
+            i18n::Message::new("worlds.not_a_world").with("world", id)
+        }
+        err => i18n::Message::text(err.to_string()),
+    }
+}
+
+// The server keeps writing the world until its process exits, so the copy waits
+// for the session lock to clear. Copying a whole world takes a while, so it runs
+// on the blocking thread pool. The worlds are handed back if the switch can go
+// ahead.
+async fn backup_world(
+    config: &core::AppConfig,
+    worlds: core::Worlds,
+    flash_messages: &session::FlashMessages,
+) -> Option<core::Worlds> {
+    let Some(backups) = &config.backups else {
+        return Some(worlds);
+    };
+
+    let (worlds, backup) = if super::server::wait_for_shutdown(config).await {
+        let backups_path = backups.path.clone();
+
+        match aweb::block(move || {
+            let backup = worlds.backup(&backups_path);
+
+            (worlds, backup)
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!("Failed to back up the current world: {err}");
+
+                flash_messages.error("worlds.backup_failed_not_switched");
+
+                return None;
+            }
+        }
+    } else {
+        let err = io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the server is still running with the world loaded",
+        );
+
+        (worlds, Err(core::WorldError::Backup(err)))
+    };
+
+    match backup {
+        Ok(path) => {
+            flash_messages
+                .info(i18n::Message::new("worlds.backed_up").with("path", path.display()));
+
+            Some(worlds)
+        }
+        Err(err) if backups.best_effort => {
+            tracing::warn!("{err}");
+
+            flash_messages.warning("worlds.backup_failed");
+
+            Some(worlds)
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+
+            flash_messages.error("worlds.backup_failed_not_switched");
+
+            None
+        }
+    }
+}
+
+// Turning automatic saving off first makes sure nothing is written to the world
+// between the flush and the shutdown. It's turned back on when the server starts,
+// or right away if the server keeps running. Returns whether it was turned off
+// here, so it is never turned on when it was off already.
+async fn save_world(client: &server::Client, flush: bool) -> Result<bool, server::Error> {
+    if !flush {
+        return client.save_all().await.map(|()| false);
+    }
+
+    let autosave_off = matches!(client.save_off().await?, server::Update::Updated(_));
+
+    if let Err(err) = client.save_all_flush().await {
+        if autosave_off {
+            restore_autosave(client).await;
+        }
+
+        return Err(err);
+    }
+
+    Ok(autosave_off)
+}
+
+async fn restore_autosave(client: &server::Client) {
+    if let Err(err) = client.save_on().await {
+        tracing::error!("Failed to turn automatic saving back on: {err}");
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorldCreateForm {
+    world_id: String,
+}
+
+pub async fn create_post(
+    config: aweb::Data<core::AppConfig>,
+    request: aweb::Form<WorldCreateForm>,
+    flash_messages: session::FlashMessages,
+    audit_log: aweb::Data<core::AuditLog>,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    match core::Worlds::new(
+        &config.worlds_path,
+        &config.server_properties_path,
+        config.allow_empty_worlds,
+    ) {
+        Ok(worlds) => match worlds.create(request.world_id.trim().to_string()) {
+            Ok(world) => {
+                web::audit(
+                    &audit_log,
+                    user_session.current_username().as_deref(),
+                    "world.create",
+                    &[("world", world.id())],
+                );
+
+                flash_messages.info(
+                    i18n::Message::new("worlds.created").with("world", id_to_name(world.id())),
+                );
+
+                if !world.is_valid {
+                    flash_messages.warning("worlds.empty_world_hint");
+                }
+
+                Ok(web::redirect("/worlds"))
+            }
+            Err(err @ (core::WorldError::InvalidName(_) | core::WorldError::AlreadyExists(_))) => {
+                flash_messages.error(error_message(err));
+
+                Ok(web::redirect("/worlds"))
+            }
+            Err(err) => {
+                tracing::error!("Failed to create the world: {err}");
+
+                Err(web::internal_server_error())
+            }
+        },
+        Err(err) => {
+            tracing::error!("Failed to load worlds: {err}");
+
+            Err(web::internal_server_error())
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct WorldDeleteForm {
+    world_id: String,
+    #[serde(default)]
+    confirm: Option<String>,
+}
+
+pub async fn delete_post(
+    config: aweb::Data<core::AppConfig>,
+    request: aweb::Form<WorldDeleteForm>,
+    flash_messages: session::FlashMessages,
+    audit_log: aweb::Data<core::AuditLog>,
+    user_session: session::UserSession,
+) -> impl actix_web::Responder {
+    if request.confirm.is_none() {
+        flash_messages.error("worlds.confirm_delete");
+
+        Ok(web::redirect("/worlds"))
+    } else {
+        match core::Worlds::new(
+            &config.worlds_path,
+            &config.server_properties_path,
+            config.allow_empty_worlds,
+        ) {
+            Ok(worlds) => match worlds.delete(request.world_id.to_string()) {
+                Ok(()) => {
+                    web::audit(
+                        &audit_log,
+                        user_session.current_username().as_deref(),
+                        "world.delete",
+                        &[("world", &request.world_id)],
+                    );
+
+                    flash_messages.info(
+                        i18n::Message::new("worlds.deleted")
+                            .with("world", id_to_name(&request.world_id)),
+                    );
+
+                    Ok(web::redirect("/worlds"))
+                }
+                Err(
+                    err @ (core::WorldError::NoSuchWorld(_)
+                    | core::WorldError::InvalidName(_)
+                    | core::WorldError::CannotDeleteActive(_)),
+                ) => {
+                    flash_messages.error(error_message(err));
+
+                    Ok(web::redirect("/worlds"))
+                }
+                Err(err) => {
+                    tracing::error!("Failed to delete the world: {err}");
+
+                    Err(web::internal_server_error())
+                }
+            },
+            Err(err) => {
+                tracing::error!("Failed to load worlds: {err}");
+
+                Err(web::internal_server_error())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modification_times_are_formatted_in_utc() {
+        let modified = time::UNIX_EPOCH + time::Duration::from_secs(1_700_000_000);
+
+        assert_eq!(format_modified(modified), "2023-11-14 22:13 UTC");
+    }
+
+    #[test]
+    fn page_links_keep_the_query_and_stay_relative() {
+        let query = WorldsQuery {
+            q: "a b".to_string(),
+            sort: WorldSort::default(),
+            page: Some(1),
+            size: Some(10),
+        };
+
+        assert_eq!(
+            query.page_url(2),
+            format!(
+                "?q=a+b&size=10&sort={}&page=2",
+                WorldSort::default().as_str()
+            )
+        );
+    }
+}
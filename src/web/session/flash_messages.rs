@@ -1,5 +1,6 @@
 use std::future;
 
+use crate::web::i18n;
 use actix_session::SessionExt;
 use actix_web::dev;
 
@@ -17,32 +18,38 @@ pub struct FlashMessage {
     level: Level,
 }
 
-pub struct FlashMessages(actix_session::Session);
+// Messages are translated as they are added, the locale of the request adding
+// them is the same one that renders them after the redirect.
+pub struct FlashMessages(actix_session::Session, i18n::Translator);
 
 impl FlashMessages {
     const FLASH_MESSAGES_KEY: &'static str = "flash_messages";
 
-    pub fn info<M: AsRef<str>>(&self, message: M) {
+    pub fn info<M: Into<i18n::Message>>(&self, message: M) {
         self.add(FlashMessage {
-            message: message.as_ref().to_string(),
+            message: self.1.translate(message),
             level: Level::Info,
         });
     }
 
-    pub fn error<M: AsRef<str>>(&self, message: M) {
+    pub fn error<M: Into<i18n::Message>>(&self, message: M) {
         self.add(FlashMessage {
-            message: message.as_ref().to_string(),
+            message: self.1.translate(message),
             level: Level::Error,
         });
     }
 
-    pub fn warning<M: AsRef<str>>(&self, message: M) {
+    pub fn warning<M: Into<i18n::Message>>(&self, message: M) {
         self.add(FlashMessage {
-            message: message.as_ref().to_string(),
+            message: self.1.translate(message),
             level: Level::Warning,
         });
     }
 
+    pub fn locale(&self) -> &str {
+        self.1.locale()
+    }
+
     pub fn take(&self) -> Vec<FlashMessage> {
         match self.0.remove_as(Self::FLASH_MESSAGES_KEY) {
             Some(Err(err)) => {
@@ -80,10 +87,13 @@ impl FlashMessages {
 }
 
 impl actix_web::FromRequest for FlashMessages {
-    type Error = <actix_session::Session as actix_web::FromRequest>::Error;
+    type Error = actix_web::Error;
     type Future = future::Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
-        future::ready(Ok(FlashMessages(req.get_session())))
+        future::ready(
+            i18n::Translator::for_request(req)
+                .map(|translator| FlashMessages(req.get_session(), translator)),
+        )
     }
 }
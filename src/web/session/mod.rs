@@ -2,8 +2,10 @@ mod file_store;
 mod flash_messages;
 mod store;
 mod user_session;
+mod users_store;
 
 pub use file_store::FileStore;
 pub use flash_messages::{FlashMessage, FlashMessages};
-pub use store::SessionStore;
+pub use store::{REMEMBER_ME_KEY, SessionStore};
 pub use user_session::UserSession;
+pub use users_store::UsersStore;
@@ -1,7 +1,7 @@
 use actix_session::storage;
 use anyhow::Context;
 use rand::distr::{self, SampleString};
-use std::{collections, time};
+use std::{cmp, collections, time};
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync;
 
@@ -9,10 +9,30 @@ type SessionState = collections::HashMap<String, SessionEntry>;
 
 type SessionData = collections::HashMap<String, String>;
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+// Sessions with this key in their state are kept for the "remember me"
+// duration instead of the regular session TTL.
+pub const REMEMBER_ME_KEY: &str = "remember_me";
+
+// Every change rewrites the whole sessions file, so the time a session was last
+// seen is only written out once it moves by more than this. A session may end
+// up to that much later after a restart than it would have otherwise.
+const LAST_SEEN_PERSIST_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct UserSessionInfo {
+    pub id: String,
+    pub last_seen: time::SystemTime,
+}
+
+// The timer only restarts when the lifetime of the session is extended, while
+// the session is seen on every request that uses it. Sessions stored before
+// this was tracked start from the time they are loaded.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct SessionEntry {
     ttl: time::Duration,
     timer: time::SystemTime,
+    #[serde(default = "time::SystemTime::now")]
+    last_seen: time::SystemTime,
     state: SessionData,
 }
 
@@ -20,18 +40,45 @@ impl SessionEntry {
     fn new(ttl: time::Duration, state: collections::HashMap<String, String>) -> Self {
         let timer = time::SystemTime::now();
 
-        Self { state, ttl, timer }
+        Self {
+            state,
+            ttl,
+            timer,
+            last_seen: timer,
+        }
     }
 
-    fn is_fresh(&self) -> bool {
-        self.timer
+    fn is_fresh(&self, idle_timeout: Option<time::Duration>) -> bool {
+        let is_alive = self
+            .timer
             .elapsed()
             .map(|dur| dur < self.ttl)
-            .unwrap_or_default()
+            .unwrap_or_default();
+        let is_active = idle_timeout.is_none_or(|idle_timeout| {
+            self.last_seen
+                .elapsed()
+                .map(|dur| dur < idle_timeout)
+                .unwrap_or_default()
+        });
+
+        is_alive && is_active
+    }
+
+    // The state values are stored JSON-encoded by the session middleware.
+    fn get(&self, key: &str) -> Option<String> {
+        self.state
+            .get(key)
+            .and_then(|value| serde_json::from_str(value).ok())
+    }
+
+    fn belongs_to(&self, username: &str, idle_timeout: Option<time::Duration>) -> bool {
+        self.is_fresh(idle_timeout)
+            && self.get(super::UserSession::USERNAME_KEY).as_deref() == Some(username)
     }
 
     fn update_ttl(&mut self, ttl: time::Duration) {
         self.timer = time::SystemTime::now();
+        self.last_seen = self.timer;
         self.ttl = ttl;
     }
 }
@@ -62,10 +109,44 @@ enum Message {
         result: oneshot::Sender<()>,
         key: String,
     },
+    ListUserSessions {
+        result: oneshot::Sender<Vec<UserSessionInfo>>,
+        username: String,
+    },
+    RevokeUserSession {
+        result: oneshot::Sender<bool>,
+        username: String,
+        session_id: String,
+    },
+}
+
+async fn persist(file_store: Option<&super::FileStore<SessionState>>, store: &SessionState) {
+    if let Some(file_store) = file_store {
+        file_store.save(store.clone()).await;
+    }
+}
+
+fn session_ttl(
+    state: &SessionData,
+    ttl: time::Duration,
+    remember_ttl: time::Duration,
+) -> time::Duration {
+    if state.contains_key(REMEMBER_ME_KEY) {
+        ttl.max(remember_ttl)
+    } else {
+        ttl
+    }
+}
+
+struct Limits {
+    remember_ttl: time::Duration,
+    idle_timeout: Option<time::Duration>,
 }
 
 async fn session_handler(
-    file_store: super::FileStore<SessionState>,
+    file_store: Option<super::FileStore<SessionState>>,
+    sweep_interval: time::Duration,
+    limits: Limits,
     mut receiver: mpsc::UnboundedReceiver<Message>,
     cancel: sync::CancellationToken,
     complete: sync::CancellationToken,
@@ -73,67 +154,174 @@ async fn session_handler(
     let _cancel_guard = cancel.drop_guard();
     let _complete_guard = complete.drop_guard();
 
-    let mut store = file_store.load().await;
-
-    while let Some(message) = receiver.recv().await {
-        match message {
-            Message::Load { result, key } => {
-                if let Err(e) = result.send(match store.get(&key) {
-                    Some(state) => {
-                        if state.is_fresh() {
-                            Some(state.state.clone())
-                        } else {
-                            None
-                        }
-                    }
-                    None => None,
-                }) {
-                    tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+    let mut store = match &file_store {
+        Some(file_store) => file_store.load().await,
+        None => SessionState::default(),
+    };
+
+    let mut sweep = tokio::time::interval(sweep_interval);
+
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(message) => {
+                    handle_message(&mut store, file_store.as_ref(), &limits, message).await
                 }
+                None => break,
+            },
+            _ = sweep.tick() => {
+                sweep_expired(&mut store, file_store.as_ref(), limits.idle_timeout).await
             }
-            Message::Save {
-                result,
-                key,
-                state,
-                ttl,
-            } => {
-                store.insert(key, SessionEntry::new(ttl, state));
+        }
+    }
 
-                if let Err(e) = result.send(()) {
-                    tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+    if let Some(file_store) = file_store {
+        file_store.save(store).await;
+        file_store.shutdown().await;
+    }
+}
+
+async fn handle_message(
+    store: &mut SessionState,
+    file_store: Option<&super::FileStore<SessionState>>,
+    limits: &Limits,
+    message: Message,
+) {
+    let remember_ttl = limits.remember_ttl;
+
+    match message {
+        // A session that has been idle for too long is gone for good, even if
+        // its lifetime has been extended past that.
+        Message::Load { result, key } => {
+            let state = match store.get_mut(&key) {
+                Some(entry) if entry.is_fresh(limits.idle_timeout) => {
+                    let now = time::SystemTime::now();
+                    let is_stale = now
+                        .duration_since(entry.last_seen)
+                        .is_ok_and(|dur| dur >= LAST_SEEN_PERSIST_INTERVAL);
+                    entry.last_seen = now;
+                    let state = entry.state.clone();
+
+                    if is_stale {
+                        persist(file_store, store).await;
+                    }
+
+                    Some(state)
                 }
-            }
-            Message::Update {
-                result,
-                key,
-                state,
-                ttl,
-            } => {
-                store.insert(key, SessionEntry::new(ttl, state));
+                Some(_) => {
+                    store.remove(&key);
+                    persist(file_store, store).await;
 
-                if let Err(e) = result.send(()) {
-                    tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+                    None
                 }
+                None => None,
+            };
+
+            if let Err(e) = result.send(state) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+            }
+        }
+        Message::Save {
+            result,
+            key,
+            state,
+            ttl,
+        } => {
+            let ttl = session_ttl(&state, ttl, remember_ttl);
+            store.insert(key, SessionEntry::new(ttl, state));
+            persist(file_store, store).await;
+
+            if let Err(e) = result.send(()) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+            }
+        }
+        Message::Update {
+            result,
+            key,
+            state,
+            ttl,
+        } => {
+            let ttl = session_ttl(&state, ttl, remember_ttl);
+            store.insert(key, SessionEntry::new(ttl, state));
+            persist(file_store, store).await;
+
+            if let Err(e) = result.send(()) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
             }
-            Message::UpdateTtl { result, key, ttl } => {
-                store.entry(key).and_modify(|v| v.update_ttl(ttl));
+        }
+        Message::UpdateTtl { result, key, ttl } => {
+            store
+                .entry(key)
+                .and_modify(|v| v.update_ttl(session_ttl(&v.state, ttl, remember_ttl)));
 
-                if let Err(e) = result.send(()) {
-                    tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
-                }
+            if let Err(e) = result.send(()) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
             }
-            Message::Delete { result, key } => {
-                store.remove(&key);
+        }
+        Message::Delete { result, key } => {
+            store.remove(&key);
+            persist(file_store, store).await;
 
-                if let Err(e) = result.send(()) {
-                    tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
-                }
+            if let Err(e) = result.send(()) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+            }
+        }
+        Message::ListUserSessions { result, username } => {
+            let mut sessions = store
+                .values()
+                .filter(|entry| entry.belongs_to(&username, limits.idle_timeout))
+                .filter_map(|entry| {
+                    Some(UserSessionInfo {
+                        id: entry.get(super::UserSession::SESSION_ID_KEY)?,
+                        last_seen: entry.last_seen,
+                    })
+                })
+                .collect::<Vec<_>>();
+            sessions.sort_by_key(|session| cmp::Reverse(session.last_seen));
+
+            if let Err(e) = result.send(sessions) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
+            }
+        }
+        // Both the username and the session id have to match, so a user can
+        // only ever revoke their own sessions.
+        Message::RevokeUserSession {
+            result,
+            username,
+            session_id,
+        } => {
+            let size = store.len();
+            store.retain(|_, entry| {
+                !(entry.belongs_to(&username, limits.idle_timeout)
+                    && entry.get(super::UserSession::SESSION_ID_KEY).as_deref()
+                        == Some(session_id.as_str()))
+            });
+
+            let revoked = store.len() != size;
+            if revoked {
+                persist(file_store, store).await;
+            }
+
+            if let Err(e) = result.send(revoked) {
+                tracing::warn!(error=?e, "Tried to send the response to the closed channel.");
             }
         }
     }
+}
 
-    file_store.save(store).await;
-    file_store.shutdown().await;
+async fn sweep_expired(
+    store: &mut SessionState,
+    file_store: Option<&super::FileStore<SessionState>>,
+    idle_timeout: Option<time::Duration>,
+) {
+    let size = store.len();
+    store.retain(|_, entry| entry.is_fresh(idle_timeout));
+
+    if store.len() != size {
+        tracing::info!("Evicted {} expired sessions", size - store.len());
+
+        persist(file_store, store).await;
+    }
 }
 
 #[derive(Clone)]
@@ -143,11 +331,27 @@ pub struct SessionStore {
 }
 
 impl SessionStore {
-    pub fn new(fs: super::FileStore<SessionState>, cancel: sync::CancellationToken) -> Self {
+    pub fn new(
+        fs: Option<super::FileStore<SessionState>>,
+        sweep_interval: time::Duration,
+        remember_ttl: time::Duration,
+        idle_timeout: Option<time::Duration>,
+        cancel: sync::CancellationToken,
+    ) -> Self {
         let complete = sync::CancellationToken::new();
         let (sender, receiver) = mpsc::unbounded_channel();
 
-        tokio::spawn(session_handler(fs, receiver, cancel, complete.clone()));
+        tokio::spawn(session_handler(
+            fs,
+            sweep_interval,
+            Limits {
+                remember_ttl,
+                idle_timeout,
+            },
+            receiver,
+            cancel,
+            complete.clone(),
+        ));
 
         Self { sender, complete }
     }
@@ -155,6 +359,40 @@ impl SessionStore {
     pub fn shutdown(self) -> sync::WaitForCancellationFutureOwned {
         self.complete.cancelled_owned()
     }
+
+    pub async fn list_user_sessions(
+        &self,
+        username: String,
+    ) -> Result<Vec<UserSessionInfo>, anyhow::Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.sender
+            .send(Message::ListUserSessions {
+                result: sender,
+                username,
+            })
+            .context("Failed to list the user sessions")?;
+
+        receiver.await.context("Failed to list the user sessions")
+    }
+
+    pub async fn revoke_user_session(
+        &self,
+        username: String,
+        session_id: String,
+    ) -> Result<bool, anyhow::Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.sender
+            .send(Message::RevokeUserSession {
+                result: sender,
+                username,
+                session_id,
+            })
+            .context("Failed to revoke the user session")?;
+
+        receiver.await.context("Failed to revoke the user session")
+    }
 }
 
 impl storage::SessionStore for SessionStore {
@@ -264,3 +502,133 @@ impl storage::SessionStore for SessionStore {
         receiver.await.context("Failed to delete the session")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: time::Duration = time::Duration::from_secs(60);
+
+    // Moves the timer of the entry back, as if it was created that long ago.
+    fn created_ago(ttl: time::Duration, ago: time::Duration) -> SessionEntry {
+        let mut entry = SessionEntry::new(ttl, SessionData::default());
+        entry.timer -= ago;
+        entry.last_seen -= ago;
+
+        entry
+    }
+
+    fn idle_for(ago: time::Duration) -> SessionEntry {
+        let mut entry = SessionEntry::new(60 * MINUTE, SessionData::default());
+        entry.last_seen -= ago;
+
+        entry
+    }
+
+    async fn load(store: &mut SessionState, idle_timeout: time::Duration, key: &str) -> bool {
+        let limits = Limits {
+            remember_ttl: MINUTE,
+            idle_timeout: Some(idle_timeout),
+        };
+        let (result, receiver) = oneshot::channel();
+        let key = key.to_string();
+
+        handle_message(store, None, &limits, Message::Load { result, key }).await;
+
+        receiver.await.unwrap().is_some()
+    }
+
+    async fn save(store: &mut SessionState, key: &str, state: SessionData, ttl: time::Duration) {
+        let limits = Limits {
+            remember_ttl: 30 * 24 * 60 * MINUTE,
+            idle_timeout: None,
+        };
+        let (result, receiver) = oneshot::channel();
+        let key = key.to_string();
+
+        handle_message(
+            store,
+            None,
+            &limits,
+            Message::Save {
+                result,
+                key,
+                state,
+                ttl,
+            },
+        )
+        .await;
+
+        receiver.await.unwrap();
+    }
+
+    fn expires_in(entry: &SessionEntry) -> time::Duration {
+        (entry.timer + entry.ttl)
+            .duration_since(time::SystemTime::now())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn saved_sessions_expire_after_the_session_ttl() {
+        let mut store = SessionState::default();
+
+        save(&mut store, "session", SessionData::default(), 15 * MINUTE).await;
+
+        let entry = &store["session"];
+        assert_eq!(entry.ttl, 15 * MINUTE);
+        assert!(expires_in(entry) > 14 * MINUTE);
+        assert!(expires_in(entry) <= 15 * MINUTE);
+    }
+
+    #[tokio::test]
+    async fn remembered_sessions_expire_after_the_remember_me_ttl() {
+        let mut store = SessionState::default();
+        let state = SessionData::from([(REMEMBER_ME_KEY.to_string(), "true".to_string())]);
+
+        save(&mut store, "remembered", state.clone(), 15 * MINUTE).await;
+        // A session TTL longer than the remember me one is kept.
+        save(&mut store, "long", state, 60 * 24 * 60 * MINUTE).await;
+
+        let entry = &store["remembered"];
+        assert_eq!(entry.ttl, 30 * 24 * 60 * MINUTE);
+        assert!(expires_in(entry) > 30 * 24 * 60 * MINUTE - MINUTE);
+        assert_eq!(store["long"].ttl, 60 * 24 * 60 * MINUTE);
+    }
+
+    #[test]
+    fn sessions_idle_past_the_limit_are_not_fresh() {
+        let entry = idle_for(2 * MINUTE);
+
+        assert!(!entry.is_fresh(Some(MINUTE)));
+        assert!(entry.is_fresh(Some(5 * MINUTE)));
+        assert!(entry.is_fresh(None));
+    }
+
+    #[tokio::test]
+    async fn load_rejects_idle_sessions() {
+        let mut store = SessionState::from([
+            ("idle".to_string(), idle_for(2 * MINUTE)),
+            ("active".to_string(), idle_for(MINUTE / 2)),
+        ]);
+
+        assert!(!load(&mut store, MINUTE, "idle").await);
+        assert!(!store.contains_key("idle"));
+
+        assert!(load(&mut store, MINUTE, "active").await);
+        // Using the session resets the idle time.
+        assert!(store["active"].last_seen.elapsed().unwrap() < MINUTE / 2);
+    }
+
+    #[tokio::test]
+    async fn sweep_evicts_expired_sessions() {
+        let mut store = SessionState::from([
+            ("expired".to_string(), created_ago(MINUTE, 2 * MINUTE)),
+            ("fresh".to_string(), created_ago(2 * MINUTE, MINUTE)),
+        ]);
+
+        sweep_expired(&mut store, None, None).await;
+
+        assert!(!store.contains_key("expired"));
+        assert!(store.contains_key("fresh"));
+    }
+}
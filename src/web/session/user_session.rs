@@ -1,26 +1,85 @@
 use crate::{
     core,
-    web::{self, middleware},
+    web::{self, middleware, session},
 };
 use actix_session::SessionExt;
-use actix_web::{dev, web as aweb};
-use std::future;
+use actix_web::{HttpMessage, dev, web as aweb};
+use rand::distr::{self, SampleString};
+use std::{future, pin, sync::Arc, time};
 
 pub struct UserSession {
     session: actix_session::Session,
-    users: core::Users,
+    users: Arc<core::Users>,
+    record_username: bool,
 }
 
+// The session is extracted more than once per request, by the authentication
+// middleware and again by the handler, but the username must only be added to
+// the request span once.
+struct UsernameRecorder;
+
 impl UserSession {
-    const USERNAME_KEY: &'static str = "username";
+    pub(super) const USERNAME_KEY: &'static str = "username";
+    pub(super) const SESSION_ID_KEY: &'static str = "session_id";
     const REDIRECT_LOCATION_KEY: &'static str = "location";
+    const SECOND_FACTOR_USERNAME_KEY: &'static str = "second_factor_username";
+    const SECOND_FACTOR_FAILURES_KEY: &'static str = "second_factor_failures";
+    const MAX_SECOND_FACTOR_FAILURES: u32 = 5;
+    const TOTP_SETUP_SECRET_KEY: &'static str = "totp_setup_secret";
+    const AUTHENTICATED_AT_KEY: &'static str = "authenticated_at";
+    const ENROLL_TOKEN_KEY: &'static str = "enroll_token";
 
     pub fn purge(&self) {
         self.session.purge();
     }
 
+    // Changing the password or deactivating the user ends every session of the
+    // user that was authenticated before the change.
     pub fn get_current_user(&self) -> Result<Option<&core::User>, actix_session::SessionGetError> {
-        match self.session.get::<String>(Self::USERNAME_KEY)? {
+        match self.get_user(Self::USERNAME_KEY)? {
+            Some(user) => {
+                let authenticated_at = self
+                    .session
+                    .get::<u64>(Self::AUTHENTICATED_AT_KEY)?
+                    .unwrap_or_default();
+
+                if user.is_deactivated()
+                    || user.password_changed_since(
+                        time::UNIX_EPOCH + time::Duration::from_secs(authenticated_at),
+                    )
+                {
+                    self.purge();
+
+                    Ok(None)
+                } else {
+                    if self.record_username {
+                        tracing::Span::current().record("username", user.username.to_string());
+                    }
+
+                    Ok(Some(user))
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn current_username(&self) -> Option<String> {
+        self.get_current_user()
+            .ok()
+            .flatten()
+            .map(|user| user.username.to_string())
+    }
+
+    // A user that has passed the password check, but still needs to provide the second factor
+    // before being authenticated.
+    pub fn get_second_factor_user(
+        &self,
+    ) -> Result<Option<&core::User>, actix_session::SessionGetError> {
+        self.get_user(Self::SECOND_FACTOR_USERNAME_KEY)
+    }
+
+    fn get_user(&self, key: &str) -> Result<Option<&core::User>, actix_session::SessionGetError> {
+        match self.session.get::<String>(key)? {
             Some(username) => match username.try_into() {
                 Ok(username) => match self.users.find_user_by_username(&username) {
                     Some(user) => Ok(Some(user)),
@@ -42,15 +101,114 @@ impl UserSession {
 
     pub fn authenticate(&self, user: &core::User) -> Result<(), actix_session::SessionInsertError> {
         self.session.renew();
+        self.session.remove(Self::SECOND_FACTOR_USERNAME_KEY);
+        self.session.remove(Self::SECOND_FACTOR_FAILURES_KEY);
+        self.session.insert(
+            Self::SESSION_ID_KEY,
+            distr::Alphanumeric.sample_string(&mut rand::rng(), 16),
+        )?;
+        self.session.insert(
+            Self::AUTHENTICATED_AT_KEY,
+            time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        )?;
         self.session
             .insert(Self::USERNAME_KEY, user.username.to_string())
     }
 
-    pub fn get_redirect_location(&self) -> String {
+    pub fn start_second_factor(
+        &self,
+        user: &core::User,
+    ) -> Result<(), actix_session::SessionInsertError> {
+        self.session.renew();
+        self.session.remove(Self::SECOND_FACTOR_FAILURES_KEY);
+        self.session
+            .insert(Self::SECOND_FACTOR_USERNAME_KEY, user.username.to_string())
+    }
+
+    // Counts the invalid codes entered for the pending second factor. After too
+    // many of them the second factor is abandoned and the password has to be
+    // entered again. Returns whether another code can be tried.
+    pub fn second_factor_failed(&self) -> Result<bool, actix_session::SessionInsertError> {
+        let failures = self
+            .session
+            .get::<u32>(Self::SECOND_FACTOR_FAILURES_KEY)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .saturating_add(1);
+
+        if failures >= Self::MAX_SECOND_FACTOR_FAILURES {
+            self.session.remove(Self::SECOND_FACTOR_USERNAME_KEY);
+            self.session.remove(Self::SECOND_FACTOR_FAILURES_KEY);
+
+            Ok(false)
+        } else {
+            self.session
+                .insert(Self::SECOND_FACTOR_FAILURES_KEY, failures)
+                .map(|()| true)
+        }
+    }
+
+    pub fn remember(&self, remember: bool) -> Result<(), actix_session::SessionInsertError> {
+        if remember {
+            self.session.insert(session::REMEMBER_ME_KEY, true)
+        } else {
+            self.session.remove(session::REMEMBER_ME_KEY);
+
+            Ok(())
+        }
+    }
+
+    pub fn save_totp_setup_secret(
+        &self,
+        secret: &core::TotpSecret,
+    ) -> Result<(), actix_session::SessionInsertError> {
+        self.session
+            .insert(Self::TOTP_SETUP_SECRET_KEY, secret.reveal())
+    }
+
+    pub fn take_totp_setup_secret(&self) -> Option<core::TotpSecret> {
+        self.session
+            .remove_as::<String>(Self::TOTP_SETUP_SECRET_KEY)
+            .and_then(Result::ok)
+            .and_then(|secret| secret.try_into().ok())
+    }
+
+    pub fn save_enroll_token(&self, token: &str) -> Result<(), actix_session::SessionInsertError> {
+        self.session.insert(Self::ENROLL_TOKEN_KEY, token)
+    }
+
+    pub fn enroll_token(&self) -> Option<String> {
+        self.session
+            .get::<String>(Self::ENROLL_TOKEN_KEY)
+            .ok()
+            .flatten()
+    }
+
+    pub fn forget_enroll_token(&self) {
+        self.session.remove(Self::ENROLL_TOKEN_KEY);
+    }
+
+    // An opaque identifier of the session, unlike the session key it is safe to
+    // show in the UI.
+    pub fn session_id(&self) -> Option<String> {
         self.session
-            .get::<String>(Self::REDIRECT_LOCATION_KEY)
-            .unwrap_or(Some("/".to_string()))
-            .unwrap_or("/".to_string())
+            .get::<String>(Self::SESSION_ID_KEY)
+            .ok()
+            .flatten()
+    }
+
+    // The location is only used once, right after logging in, and anything
+    // but a path on this server is ignored.
+    pub fn take_redirect_location(&self) -> String {
+        self.session
+            .remove_as::<String>(Self::REDIRECT_LOCATION_KEY)
+            .and_then(Result::ok)
+            .filter(|location| web::is_local_path(location))
+            .unwrap_or_else(|| "/".to_string())
     }
 }
 
@@ -70,23 +228,136 @@ impl middleware::AuthSession for UserSession {
 
 impl actix_web::FromRequest for UserSession {
     type Error = <actix_session::Session as actix_web::FromRequest>::Error;
-    type Future = future::Ready<Result<Self, Self::Error>>;
+    type Future = pin::Pin<Box<dyn future::Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
-        let config = req
-            .app_data::<aweb::Data<core::AppConfig>>()
-            .expect("Application is misconfigured. Missing AppConfig struct.");
-
-        match core::Users::load(&config.users_file_path) {
-            Ok(users) => {
-                let session = req.get_session();
-                future::ready(Ok(UserSession { users, session }))
+        let users_store = req
+            .app_data::<aweb::Data<session::UsersStore>>()
+            .expect("Application is misconfigured. Missing UsersStore struct.")
+            .clone();
+        let session = req.get_session();
+        let record_username = req.extensions_mut().insert(UsernameRecorder).is_none();
+
+        Box::pin(async move {
+            match users_store.load().await {
+                Ok(users) => Ok(UserSession {
+                    users,
+                    session,
+                    record_username,
+                }),
+                Err(err) => {
+                    tracing::error!("Unable to load users: {err}");
+
+                    Err(web::internal_server_error().into())
+                }
             }
-            Err(err) => {
-                tracing::error!("Unable to load users: {err}");
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::{middleware::AuthSession, testing};
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn stored_locations_off_the_site_are_ignored() {
+        let env = testing::TestEnv::new("");
+        let app = test::init_service(
+            env.app()
+                .route(
+                    "/login/save",
+                    aweb::get().to(|session: UserSession| async move {
+                        session
+                            .save_redirect("https://evil.com/".to_string())
+                            .unwrap();
+
+                        ""
+                    }),
+                )
+                .route(
+                    "/login/take",
+                    aweb::get()
+                        .to(|session: UserSession| async move { session.take_redirect_location() }),
+                ),
+        )
+        .await;
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/login/save").to_request(),
+        )
+        .await;
+        let session = testing::session_cookie(&res).unwrap();
 
-                future::ready(Err(web::internal_server_error().into()))
+        let location = test::call_and_read_body(
+            &app,
+            test::TestRequest::get()
+                .uri("/login/take")
+                .cookie(session)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(location, "/");
+    }
+
+    #[actix_web::test]
+    async fn too_many_invalid_codes_abandon_the_second_factor() {
+        let env = testing::TestEnv::new("");
+        let app = test::init_service(
+            env.app()
+                .route(
+                    "/login/start",
+                    aweb::get().to(|session: UserSession| async move {
+                        let user = session
+                            .users
+                            .find_user_by_username(
+                                &testing::USERNAME.to_string().try_into().unwrap(),
+                            )
+                            .unwrap();
+                        session.start_second_factor(user).unwrap();
+
+                        ""
+                    }),
+                )
+                .route(
+                    "/login/fail",
+                    aweb::get().to(|session: UserSession| async move {
+                        let is_allowed = session.second_factor_failed().unwrap();
+                        let is_pending = session.get_second_factor_user().unwrap().is_some();
+
+                        format!("{is_allowed} {is_pending}")
+                    }),
+                ),
+        )
+        .await;
+
+        let res = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/login/start").to_request(),
+        )
+        .await;
+        let mut session = testing::session_cookie(&res).unwrap();
+        let mut replies = vec![];
+
+        for _ in 0..UserSession::MAX_SECOND_FACTOR_FAILURES {
+            let res = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri("/login/fail")
+                    .cookie(session.clone())
+                    .to_request(),
+            )
+            .await;
+            if let Some(cookie) = testing::session_cookie(&res) {
+                session = cookie;
             }
+            replies.push(test::read_body(res).await);
         }
+
+        assert_eq!(replies[0], "true true");
+        assert_eq!(replies[replies.len() - 2], "true true");
+        assert_eq!(replies[replies.len() - 1], "false false");
     }
 }
@@ -0,0 +1,105 @@
+use crate::core;
+use anyhow::Context;
+use std::{os::unix::fs::MetadataExt, path, sync::Arc, time};
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync;
+
+enum Message {
+    Load {
+        result: oneshot::Sender<Result<Arc<core::Users>, core::ManageUsersError>>,
+    },
+}
+
+// Changes are written to a new file which replaces the old one, and the command
+// line tool may change the file at any time, so a different inode, size or
+// modification time means the cached users are stale.
+#[derive(PartialEq)]
+struct Fingerprint {
+    inode: u64,
+    size: u64,
+    modified: Option<time::SystemTime>,
+}
+
+impl From<std::fs::Metadata> for Fingerprint {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        Self {
+            inode: metadata.ino(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+struct Cache {
+    users: Arc<core::Users>,
+    fingerprint: Fingerprint,
+}
+
+// Checking the metadata of the file is a lot cheaper than reading and parsing
+// it on every request, so the file is only loaded again once it has changed.
+async fn load(
+    path: &path::Path,
+    cache: &mut Option<Cache>,
+) -> Result<Arc<core::Users>, core::ManageUsersError> {
+    let fingerprint: Fingerprint = tokio::fs::metadata(path)
+        .await
+        .map_err(core::ManageUsersError::LoadStorage)?
+        .into();
+
+    match cache {
+        Some(cache) if cache.fingerprint == fingerprint => Ok(cache.users.clone()),
+        _ => {
+            let users = Arc::new(core::Users::load_lenient(path)?);
+            *cache = Some(Cache {
+                users: users.clone(),
+                fingerprint,
+            });
+
+            Ok(users)
+        }
+    }
+}
+
+async fn users_handler(
+    path: path::PathBuf,
+    mut receiver: mpsc::UnboundedReceiver<Message>,
+    cancel: sync::CancellationToken,
+) {
+    let _cancel_guard = cancel.drop_guard();
+    let mut cache = None;
+
+    while let Some(message) = receiver.recv().await {
+        match message {
+            Message::Load { result } => {
+                if result.send(load(&path, &mut cache).await).is_err() {
+                    tracing::warn!("Tried to send the response to the closed channel.");
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UsersStore {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl UsersStore {
+    pub fn new(path: path::PathBuf, cancel: sync::CancellationToken) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(users_handler(path, receiver, cancel));
+
+        Self { sender }
+    }
+
+    pub async fn load(&self) -> Result<Arc<core::Users>, anyhow::Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.sender
+            .send(Message::Load { result: sender })
+            .context("Failed to load the users")?;
+
+        Ok(receiver.await.context("Failed to load the users")??)
+    }
+}
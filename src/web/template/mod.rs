@@ -1,15 +1,71 @@
-use crate::web::{self, session};
+use crate::{
+    core,
+    web::{self, session},
+};
 use actix_web::{error, http::header};
+use std::path;
+
+// Every page the routes render and the partials they include, checked at
+// startup so a missing template fails right away instead of when somebody
+// opens the page. A new page has to be added here as well.
+const REQUIRED_TEMPLATES: [&str; 19] = [
+    "page",
+    "account_menu",
+    "index",
+    "login",
+    "totp_login",
+    "enroll",
+    "worlds",
+    "console",
+    "logs",
+    "whitelist",
+    "bans",
+    "ops",
+    "audit",
+    "users",
+    "settings",
+    "maintenance",
+    "password",
+    "totp_setup",
+    "sessions",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum LoadError {
+    #[error("{}", describe_template_error(.0))]
+    Compile(#[source] handlebars::TemplateError),
+    #[error("Missing templates: {}", .0.join(", "))]
+    Missing(Vec<&'static str>),
+}
+
+fn describe_template_error(err: &handlebars::TemplateError) -> String {
+    let name = err.name().map_or("unknown", String::as_str);
+
+    match err.pos() {
+        Some((line, column)) => format!(
+            "Failed to compile the `{name}` template at {line}:{column}: {}",
+            err.reason()
+        ),
+        None => format!("Failed to load the `{name}` template: {}", err.reason()),
+    }
+}
 
 #[derive(serde::Serialize)]
 pub struct Content<C: serde::Serialize> {
     app_version: &'static str,
+    git_hash: &'static str,
+    site_title: Option<String>,
+    logo_path: Option<String>,
+    base_path: String,
+    locale: String,
     content: C,
     flash_messages: Vec<session::FlashMessage>,
     menu: ActiveMenu,
+    role: Option<core::Role>,
 }
 
-const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("MCTRLRS_GIT_HASH");
 
 #[derive(Default)]
 pub enum ActiveMenu {
@@ -17,6 +73,16 @@ pub enum ActiveMenu {
     None,
     Home,
     Worlds,
+    Console,
+    Whitelist,
+    Bans,
+    Ops,
+    Logs,
+    Audit,
+    Users,
+    Settings,
+    Maintenance,
+    Account,
 }
 
 impl serde::Serialize for ActiveMenu {
@@ -28,6 +94,16 @@ impl serde::Serialize for ActiveMenu {
             Self::None => "",
             Self::Home => "home",
             Self::Worlds => "worlds",
+            Self::Console => "console",
+            Self::Whitelist => "whitelist",
+            Self::Bans => "bans",
+            Self::Ops => "ops",
+            Self::Logs => "logs",
+            Self::Audit => "audit",
+            Self::Users => "users",
+            Self::Settings => "settings",
+            Self::Maintenance => "maintenance",
+            Self::Account => "account",
         };
 
         String::serialize(&value.to_string(), serializer)
@@ -35,23 +111,65 @@ impl serde::Serialize for ActiveMenu {
 }
 
 impl<C: serde::Serialize> Content<C> {
-    pub fn new(flash_messages: session::FlashMessages, content: C) -> Self {
+    pub fn new(
+        config: &core::AppConfig,
+        flash_messages: session::FlashMessages,
+        content: C,
+    ) -> Self {
         Self {
             content,
             app_version: APP_VERSION,
+            git_hash: GIT_HASH,
+            site_title: config.site_title.clone(),
+            logo_path: config.logo_path.clone(),
+            base_path: config.base_path.clone(),
+            locale: flash_messages.locale().to_string(),
             flash_messages: flash_messages.take(),
             menu: Default::default(),
+            role: None,
         }
     }
 
-    pub fn with_menu(self, active_item: ActiveMenu) -> Self {
+    // The menu only links the pages the role of the user can reach.
+    pub fn with_menu(self, active_item: ActiveMenu, user_session: &session::UserSession) -> Self {
         Self {
             menu: active_item,
+            role: user_session
+                .get_current_user()
+                .ok()
+                .flatten()
+                .map(|user| user.role),
             ..self
         }
     }
 }
 
+// In the development mode the templates are read from the disk again whenever
+// they change, so it has to be enabled before they are registered.
+pub fn load(
+    templates_path: &path::Path,
+    dev_mode: bool,
+) -> Result<handlebars::Handlebars<'static>, LoadError> {
+    let mut templates = handlebars::Handlebars::new();
+    templates.set_dev_mode(dev_mode);
+    templates
+        .register_templates_directory(
+            templates_path,
+            handlebars::DirectorySourceOptions::default(),
+        )
+        .map_err(LoadError::Compile)?;
+
+    let missing: Vec<_> = REQUIRED_TEMPLATES
+        .into_iter()
+        .filter(|name| !templates.has_template(name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(LoadError::Missing(missing));
+    }
+
+    Ok(templates)
+}
+
 pub fn render_template<N: AsRef<str>, C: serde::Serialize>(
     templates: &handlebars::Handlebars,
     name: N,
@@ -78,3 +196,86 @@ pub fn render_response<N: AsRef<str>, C: serde::Serialize>(
             .body(content)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::testing, web::i18n};
+    use actix_web::web as aweb;
+    use std::{env, fs};
+
+    fn templates_dir() -> path::PathBuf {
+        path::Path::new(env!("CARGO_MANIFEST_DIR")).join("templates")
+    }
+
+    fn render_menu(role: Option<core::Role>) -> String {
+        let locales_path = path::Path::new(env!("CARGO_MANIFEST_DIR")).join("locales");
+        let catalogs = aweb::Data::new(i18n::Catalogs::load(&locales_path, "en").unwrap());
+        let mut templates = load(&templates_dir(), false).unwrap();
+        templates.register_helper("t", Box::new(i18n::helper(catalogs)));
+        let content = Content {
+            app_version: APP_VERSION,
+            git_hash: GIT_HASH,
+            site_title: None,
+            logo_path: None,
+            base_path: String::new(),
+            locale: "en".to_string(),
+            content: (),
+            flash_messages: vec![],
+            menu: ActiveMenu::Maintenance,
+            role,
+        };
+
+        templates.render("maintenance", &content).unwrap()
+    }
+
+    #[test]
+    fn the_menu_only_links_the_pages_the_role_can_reach() {
+        let viewer = render_menu(Some(core::Role::Viewer));
+        assert!(!viewer.contains(r#"href="/console""#));
+        assert!(!viewer.contains(r#"href="/audit""#));
+        assert!(!viewer.contains(r#"href="/users""#));
+        assert!(viewer.contains(r#"href="/worlds""#));
+
+        let operator = render_menu(Some(core::Role::Operator));
+        assert!(operator.contains(r#"href="/console""#));
+        assert!(!operator.contains(r#"href="/audit""#));
+        assert!(!operator.contains(r#"href="/users""#));
+
+        let admin = render_menu(Some(core::Role::Admin));
+        assert!(admin.contains(r#"href="/console""#));
+        assert!(admin.contains(r#"href="/audit""#));
+        assert!(admin.contains(r#"href="/users""#));
+    }
+
+    #[test]
+    fn bundled_templates_load() {
+        load(&templates_dir(), false).unwrap();
+    }
+
+    #[test]
+    fn every_bundled_template_is_required() {
+        for entry in fs::read_dir(templates_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_stem().unwrap().to_str().unwrap();
+
+            assert!(
+                REQUIRED_TEMPLATES.contains(&name),
+                "`{name}` is missing from REQUIRED_TEMPLATES"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_templates_are_reported() {
+        let dir = testing::TempDir::new();
+        for name in REQUIRED_TEMPLATES.iter().filter(|&&name| name != "login") {
+            dir.write(&format!("{name}.hbs"), "");
+        }
+
+        assert!(matches!(
+            load(dir.path(), false),
+            Err(LoadError::Missing(missing)) if missing == ["login"]
+        ));
+    }
+}
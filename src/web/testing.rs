@@ -0,0 +1,152 @@
+use super::{SESSION_COOKIE_NAME, SESSION_SWEEP_INTERVAL, i18n, middleware, session, template};
+use crate::core::{self, testing};
+use actix_web::{body, cookie, dev, http, test, web};
+use std::{fs, path};
+use tokio_util::sync;
+
+pub const USERNAME: &str = "Admin";
+pub const PASSWORD: &str = "Other-Pass-456";
+const PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$/YkQjpB8GRwC3q7vOKNnlg$WPvN/p8ukfRnWbE820eZsOh5rhVGNuFib6d0o7D+iIE";
+
+// The application state the routes need, backed by a temporary directory with
+// a single admin user. The stores run as tasks, so it has to be created inside
+// of an async test.
+pub struct TestEnv {
+    _dir: testing::TempDir,
+    _cancel: sync::DropGuard,
+    config: web::Data<core::AppConfig>,
+    templates: web::Data<handlebars::Handlebars<'static>>,
+    catalogs: web::Data<i18n::Catalogs>,
+    users: web::Data<session::UsersStore>,
+    audit_log: web::Data<core::AuditLog>,
+    session_store: session::SessionStore,
+    secret_key: cookie::Key,
+}
+
+impl TestEnv {
+    // The options are appended to the configuration file.
+    pub fn new(options: &str) -> Self {
+        let dir = testing::TempDir::new();
+        let worlds_path = dir.path().join("worlds");
+        fs::create_dir(&worlds_path).unwrap();
+        let users_path = dir.write(
+            "users.yml",
+            &format!("- username: {USERNAME}\n  password: {PASSWORD_HASH}\n  role: admin\n"),
+        );
+        let properties_path = dir.write(
+            "server.properties",
+            "rcon.port=25575\nrcon.password=secret\n",
+        );
+        let project_path = path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config_path = dir.write(
+            "config.yml",
+            &format!(
+                "listen_on: 127.0.0.1:0\n\
+                 base_url: http://localhost/\n\
+                 worlds_path: {}\n\
+                 users_file_path: {}\n\
+                 server_properties_path: {}\n\
+                 templates_path: {}\n\
+                 static_path: {}\n\
+                 locales_path: {}\n\
+                 {options}",
+                worlds_path.display(),
+                users_path.display(),
+                properties_path.display(),
+                project_path.join("templates").display(),
+                project_path.join("static").display(),
+                project_path.join("locales").display(),
+            ),
+        );
+        let config = core::Config::load(config_path).unwrap();
+
+        let catalogs = web::Data::new(
+            i18n::Catalogs::load(&config.locales_path, &config.default_locale).unwrap(),
+        );
+        let mut templates = template::load(&config.templates_path, false).unwrap();
+        templates.register_helper("t", Box::new(i18n::helper(catalogs.clone())));
+
+        let cancel = sync::CancellationToken::new();
+        let session_store = session::SessionStore::new(
+            None,
+            SESSION_SWEEP_INTERVAL,
+            config.remember_me_ttl.unsigned_abs(),
+            config.session_idle_timeout,
+            cancel.clone(),
+        );
+        let users = session::UsersStore::new(users_path, cancel.clone());
+
+        Self {
+            _dir: dir,
+            _cancel: cancel.drop_guard(),
+            audit_log: web::Data::new(core::AuditLog::new(None)),
+            config: web::Data::new(config.app_config),
+            templates: web::Data::new(templates),
+            catalogs,
+            users: web::Data::new(users),
+            session_store,
+            secret_key: cookie::Key::generate(),
+        }
+    }
+
+    // The same authentication, session and base path handling as the server,
+    // the routes are added by the tests.
+    pub fn app(
+        &self,
+    ) -> actix_web::App<
+        impl dev::ServiceFactory<
+            dev::ServiceRequest,
+            Config = (),
+            Response = dev::ServiceResponse<impl body::MessageBody + use<>>,
+            Error = actix_web::Error,
+            InitError = (),
+        > + use<>,
+    > {
+        let auth_config = self.config.clone();
+
+        actix_web::App::new()
+            .app_data(self.config.clone())
+            .app_data(self.templates.clone())
+            .app_data(self.catalogs.clone())
+            .app_data(self.users.clone())
+            .app_data(self.audit_log.clone())
+            .wrap(middleware::ConditionalMiddleware::new(
+                middleware::AuthMiddleware::<session::UserSession>::new("/login"),
+                move |req: &dev::ServiceRequest| super::requires_authentication(req, &auth_config),
+            ))
+            .wrap(
+                actix_session::SessionMiddleware::builder(
+                    self.session_store.clone(),
+                    self.secret_key.clone(),
+                )
+                .cookie_name(SESSION_COOKIE_NAME.to_string())
+                .build(),
+            )
+            .wrap(actix_web::middleware::from_fn(middleware::strip_base_path))
+    }
+}
+
+pub fn location<B>(res: &dev::ServiceResponse<B>) -> Option<&str> {
+    res.headers()
+        .get(http::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+}
+
+// The session cookie set by the response, if the session changed.
+pub fn session_cookie<B>(res: &dev::ServiceResponse<B>) -> Option<cookie::Cookie<'static>> {
+    res.response()
+        .cookies()
+        .find(|cookie| cookie.name() == SESSION_COOKIE_NAME)
+        .map(cookie::Cookie::into_owned)
+}
+
+pub fn login_request(session: Option<&cookie::Cookie<'static>>) -> test::TestRequest {
+    let request = test::TestRequest::post()
+        .uri("/login")
+        .set_form([("username", USERNAME), ("password", PASSWORD)]);
+
+    match session {
+        Some(session) => request.cookie(session.clone()),
+        None => request,
+    }
+}
@@ -0,0 +1,154 @@
+use crate::core;
+use actix_tls::accept::rustls_0_23;
+use actix_web::dev;
+use rustls::{server, sign};
+use std::{any, fs, io, path, sync};
+
+pub struct ClientCertificate {
+    pub subject: String,
+}
+
+// Makes the subject of a verified client certificate available to the request
+// handlers via `HttpRequest::conn_data`.
+pub fn client_certificate(connection: &dyn any::Any, data: &mut dev::Extensions) {
+    let stream = connection.downcast_ref::<rustls_0_23::TlsStream<actix_web::rt::net::TcpStream>>();
+
+    if let Some(cert) = stream
+        .and_then(|stream| stream.get_ref().1.peer_certificates())
+        .and_then(|certs| certs.first())
+    {
+        match x509_parser::parse_x509_certificate(cert) {
+            Ok((_, cert)) => {
+                let subject = cert.subject().to_string();
+                tracing::debug!("Client connected with a certificate for `{subject}`");
+
+                data.insert(ClientCertificate { subject });
+            }
+            Err(err) => tracing::warn!("Failed to parse the client certificate: {err}"),
+        }
+    }
+}
+
+// Serves the currently loaded certificate, which can be swapped out while the
+// server is running to pick up renewed certificates.
+#[derive(Debug)]
+pub struct CertResolver {
+    key: path::PathBuf,
+    chain: path::PathBuf,
+    current: sync::RwLock<sync::Arc<sign::CertifiedKey>>,
+}
+
+impl CertResolver {
+    fn new(key: path::PathBuf, chain: path::PathBuf) -> Result<Self, String> {
+        let current = load_certified_key(&key, &chain)?;
+
+        Ok(Self {
+            key,
+            chain,
+            current: sync::RwLock::new(sync::Arc::new(current)),
+        })
+    }
+
+    // The previous certificate stays active if the new one fails to load.
+    pub fn reload(&self) {
+        match load_certified_key(&self.key, &self.chain) {
+            Ok(certified_key) => {
+                *self
+                    .current
+                    .write()
+                    .unwrap_or_else(sync::PoisonError::into_inner) = sync::Arc::new(certified_key);
+
+                tracing::info!("Reloaded the TLS certificate");
+            }
+            Err(err) => tracing::error!("Failed to reload the TLS certificate: {err}"),
+        }
+    }
+}
+
+impl server::ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: server::ClientHello<'_>) -> Option<sync::Arc<sign::CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(sync::PoisonError::into_inner)
+                .clone(),
+        )
+    }
+}
+
+pub fn configure(
+    tls: core::TlsConfig,
+) -> Result<(rustls::ServerConfig, sync::Arc<CertResolver>), String> {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .map_err(|_| "Failed to install the default TLS provider to ring".to_string())?;
+
+    let config = rustls::ServerConfig::builder();
+    let config = match tls.client_ca {
+        Some(client_ca) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for cert in client_ca {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid client CA certificate: {e}"))?;
+            }
+
+            let verifier = server::WebPkiClientVerifier::builder(sync::Arc::new(roots))
+                .build()
+                .map_err(|e| format!("Failed to build the client certificate verifier: {e}"))?;
+
+            config.with_client_cert_verifier(verifier)
+        }
+        None => config.with_no_client_auth(),
+    };
+
+    let resolver = sync::Arc::new(CertResolver::new(tls.key, tls.chain)?);
+
+    Ok((config.with_cert_resolver(resolver.clone()), resolver))
+}
+
+fn load_certified_key(key: &path::Path, chain: &path::Path) -> Result<sign::CertifiedKey, String> {
+    let key_file = fs::File::open(key)
+        .map_err(|e| format!("Failed to open a private key file `{}`: {e}", key.display()))?;
+    let key_file = &mut io::BufReader::new(key_file);
+
+    let chain_file = fs::File::open(chain).map_err(|e| {
+        format!(
+            "Failed to open a certificate chain file `{}`: {e}",
+            chain.display()
+        )
+    })?;
+    let chain_file = &mut io::BufReader::new(chain_file);
+
+    let cert_chain = rustls_pemfile::certs(chain_file)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            format!(
+                "Failed to parse a certificate chain file `{}`: {e}",
+                chain.display()
+            )
+        })?;
+
+    let private_key = rustls_pemfile::private_key(key_file).map_err(|e| {
+        format!(
+            "Failed to parse a private key file `{}`: {e}",
+            key.display()
+        )
+    })?;
+    let private_key = private_key
+        .ok_or_else(|| format!("No keys found in a private key file `{}`", key.display()))?;
+
+    if cert_chain.is_empty() {
+        Err(format!(
+            "No certificates found in a certificate chain file `{}`",
+            chain.display()
+        ))
+    } else {
+        sign::CertifiedKey::from_der(
+            cert_chain,
+            private_key,
+            &rustls::crypto::ring::default_provider(),
+        )
+        .map_err(|e| format!("Invalid certificate/key pair: {e}"))
+    }
+}
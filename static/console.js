@@ -0,0 +1,39 @@
+// Runs console commands over a WebSocket when available, falling back to the
+// regular form submission otherwise. Only the output of the commands sent from
+// this page is shown, the server log is not streamed.
+(function () {
+  var form = document.getElementById("console");
+  var log = document.getElementById("console-log");
+  var input = form.querySelector("input[name=command]");
+  var scheme = window.location.protocol === "https:" ? "wss:" : "ws:";
+  var basePath = document.body.dataset.basePath || "";
+  var socket = new WebSocket(scheme + "//" + window.location.host + basePath + "/ws/console");
+
+  function append(line) {
+    log.hidden = false;
+    log.textContent += line + "\n";
+    log.scrollTop = log.scrollHeight;
+  }
+
+  socket.addEventListener("message", function (event) {
+    append(event.data);
+  });
+
+  socket.addEventListener("close", function () {
+    append("The console connection was closed, reload the page to reconnect.");
+  });
+
+  form.addEventListener("submit", function (event) {
+    if (socket.readyState !== WebSocket.OPEN) {
+      return;
+    }
+
+    event.preventDefault();
+
+    if (input.value.trim() !== "") {
+      append("> " + input.value);
+      socket.send(input.value);
+      input.value = "";
+    }
+  });
+})();
@@ -0,0 +1,15 @@
+// Appends new server log lines as they are written, when WebSockets are
+// available. Otherwise the page only shows the lines present when it loaded.
+(function () {
+  var log = document.getElementById("log-lines");
+  var scheme = window.location.protocol === "https:" ? "wss:" : "ws:";
+  var basePath = document.body.dataset.basePath || "";
+  var socket = new WebSocket(scheme + "//" + window.location.host + basePath + "/ws/logs");
+
+  log.scrollTop = log.scrollHeight;
+
+  socket.addEventListener("message", function (event) {
+    log.textContent += event.data + "\n";
+    log.scrollTop = log.scrollHeight;
+  });
+})();